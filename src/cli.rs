@@ -0,0 +1,367 @@
+//! Command-line interface definition.
+
+use crate::data::connectivity::DEFAULT_CHECK_URL;
+use crate::data::http::HttpUrl;
+use crate::data::port_probe::{self, parse_port_list};
+use crate::data::snmp::{self, parse_community_list};
+use crate::display::tree::{GroupBy, SortKey};
+use crate::display::{parse_device_template, parse_text_template, Template};
+use crate::domain::{parse_icon_override, DeviceType, IconTheme};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
+
+/// A whole port list per argument; clap would otherwise treat `Vec` as repeated values
+type PortList = Vec<u16>;
+
+/// A whole community list per argument, for the same reason
+type CommunityList = Vec<String>;
+
+/// LAN device monitor for Waybar
+#[derive(Debug, Parser)]
+#[command(name = "waybar_lan", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Status bar protocol used by the `bar` command
+    #[arg(long, value_enum, default_value_t = OutputBackend::Waybar, global = true)]
+    pub output: OutputBackend,
+
+    /// Keep running and print a new update every interval
+    #[arg(long, global = true)]
+    pub watch: bool,
+
+    /// Seconds between updates in watch mode
+    #[arg(long, default_value_t = 30, value_name = "SECONDS", global = true)]
+    pub interval: u64,
+
+    /// Serve Prometheus metrics on /metrics at this address (watch mode only)
+    #[arg(long, value_name = "ADDR", requires = "watch", global = true)]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Write metrics in node_exporter textfile-collector format after each scan
+    #[arg(long, value_name = "PATH", global = true)]
+    pub textfile: Option<PathBuf>,
+
+    /// Save each scan as versioned JSON export
+    #[arg(long, value_name = "PATH", global = true)]
+    pub export: Option<PathBuf>,
+
+    /// Load a snapshot from an export file instead of scanning
+    #[arg(long, value_name = "PATH", conflicts_with = "export", global = true)]
+    pub import: Option<PathBuf>,
+
+    /// Host checked for reachability beyond the gateway, e.g. "1.1.1.1:53" or "example.com"
+    /// The port defaults to 443
+    #[arg(long, value_name = "HOST[:PORT]", global = true)]
+    pub upstream: Option<String>,
+
+    /// Detect captive portals by requesting a "generate_204"-style URL (http:// only)
+    /// Without a value, Google's connectivity check URL is used
+    #[arg(long, value_name = "URL", num_args = 0..=1, default_missing_value = DEFAULT_CHECK_URL, global = true)]
+    pub connectivity_check: Option<HttpUrl>,
+
+    /// Probe devices announcing nothing over mDNS or SSDP for open TCP ports, e.g. "22,80,8000-8010"
+    /// Without a list, common ports are probed: 22,80,443,445,515,548,554,631,3389,8080,9100
+    #[arg(long, value_name = "PORTS", num_args = 0..=1, default_missing_value = port_probe::DEFAULT_PORTS,
+        value_parser = parse_port_list, global = true)]
+    pub probe_ports: Option<PortList>,
+
+    /// Port probe connections in flight at once
+    #[arg(long, value_name = "N", default_value_t = NonZeroUsize::new(port_probe::DEFAULT_CONCURRENCY).unwrap(),
+        global = true)]
+    pub probe_concurrency: NonZeroUsize,
+
+    /// Port probe connections started per second
+    #[arg(long, value_name = "N", default_value_t = NonZeroU32::new(port_probe::DEFAULT_RATE).unwrap(),
+        global = true)]
+    pub probe_rate: NonZeroU32,
+
+    /// Read HTTP page titles, Server headers and SSH versions to identify devices
+    #[arg(long, global = true)]
+    pub grab_banners: bool,
+
+    /// Ask printers for their state, ink and toner levels over IPP
+    #[arg(long, global = true)]
+    pub printer_status: bool,
+
+    /// Ask UPnP Internet gateways for the external address, uptime and port mappings
+    #[arg(long, global = true)]
+    pub igd: bool,
+
+    /// Ask the default gateway for its public address over NAT-PMP, or over PCP with a short-lived mapping
+    #[arg(long, global = true)]
+    pub nat_pmp: bool,
+
+    /// Query the SNMP v2c system group of every device, trying these communities in order
+    /// Without a list, "public" is tried
+    #[arg(long, value_name = "COMMUNITIES", num_args = 0..=1, default_missing_value = snmp::DEFAULT_COMMUNITY,
+        value_parser = parse_community_list, global = true)]
+    pub snmp: Option<CommunityList>,
+
+    /// Account traffic per device from /proc/net/nf_conntrack (for hosts routing the LAN)
+    /// Needs read access to the table and net.netfilter.nf_conntrack_acct=1
+    #[arg(long, global = true)]
+    pub conntrack: bool,
+
+    /// Directory holding the device history used to detect new devices
+    /// Defaults to $XDG_STATE_HOME/waybar_lan
+    #[arg(long, value_name = "DIR", global = true)]
+    pub state_dir: Option<PathBuf>,
+
+    /// Read /proc, /sys and /etc files below this directory instead of the live system
+    /// Skips mDNS, SSDP, Wi-Fi links, the ping sweep, reverse DNS and gateway probes,
+    /// and records nothing in the state directory unless --state-dir is given
+    #[arg(long, value_name = "DIR", global = true)]
+    pub sysroot: Option<PathBuf>,
+
+    /// Bar text template, e.g. "{icon} {count} ({new} new)"
+    /// Placeholders: icon, count, devices, active, new, gateway_latency, gateway_loss, upstream,
+    /// connectivity, by_type
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_text_template, global = true)]
+    pub text_template: Option<Template>,
+
+    /// Tooltip device line template, e.g. "{icon} {name} ({ip}) {latency}"
+    /// Placeholders: icon, name, ip, mac, latency, type, hostname, interface
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_device_template, global = true)]
+    pub device_template: Option<Template>,
+
+    /// Device (IP, MAC or full name) that should always be present; repeatable
+    /// Its absence sets the `critical-missing` class
+    #[arg(long = "critical", value_name = "DEVICE", global = true)]
+    pub critical_devices: Vec<String>,
+
+    /// Icon set: emoji, nerd-font or ascii
+    #[arg(long, value_name = "SET", default_value = "emoji", global = true)]
+    pub icons: IconTheme,
+
+    /// Override the icon for a device type, e.g. "nas=🗃"; repeatable
+    #[arg(long = "icon", value_name = "TYPE=ICON", value_parser = parse_icon_override, global = true)]
+    pub icon_overrides: Vec<(DeviceType, String)>,
+
+    /// Tooltip grouping: interface, type or status
+    #[arg(long, value_name = "GROUP", default_value = "interface", global = true)]
+    pub group_by: GroupBy,
+
+    /// Device order within a group: ip, name, last-seen or latency
+    #[arg(long, value_name = "KEY", default_value = "ip", global = true)]
+    pub sort_by: SortKey,
+
+    /// Maximum tooltip lines; the rest is summarized as "+N more devices"
+    #[arg(long, value_name = "LINES", global = true)]
+    pub max_lines: Option<NonZeroUsize>,
+
+    /// Hide devices not seen for over 30 minutes in the tooltip
+    #[arg(long, global = true)]
+    pub hide_stale: bool,
+
+    /// Hide devices without a resolved hostname or friendly name in the tooltip
+    #[arg(long, global = true)]
+    pub hide_unnamed: bool,
+}
+
+/// Status bar output backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputBackend {
+    /// Waybar custom module JSON
+    Waybar,
+    /// i3bar/swaybar JSON protocol
+    I3bar,
+    /// i3blocks full_text/short_text/color lines
+    I3blocks,
+    /// Polybar script with %{F} format tags
+    Polybar,
+    /// eww deflisten JSON stream
+    Eww,
+}
+
+/// Subcommands; running without one is the same as `bar`
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Print Waybar JSON (default)
+    Bar,
+    /// Print a table of discovered devices
+    List,
+    /// Print identity, services, UPnP info and evidence for one device
+    Show {
+        /// IP address, MAC address or name of the device
+        query: String,
+    },
+    /// Print the snapshot in the versioned JSON export format
+    Json,
+    /// Render the tooltip for a terminal
+    Tooltip,
+    /// Compare two exported snapshots by device MAC address
+    Diff {
+        /// Snapshot taken before the change
+        old: PathBuf,
+        /// Snapshot taken after the change
+        new: PathBuf,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
+    /// Pick a device and an action for it in a dmenu-compatible launcher
+    Menu {
+        /// Launcher command reading choices on stdin, e.g. "fuzzel --dmenu"
+        /// Defaults to the first of rofi, wofi, fuzzel or dmenu found on PATH
+        #[arg(long)]
+        launcher: Option<String>,
+    },
+    /// Send a Wake-on-LAN magic packet out of the interface the device was last seen on
+    Wake {
+        /// MAC address, IP address or name of a device in the device store
+        target: String,
+    },
+}
+
+/// Output formats for the `diff` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_no_subcommand() {
+        let cli = Cli::try_parse_from(["waybar_lan"]).unwrap();
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_show_requires_query() {
+        assert!(Cli::try_parse_from(["waybar_lan", "show"]).is_err());
+
+        let cli = Cli::try_parse_from(["waybar_lan", "show", "192.168.1.1"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Show { query }) if query == "192.168.1.1"));
+    }
+
+    #[test]
+    fn test_wake_target() {
+        assert!(Cli::try_parse_from(["waybar_lan", "wake"]).is_err());
+
+        let cli = Cli::try_parse_from(["waybar_lan", "wake", "nas"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Wake { target }) if target == "nas"));
+    }
+
+    #[test]
+    fn test_sysroot_is_global() {
+        let cli = Cli::try_parse_from(["waybar_lan", "list", "--sysroot", "tests/lan"]).unwrap();
+        assert_eq!(cli.sysroot, Some(PathBuf::from("tests/lan")));
+    }
+
+    #[test]
+    fn test_output_backend() {
+        let cli = Cli::try_parse_from(["waybar_lan"]).unwrap();
+        assert_eq!(cli.output, OutputBackend::Waybar);
+        assert!(!cli.watch);
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--output", "i3bar", "--watch", "--interval", "5"]).unwrap();
+        assert_eq!(cli.output, OutputBackend::I3bar);
+        assert!(cli.watch);
+        assert_eq!(cli.interval, 5);
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--output", "lemonbar"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_arguments() {
+        let cli = Cli::try_parse_from(["waybar_lan", "diff", "old.json", "new.json", "--format", "json"]).unwrap();
+        let Some(Command::Diff { old, new, format }) = cli.command else { panic!("expected diff") };
+
+        assert_eq!(old, PathBuf::from("old.json"));
+        assert_eq!(new, PathBuf::from("new.json"));
+        assert_eq!(format, DiffFormat::Json);
+    }
+
+    #[test]
+    fn test_metrics_addr_requires_watch() {
+        assert!(Cli::try_parse_from(["waybar_lan", "--metrics-addr", "127.0.0.1:9845"]).is_err());
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--watch", "--metrics-addr", "127.0.0.1:9845"]).unwrap();
+        assert_eq!(cli.metrics_addr, Some("127.0.0.1:9845".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_connectivity_check_url() {
+        let cli = Cli::try_parse_from(["waybar_lan"]).unwrap();
+        assert_eq!(cli.connectivity_check, None);
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--connectivity-check"]).unwrap();
+        assert_eq!(cli.connectivity_check, Some(DEFAULT_CHECK_URL.parse().unwrap()));
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--connectivity-check=http://10.0.0.1:8080/ok"]).unwrap();
+        assert_eq!(cli.connectivity_check.unwrap().port, 8080);
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--connectivity-check=https://example.com/"]).is_err());
+    }
+
+    #[test]
+    fn test_port_probe_arguments() {
+        let cli = Cli::try_parse_from(["waybar_lan"]).unwrap();
+        assert_eq!(cli.probe_ports, None);
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--probe-ports"]).unwrap();
+        assert_eq!(cli.probe_ports.unwrap().len(), 11);
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--probe-ports=22,8000-8001", "--probe-rate", "50"]).unwrap();
+        assert_eq!(cli.probe_ports, Some(vec![22, 8000, 8001]));
+        assert_eq!(cli.probe_rate.get(), 50);
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--probe-ports=ssh"]).is_err());
+        assert!(Cli::try_parse_from(["waybar_lan", "--probe-concurrency", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_snmp_communities() {
+        let cli = Cli::try_parse_from(["waybar_lan", "--snmp"]).unwrap();
+        assert_eq!(cli.snmp, Some(vec!["public".to_string()]));
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--snmp=public,office", "show", "switch"]).unwrap();
+        assert_eq!(cli.snmp, Some(vec!["public".to_string(), "office".to_string()]));
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--snmp="]).is_err());
+    }
+
+    #[test]
+    fn test_templates_validated_at_parse_time() {
+        let cli = Cli::try_parse_from(["waybar_lan", "--text-template", "{count} ({new} new)"]).unwrap();
+        assert_eq!(cli.text_template, Some(parse_text_template("{count} ({new} new)").unwrap()));
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--text-template", "{bogus}"]).is_err());
+        assert!(Cli::try_parse_from(["waybar_lan", "--device-template", "{count}"]).is_err());
+    }
+
+    #[test]
+    fn test_icon_arguments() {
+        let cli = Cli::try_parse_from(["waybar_lan"]).unwrap();
+        assert_eq!(cli.icons, IconTheme::Emoji);
+
+        let cli = Cli::try_parse_from(["waybar_lan", "--icons", "nerd-font", "--icon", "router=R"]).unwrap();
+        assert_eq!(cli.icons, IconTheme::NerdFont);
+        assert_eq!(cli.icon_overrides, vec![(DeviceType::Router, "R".to_string())]);
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--icons", "wingdings"]).is_err());
+        assert!(Cli::try_parse_from(["waybar_lan", "--icon", "toaster=T"]).is_err());
+    }
+
+    #[test]
+    fn test_tooltip_layout_arguments() {
+        let cli = Cli::try_parse_from(["waybar_lan", "--group-by", "type", "--sort-by", "last-seen",
+            "--max-lines", "20", "--hide-stale"]).unwrap();
+        assert_eq!(cli.group_by, GroupBy::DeviceType);
+        assert_eq!(cli.sort_by, SortKey::LastSeen);
+        assert_eq!(cli.max_lines, NonZeroUsize::new(20));
+        assert!(cli.hide_stale);
+        assert!(!cli.hide_unnamed);
+
+        assert!(Cli::try_parse_from(["waybar_lan", "--group-by", "vendor"]).is_err());
+        assert!(Cli::try_parse_from(["waybar_lan", "--max-lines", "0"]).is_err());
+    }
+}
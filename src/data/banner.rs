@@ -0,0 +1,167 @@
+//! HTTP title, Server header and SSH version grabbing for device identification.
+//!
+//! Only plain HTTP is fetched; HTTPS services are skipped as there is no TLS client,
+//! so devices that serve their web interface only over HTTPS get no HTTP banners.
+
+use crate::data::http::{self, DeadlineStream, HttpUrl};
+use crate::data::workers;
+use crate::domain::{BannerKind, NetworkDevice, ServiceBanner};
+use anyhow::{bail, Result};
+use std::io::{BufRead, BufReader, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// Time allowed for each grab, from connecting to the last byte
+pub const BANNER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// HTTP responses are cut off after this many bytes; titles sit near the top
+pub const MAX_PAGE_BYTES: u64 = 32 * 1024;
+
+/// Longest SSH identification line allowed by RFC 4253
+const MAX_SSH_LINE: u64 = 255;
+
+/// Banners are shortened to this many characters
+const MAX_BANNER_CHARS: usize = 80;
+
+/// Devices handled at once
+const WORKERS: usize = 16;
+
+/// Fetches `/` and returns the page title and Server header
+pub fn grab_http(ip: IpAddr, port: u16, timeout: Duration) -> Result<Vec<ServiceBanner>> {
+    let url: HttpUrl = format!("http://{}/", SocketAddr::new(ip, port)).parse()?;
+    let response = http::get_limited(&url, timeout, MAX_PAGE_BYTES)?;
+
+    let mut banners = Vec::new();
+    if let Some(title) = extract_title(&String::from_utf8_lossy(&response.body)) {
+        banners.push(ServiceBanner { port, kind: BannerKind::HttpTitle, text: title });
+    }
+    if let Some(server) = response.header("Server").map(clean).filter(|server| !server.is_empty()) {
+        banners.push(ServiceBanner { port, kind: BannerKind::HttpServer, text: server });
+    }
+    Ok(banners)
+}
+
+/// Reads the identification line an SSH server sends on connect
+pub fn grab_ssh(ip: IpAddr, port: u16, timeout: Duration) -> Result<ServiceBanner> {
+    let addr = SocketAddr::new(ip, port);
+    let stream = DeadlineStream::connect(&addr, timeout)?;
+
+    let mut line = String::new();
+    BufReader::new(stream.take(MAX_SSH_LINE)).read_line(&mut line)?;
+    // "SSH-2.0-OpenSSH_9.6p1 Debian-3" -> "OpenSSH_9.6p1 Debian-3"
+    let Some(version) = line.strip_prefix("SSH-").and_then(|rest| rest.split_once('-')) else {
+        bail!("No SSH identification from {}", addr);
+    };
+    Ok(ServiceBanner { port, kind: BannerKind::SshVersion, text: clean(version.1) })
+}
+
+/// Text of the first `<title>` element, with entities decoded and whitespace collapsed
+pub fn extract_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = clean(&decode_entities(&html[start..end]));
+    (!title.is_empty()).then_some(title)
+}
+
+/// Decodes the named entities common in HTML and XML text
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Collapses whitespace, drops control characters and limits the length
+fn clean(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_BANNER_CHARS)
+        .collect()
+}
+
+/// Banners from a device's HTTP and SSH services; failures are skipped
+fn device_banners(device: &NetworkDevice) -> Vec<ServiceBanner> {
+    let mut banners = Vec::new();
+    for service in &device.services {
+        let service_type = service.service_type.as_str();
+        if service_type.starts_with("_http._tcp") {
+            banners.extend(grab_http(device.ip, service.port, BANNER_TIMEOUT).unwrap_or_default());
+        } else if service_type.starts_with("_ssh._tcp") {
+            banners.extend(grab_ssh(device.ip, service.port, BANNER_TIMEOUT).ok());
+        }
+    }
+    banners.dedup();
+    banners
+}
+
+/// Grabs banners for all devices, a few devices at a time
+pub fn grab_banners(devices: &mut [NetworkDevice]) {
+    workers::for_each_device(devices, WORKERS, |device| device.banners = device_banners(device));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DeviceType, InterfaceName, MacAddress, ServiceInfo, ServiceInstanceName, ServiceType};
+
+    #[test]
+    fn test_extract_title() {
+        assert_eq!(extract_title("<html><HEAD><title>\n  Synology&nbsp;DiskStation </TITLE>"),
+            Some("Synology DiskStation".to_string()));
+        assert_eq!(extract_title("<title lang=\"en\">Tom &amp; Jerry</title>"), Some("Tom & Jerry".to_string()));
+        assert_eq!(extract_title("<title></title>"), None);
+        assert_eq!(extract_title("<h1>No title</h1>"), None);
+    }
+
+    #[test]
+    fn test_grab_from_local_stand_ins() {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+
+        let http_port = http::serve_once("HTTP/1.1 200 OK\r\nServer: lighttpd/1.4.69\r\nContent-Length: 45\r\n\r\n\
+            <html><title>Pi-hole - pi.hole</title></html>", true);
+        let banners = grab_http(localhost, http_port, BANNER_TIMEOUT).unwrap();
+        assert_eq!(banners, vec![
+            ServiceBanner { port: http_port, kind: BannerKind::HttpTitle, text: "Pi-hole - pi.hole".to_string() },
+            ServiceBanner { port: http_port, kind: BannerKind::HttpServer, text: "lighttpd/1.4.69".to_string() },
+        ]);
+
+        let ssh_port = http::serve_once("SSH-2.0-OpenSSH_9.6p1 Debian-3\r\n", false);
+        let banner = grab_ssh(localhost, ssh_port, BANNER_TIMEOUT).unwrap();
+        assert_eq!(banner.text, "OpenSSH_9.6p1 Debian-3");
+
+        let not_ssh = http::serve_once("220 FTP ready\r\n", false);
+        assert!(grab_ssh(localhost, not_ssh, BANNER_TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn test_banners_feed_identity() {
+        let http_port = http::serve_once("HTTP/1.1 200 OK\r\nContent-Length: 37\r\n\r\n\
+            <title>Synology DiskStation</title>\r\n", true);
+        let mut device = NetworkDevice::new(
+            IpAddr::from([127, 0, 0, 1]),
+            MacAddress::new("AA:BB:CC:DD:EE:10".to_string()).unwrap(),
+            InterfaceName::new("lo".to_string()),
+        );
+        device.services.push(ServiceInfo::new(
+            ServiceType::new("_http._tcp.local.".to_string()),
+            ServiceInstanceName::new("port probe".to_string()),
+            http_port,
+        ));
+
+        let mut devices = vec![device];
+        grab_banners(&mut devices);
+        devices[0].build_identity();
+
+        assert_eq!(devices[0].identity.device_type, DeviceType::NAS);
+        assert!(devices[0].identity.evidence.iter()
+            .any(|e| e.detail == format!("port {} title \"Synology DiskStation\"", http_port)));
+    }
+}
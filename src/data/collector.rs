@@ -1,44 +1,236 @@
 //! Network data collection from system interfaces.
 
-use crate::domain::{NetworkData, NetworkSnapshot, UpnpInfo, FriendlyName, ManufacturerName, ModelName, DeviceTypeName};
-use crate::data::{mdns_discovery::MdnsDiscovery, proc_parsers, ssdp_discovery::SsdpDiscovery};
+use crate::domain::{
+    ConnectivityStatus, DeviceTypeName, DiscoverySource, FriendlyName, ManufacturerName, ModelName, NetworkData,
+    NetworkSnapshot, ScanMetadata, ServiceInfo, SourceReport, UpnpInfo,
+};
+use crate::data::{
+    banner, connectivity, health, http::HttpUrl, igd, ipp, nat_pmp, port_probe::{self, PortProbe},
+    proc_parsers, snmp::SnmpProbe,
+    sources::{
+        InterfaceSource, LiveMdns, MdnsSource, NeighborSource, ProcInterfaces, ProcNeighbors, ProcRoutes,
+        ResolvConf, ResolverSource, RouteSource, SsdpSource, SystemInterfaces,
+    },
+    ssdp_discovery::{SsdpDiscovery, UpnpDeviceInfo}, wifi,
+};
 use anyhow::Result;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Collector reading files below a root directory, with no mDNS or SSDP discovery
+pub type SysrootCollector = NetworkCollector<
+    ProcInterfaces, ProcNeighbors, ProcRoutes, ResolvConf,
+    HashMap<IpAddr, Vec<ServiceInfo>>, HashMap<IpAddr, UpnpDeviceInfo>,
+>;
 
 /// Collects network information from local system
-pub struct NetworkCollector;
+#[derive(Default)]
+pub struct NetworkCollector<
+    I = SystemInterfaces, N = ProcNeighbors, R = ProcRoutes, D = ResolvConf, M = LiveMdns, S = SsdpDiscovery,
+> {
+    interface_source: I,
+    neighbor_source: N,
+    route_source: R,
+    resolver_source: D,
+    mdns_source: M,
+    ssdp_source: S,
+    /// Skip Wi-Fi link queries, the ping sweep, reverse DNS and gateway probes
+    without_live_probes: bool,
+    /// Host checked for reachability beyond the gateway
+    upstream: Option<String>,
+    /// URL requested to detect captive portals
+    connectivity_url: Option<HttpUrl>,
+    /// TCP ports probed on silent devices, off unless configured
+    port_probe: Option<PortProbe>,
+    /// Fetch HTTP titles and SSH versions for identification
+    grab_banners: bool,
+    /// Ask printers for their state and supply levels over IPP
+    query_printers: bool,
+    /// Communities for SNMP system group queries, off unless configured
+    snmp: Option<SnmpProbe>,
+    /// Read UPnP descriptions and the WAN status of Internet gateways
+    query_gateways: bool,
+    /// Ask the default gateway for its external address over NAT-PMP or PCP
+    query_nat_pmp: bool,
+}
 
 impl NetworkCollector {
     /// Creates a new NetworkCollector instance
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self::default())
+    }
+}
+
+impl SysrootCollector {
+    /// Reads interfaces, neighbors, routes and resolver settings below `root` instead of `/`
+    /// Nothing is sent on the network unless opt-in steps are enabled
+    pub fn from_sysroot(root: &Path) -> Self {
+        NetworkCollector::from_sources(
+            ProcInterfaces::new(root),
+            ProcNeighbors::new(root),
+            ProcRoutes::new(root),
+            ResolvConf::new(root),
+            HashMap::new(),
+            HashMap::new(),
+        )
+        .without_live_probes()
+    }
+}
+
+impl<I, N, R, D, M, S> NetworkCollector<I, N, R, D, M, S> {
+    /// Creates a collector reading from the given sources
+    pub fn from_sources(interface_source: I, neighbor_source: N, route_source: R,
+        resolver_source: D, mdns_source: M, ssdp_source: S) -> Self {
+        Self {
+            interface_source,
+            neighbor_source,
+            route_source,
+            resolver_source,
+            mdns_source,
+            ssdp_source,
+            without_live_probes: false,
+            upstream: None,
+            connectivity_url: None,
+            port_probe: None,
+            grab_banners: false,
+            query_printers: false,
+            snmp: None,
+            query_gateways: false,
+            query_nat_pmp: false,
+        }
+    }
+
+    /// Skips the steps that probe the live system beyond the sources
+    /// Wi-Fi links, the ping sweep, reverse DNS and gateway health are left out
+    pub fn without_live_probes(mut self) -> Self {
+        self.without_live_probes = true;
+        self
+    }
+
+    /// Also checks that this host ("host[:port]") accepts connections
+    pub fn with_upstream(mut self, target: String) -> Self {
+        self.upstream = Some(target);
+        self
+    }
+
+    /// Also requests this URL to tell open internet access from a captive portal
+    pub fn with_connectivity_check(mut self, url: HttpUrl) -> Self {
+        self.connectivity_url = Some(url);
+        self
+    }
+
+    /// Also probes devices that announce nothing for open TCP ports and records them as services
+    pub fn with_port_probe(mut self, probe: PortProbe) -> Self {
+        self.port_probe = Some(probe);
+        self
+    }
+
+    /// Also connects to HTTP and SSH services to read their titles and versions
+    pub fn with_banner_grabbing(mut self) -> Self {
+        self.grab_banners = true;
+        self
+    }
+
+    /// Also asks devices announcing IPP or LPD for printer state and supply levels
+    pub fn with_printer_status(mut self) -> Self {
+        self.query_printers = true;
+        self
+    }
+
+    /// Also queries every device's SNMP system group for its name, description and vendor
+    pub fn with_snmp(mut self, probe: SnmpProbe) -> Self {
+        self.snmp = Some(probe);
+        self
+    }
+
+    /// Also reads UPnP device descriptions, and the external address and port mappings of gateways
+    pub fn with_gateway_queries(mut self) -> Self {
+        self.query_gateways = true;
+        self
     }
 
+    /// Also asks the default gateway for its external address and epoch over NAT-PMP or PCP
+    pub fn with_nat_pmp(mut self) -> Self {
+        self.query_nat_pmp = true;
+        self
+    }
+}
+
+impl<I, N, R, D, M, S> NetworkCollector<I, N, R, D, M, S>
+where
+    I: InterfaceSource,
+    N: NeighborSource,
+    R: RouteSource,
+    D: ResolverSource,
+    M: MdnsSource,
+    S: SsdpSource,
+{
     /// Collects current network information snapshot
+    /// Every discovery step is timed and recorded in the snapshot's scan metadata
     pub fn collect_network_info(&self) -> Result<NetworkData> {
+        let started_at = SystemTime::now();
+        let scan_start = Instant::now();
+        let mut reports = Vec::new();
+
         // Get all network interfaces
-        let interfaces = proc_parsers::get_network_interfaces()?;
+        let interfaces = timed(&mut reports, DiscoverySource::Interfaces,
+            || self.interface_source.interfaces())?;
+
+        // Add link details to wireless interfaces, one interface failing does not affect the others
+        let has_wireless = interfaces.iter().any(|interface| interface.name.is_wireless());
+        let interfaces = if has_wireless && !self.without_live_probes {
+            let start = Instant::now();
+            let (interfaces, error) = wifi::add_wifi_links(interfaces, wifi::read_wifi_link);
+            reports.push(SourceReport {
+                source: DiscoverySource::Wifi,
+                duration: start.elapsed(),
+                error,
+            });
+            interfaces
+        } else {
+            interfaces
+        };
 
         // Perform ping sweep to populate ARP table with all active devices
-        // This spawns concurrent ping processes for the entire subnet
-        proc_parsers::ping_sweep_subnet(&interfaces)?;
+        // Every ping has finished before the table is read, so no host is missed
+        let round_trip_times = match self.without_live_probes {
+            true => HashMap::new(),
+            false => timed(&mut reports, DiscoverySource::PingSweep,
+                || Ok(proc_parsers::ping_sweep_subnet(&interfaces)))?,
+        };
 
         // Get devices from ARP table (now populated by ping sweep)
-        let devices = proc_parsers::parse_arp_table()?;
+        let devices = timed(&mut reports, DiscoverySource::Arp, || self.neighbor_source.neighbors())?;
 
         // Discover mDNS services (with 3 second timeout to catch all responses)
-        let mdns_services = MdnsDiscovery::new()
-            .and_then(|discovery| discovery.discover_services(Duration::from_secs(3)))
+        let mdns_services = timed(&mut reports, DiscoverySource::Mdns,
+            || self.mdns_source.discover_services(Duration::from_secs(3)))
             .unwrap_or_default();
 
         // Discover SSDP/UPnP devices (with 2 second timeout)
-        let ssdp_devices = SsdpDiscovery::new()
-            .discover_devices(Duration::from_secs(2))
+        let ssdp_devices = timed(&mut reports, DiscoverySource::Ssdp,
+            || self.ssdp_source.discover_devices(Duration::from_secs(2)))
             .unwrap_or_default();
 
+        // Probe silent devices for well-known TCP ports
+        // Devices announcing services over mDNS or SSDP already say what they are
+        let open_ports = match &self.port_probe {
+            Some(probe) => {
+                let hosts: Vec<_> = devices
+                    .iter()
+                    .map(|device| device.ip)
+                    .filter(|ip| !mdns_services.contains_key(ip) && !ssdp_devices.contains_key(ip))
+                    .collect();
+                timed(&mut reports, DiscoverySource::PortProbe, || Ok(probe.scan(&hosts)))?
+            }
+            None => std::collections::HashMap::new(),
+        };
+
         // Enrich devices with mDNS and UPnP information
         // Extract mDNS instance names for later hostname priority decision
-        let (devices, mdns_names) = devices.into_iter().fold(
+        let (mut devices, mdns_names) = devices.into_iter().fold(
             (Vec::new(), std::collections::HashMap::new()),
             |(mut enriched, mut names), mut device| {
                 // Add mDNS services and extract instance name
@@ -55,6 +247,12 @@ impl NetworkCollector {
                     }
                 }
 
+                // Add services found by the port probe that were not announced
+                if let Some(ports) = open_ports.get(&device.ip) {
+                    let probed = port_probe::probed_services(ports, &device.services);
+                    device.services.extend(probed);
+                }
+
                 // Add UPnP device info
                 if let Some(upnp_device_info) = ssdp_devices.get(&device.ip) {
                     device.upnp_info = Some(UpnpInfo {
@@ -62,6 +260,7 @@ impl NetworkCollector {
                         manufacturer: upnp_device_info.manufacturer.as_ref().map(|s| ManufacturerName::new(s.clone())),
                         model_name: upnp_device_info.model_name.as_ref().map(|s| ModelName::new(s.clone())),
                         device_type: upnp_device_info.device_type.as_ref().map(|s| DeviceTypeName::new(s.clone())),
+                        location: upnp_device_info.location.clone(),
                     });
                     device.update_last_seen();
                 }
@@ -71,22 +270,56 @@ impl NetworkCollector {
             },
         );
 
+        // Read titles and versions of the services found so far
+        if self.grab_banners {
+            timed(&mut reports, DiscoverySource::Banners, || {
+                banner::grab_banners(&mut devices);
+                Ok(())
+            })?;
+        }
+        if self.query_printers {
+            timed(&mut reports, DiscoverySource::Ipp, || {
+                ipp::query_printers(&mut devices);
+                Ok(())
+            })?;
+        }
+        if let Some(probe) = &self.snmp {
+            timed(&mut reports, DiscoverySource::Snmp, || {
+                probe.query_devices(&mut devices);
+                Ok(())
+            })?;
+        }
+        // A gateway that cannot be read is reported without aborting the scan
+        if self.query_gateways {
+            let start = Instant::now();
+            let error = igd::query_gateways(&mut devices);
+            reports.push(SourceReport {
+                source: DiscoverySource::Igd,
+                duration: start.elapsed(),
+                error,
+            });
+        }
+
         // Perform reverse DNS lookups in parallel and apply hostname priority logic
-        // Priority: UPnP friendly_name > DNS > mDNS instance name > Unknown
-        let devices = {
+        // Priority: UPnP friendly_name > DNS > mDNS instance name > SNMP sysName > Unknown
+        let reverse_dns_start = Instant::now();
+        let devices: Vec<_> = {
             let device_ips: Vec<_> = devices.iter().map(|d| d.ip).collect();
 
-            let dns_results: Vec<_> = std::thread::scope(|s| {
-                device_ips
-                    .iter()
-                    .map(|ip| {
-                        s.spawn(move || proc_parsers::reverse_dns_lookup(ip))
-                    })
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .map(|handle| handle.join().unwrap_or(crate::domain::Hostname::Unknown))
-                    .collect()
-            });
+            let dns_results: Vec<_> = match self.without_live_probes {
+                true => vec![crate::domain::Hostname::Unknown; device_ips.len()],
+                false => std::thread::scope(|s| {
+                    device_ips
+                        .iter()
+                        .map(|ip| {
+                            s.spawn(move || proc_parsers::reverse_dns_lookup(ip))
+                        })
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .map(|handle| handle.join().unwrap_or(crate::domain::Hostname::Unknown))
+                        .collect()
+                }),
+            };
 
             devices
                 .into_iter()
@@ -107,6 +340,8 @@ impl NetworkCollector {
                         dns_hostname
                     } else if let Some(mdns_name) = mdns_names.get(&device.ip) {
                         crate::domain::Hostname::resolved(mdns_name.clone())
+                    } else if let Some(sys_name) = device.snmp.as_ref().and_then(|snmp| snmp.sys_name.clone()) {
+                        crate::domain::Hostname::resolved(sys_name)
                     } else {
                         crate::domain::Hostname::Unknown
                     };
@@ -118,20 +353,83 @@ impl NetworkCollector {
                 .collect()
         };
 
+        if !self.without_live_probes {
+            reports.push(SourceReport {
+                source: DiscoverySource::ReverseDns,
+                duration: reverse_dns_start.elapsed(),
+                error: None,
+            });
+        }
+
+        // Round-trip times from the ping sweep
+        let devices = devices
+            .into_iter()
+            .map(|mut device| {
+                device.latency = round_trip_times.get(&device.ip).copied();
+                device
+            })
+            .collect();
+
         // Get default gateway
-        let gateway = proc_parsers::parse_default_gateway()?;
+        let gateway = timed(&mut reports, DiscoverySource::Route, || self.route_source.default_gateway())?;
 
         // Get DNS servers
-        let dns_servers = proc_parsers::parse_dns_servers().unwrap_or_default();
+        let dns_servers = timed(&mut reports, DiscoverySource::ResolvConf, || self.resolver_source.dns_servers())
+            .unwrap_or_default();
+
+        // Probe the gateway and the upstream host
+        // A failed ping is reported on its own and does not skip the upstream check
+        let gateway_health = gateway.filter(|_| !self.without_live_probes).map(|gateway| {
+            let stats = timed(&mut reports, DiscoverySource::GatewayHealth,
+                || health::ping_host(gateway.0, health::GATEWAY_PROBES))
+                .ok();
+            health::gateway_health(stats, self.upstream.as_deref())
+        });
+
+        // Ask the gateway for its public address
+        let gateway_nat = gateway.filter(|_| self.query_nat_pmp).and_then(|gateway| {
+            timed(&mut reports, DiscoverySource::NatPmp,
+                || nat_pmp::query_gateway(gateway.0, nat_pmp::NAT_PMP_PORT, nat_pmp::NAT_TIMEOUT))
+                .ok()
+        });
+
+        // Look for a captive portal between us and the internet
+        // A portal is a finding, not an error; only a failed request is reported as one
+        let connectivity = self.connectivity_url.as_ref().map(|url| {
+            let start = Instant::now();
+            let check = connectivity::check_connectivity(url);
+            reports.push(SourceReport {
+                source: DiscoverySource::Connectivity,
+                duration: start.elapsed(),
+                error: check.detail.clone().filter(|_| check.status == ConnectivityStatus::Offline),
+            });
+            check
+        });
 
-        Ok(NetworkSnapshot::new(interfaces, devices, gateway, dns_servers))
+        let scan = ScanMetadata {
+            started_at,
+            duration: scan_start.elapsed(),
+            sources: reports,
+        };
+        Ok(NetworkSnapshot::new(interfaces, devices, gateway, dns_servers)
+            .with_scan(scan)
+            .with_gateway_health(gateway_health)
+            .with_gateway_nat(gateway_nat)
+            .with_connectivity(connectivity))
     }
 }
 
-impl Default for NetworkCollector {
-    fn default() -> Self {
-        Self
-    }
+/// Runs one discovery step, recording its duration and any error
+fn timed<T>(reports: &mut Vec<SourceReport>, source: DiscoverySource,
+    step: impl FnOnce() -> Result<T>) -> Result<T> {
+    let start = Instant::now();
+    let result = step();
+    reports.push(SourceReport {
+        source,
+        duration: start.elapsed(),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+    });
+    result
 }
 
 #[cfg(test)]
@@ -160,5 +458,111 @@ mod tests {
         if let Some(gw) = snapshot.gateway {
             println!("Gateway: {}", gw);
         }
+
+        let scan = snapshot.scan.expect("collector records scan metadata");
+        assert!(scan.sources.iter().any(|r| r.source == DiscoverySource::Arp));
+    }
+
+    #[test]
+    fn test_collect_from_fixture_tree() {
+        use crate::data::sources::fixture_root;
+        use crate::domain::{Hostname, ServiceInstanceName, ServiceType};
+
+        let nas = IpAddr::from([192, 168, 1, 10]);
+        let tv = IpAddr::from([192, 168, 1, 30]);
+        let mdns = HashMap::from([(nas, vec![ServiceInfo::new(
+            ServiceType::new("_smb._tcp.local.".to_string()),
+            ServiceInstanceName::new("diskstation._smb._tcp.local.".to_string()),
+            445,
+        )])]);
+        let ssdp = HashMap::from([(tv, UpnpDeviceInfo {
+            friendly_name: Some("Living Room TV".to_string()),
+            ..UpnpDeviceInfo::new()
+        })]);
+
+        let root = fixture_root();
+        let collector = NetworkCollector::from_sources(ProcInterfaces::new(&root), ProcNeighbors::new(&root),
+            ProcRoutes::new(&root), ResolvConf::new(&root), mdns, ssdp)
+            .without_live_probes();
+        let snapshot = collector.collect_network_info().unwrap();
+
+        assert_eq!(snapshot.interfaces.len(), 2);
+        assert_eq!(snapshot.gateway.map(|gateway| gateway.0), Some(IpAddr::from([192, 168, 1, 1])));
+        assert_eq!(snapshot.dns_servers, vec![IpAddr::from([192, 168, 1, 1])]);
+
+        let hostnames: Vec<_> = snapshot.devices.iter().map(|device| (device.ip, device.hostname.clone())).collect();
+        assert_eq!(hostnames, vec![
+            (IpAddr::from([192, 168, 1, 1]), Hostname::Unknown),
+            (nas, Hostname::resolved("diskstation".to_string())),
+            (tv, Hostname::resolved("Living Room TV".to_string())),
+        ]);
+
+        // Only the sources ran; nothing probed the live network
+        let sources: Vec<_> = snapshot.scan.unwrap().sources.iter().map(|report| report.source).collect();
+        assert_eq!(sources, vec![
+            DiscoverySource::Interfaces, DiscoverySource::Arp, DiscoverySource::Mdns,
+            DiscoverySource::Ssdp, DiscoverySource::Route, DiscoverySource::ResolvConf,
+        ]);
+
+        let snapshot = SysrootCollector::from_sysroot(&root).collect_network_info().unwrap();
+        assert_eq!(snapshot.devices.len(), 3);
+        assert!(snapshot.gateway_health.is_none());
+    }
+
+    #[test]
+    fn test_port_probe_skips_announcing_devices() {
+        use crate::data::sources::fixture_root;
+        use crate::domain::{InterfaceName, MacAddress, NetworkDevice, ServiceInstanceName, ServiceType};
+        use std::net::TcpListener;
+
+        struct Neighbors(Vec<NetworkDevice>);
+        impl NeighborSource for Neighbors {
+            fn neighbors(&self) -> Result<Vec<NetworkDevice>> {
+                Ok(self.0.clone())
+            }
+        }
+
+        // Every loopback address reaches a listener on the unspecified address
+        let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let [announcing, upnp, silent] = [1, 2, 3].map(|i| IpAddr::from([127, 0, 0, i]));
+        let neighbors = Neighbors([announcing, upnp, silent].iter().enumerate()
+            .map(|(i, ip)| NetworkDevice::new(
+                *ip,
+                MacAddress::new(format!("AA:BB:CC:DD:EE:{:02X}", i)).unwrap(),
+                InterfaceName::new("lo".to_string()),
+            ))
+            .collect());
+        let mdns = HashMap::from([(announcing, vec![ServiceInfo::new(
+            ServiceType::new("_smb._tcp.local.".to_string()),
+            ServiceInstanceName::new("diskstation._smb._tcp.local.".to_string()),
+            445,
+        )])]);
+        let ssdp = HashMap::from([(upnp, UpnpDeviceInfo::new())]);
+
+        let root = fixture_root();
+        let collector = NetworkCollector::from_sources(ProcInterfaces::new(&root), neighbors,
+            ProcRoutes::new(&root), ResolvConf::new(&root), mdns, ssdp)
+            .without_live_probes()
+            .with_port_probe(PortProbe::new(vec![port]));
+        let snapshot = collector.collect_network_info().unwrap();
+
+        let ports: Vec<_> = snapshot.devices.iter()
+            .map(|device| (device.ip, device.services.iter().map(|service| service.port).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(ports, vec![(announcing, vec![445]), (upnp, vec![]), (silent, vec![port])]);
+    }
+
+    #[test]
+    fn test_timed_records_errors() {
+        let mut reports = Vec::new();
+        let ok = timed(&mut reports, DiscoverySource::Route, || Ok(1));
+        let err: Result<()> = timed(&mut reports, DiscoverySource::Mdns, || anyhow::bail!("no multicast"));
+
+        assert_eq!(ok.unwrap(), 1);
+        assert!(err.is_err());
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].error, None);
+        assert_eq!(reports[1].error.as_deref(), Some("no multicast"));
     }
 }
@@ -0,0 +1,84 @@
+//! Captive portal detection via a "generate_204"-style check URL.
+
+use crate::data::http::{self, HttpResponse, HttpUrl};
+use crate::domain::{ConnectivityCheck, ConnectivityStatus};
+use anyhow::Result;
+use std::time::Duration;
+
+/// Check URL used when `--connectivity-check` is given without one
+pub const DEFAULT_CHECK_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+
+/// Time allowed for the check request
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Classifies the outcome of a check request
+/// The open internet answers 204 (or 200 with an empty body); a portal
+/// redirects to its login page or serves a page of its own instead.
+pub fn classify(url: &HttpUrl, result: Result<HttpResponse>) -> ConnectivityCheck {
+    let (status, portal_url, detail) = match result {
+        Err(e) => (ConnectivityStatus::Offline, None, Some(format!("{:#}", e))),
+        Ok(response) => match response.status {
+            204 => (ConnectivityStatus::Online, None, None),
+            200 if response.body.iter().all(u8::is_ascii_whitespace) => (ConnectivityStatus::Online, None, None),
+            300..=399 => (
+                ConnectivityStatus::Captive,
+                response.header("Location").map(str::to_string),
+                Some(format!("Redirected with HTTP {}", response.status)),
+            ),
+            code => (
+                ConnectivityStatus::Captive,
+                None,
+                Some(format!("Unexpected HTTP {} with {} byte body", code, response.body.len())),
+            ),
+        },
+    };
+
+    ConnectivityCheck {
+        url: url.to_string(),
+        status,
+        portal_url,
+        detail,
+    }
+}
+
+/// Requests the check URL and classifies the answer
+pub fn check_connectivity(url: &HttpUrl) -> ConnectivityCheck {
+    classify(url, http::get(url, CHECK_TIMEOUT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn stand_in(response: &'static str) -> HttpUrl {
+        format!("http://127.0.0.1:{}/generate_204", http::serve_once(response, true)).parse().unwrap()
+    }
+
+    #[test]
+    fn test_check_against_local_stand_in() {
+        let online = check_connectivity(&stand_in("HTTP/1.1 204 No Content\r\n\r\n"));
+        assert_eq!(online.status, ConnectivityStatus::Online);
+
+        let redirect = check_connectivity(&stand_in(
+            "HTTP/1.1 302 Found\r\nLocation: http://portal.example/login\r\nContent-Length: 0\r\n\r\n"));
+        assert_eq!(redirect.status, ConnectivityStatus::Captive);
+        assert_eq!(redirect.portal_url.as_deref(), Some("http://portal.example/login"));
+
+        let login_page = check_connectivity(&stand_in(
+            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n<h1>Login</h1>"));
+        assert_eq!(login_page.status, ConnectivityStatus::Captive);
+        assert_eq!(login_page.portal_url, None);
+    }
+
+    #[test]
+    fn test_unreachable_check_url_is_offline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: HttpUrl = format!("http://{}/generate_204", listener.local_addr().unwrap()).parse().unwrap();
+        drop(listener);
+
+        let check = check_connectivity(&url);
+        assert_eq!(check.status, ConnectivityStatus::Offline);
+        assert!(check.detail.is_some());
+    }
+}
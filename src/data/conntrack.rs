@@ -0,0 +1,274 @@
+//! Per-device traffic accounting from the netfilter connection tracking table.
+//!
+//! Byte and packet counters are only present when accounting is enabled
+//! (`sysctl net.netfilter.nf_conntrack_acct=1`); flows without them are skipped.
+
+use crate::data::state_file;
+use crate::data::traffic::MAX_SAMPLE_AGE;
+use crate::domain::{DeviceTraffic, NetworkData, Throughput};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Location of the connection tracking table, relative to the root directory
+pub const CONNTRACK_PATH: &str = "proc/net/nf_conntrack";
+
+/// Addresses and counters of one direction of a flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowDirection {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// A tracked connection with its original and reply directions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// Protocol and original-direction tuple, stable for the life of the connection
+    pub key: String,
+    pub original: FlowDirection,
+    pub reply: FlowDirection,
+}
+
+/// Parses /proc/net/nf_conntrack
+///
+/// ```text
+/// ipv4 2 tcp 6 431999 ESTABLISHED src=192.168.1.20 dst=93.184.216.34 sport=51234 dport=443 packets=120 bytes=9600
+///     src=93.184.216.34 dst=203.0.113.5 sport=443 dport=51234 packets=300 bytes=4200000 [ASSURED] mark=0 use=2
+/// ```
+pub fn parse_conntrack(content: &str) -> Vec<Flow> {
+    content.lines().filter_map(parse_flow).collect()
+}
+
+fn parse_flow(line: &str) -> Option<Flow> {
+    let protocol = line.split_whitespace().nth(2)?;
+
+    // Each direction starts at its own src= field
+    let mut directions: Vec<Vec<(&str, &str)>> = Vec::new();
+    for (key, value) in line.split_whitespace().filter_map(|field| field.split_once('=')) {
+        if key == "src" {
+            directions.push(Vec::new());
+        }
+        if let Some(direction) = directions.last_mut() {
+            direction.push((key, value));
+        }
+    }
+    let [original, reply] = directions.as_slice() else {
+        return None;
+    };
+
+    let key = std::iter::once(protocol.to_string())
+        .chain(original
+            .iter()
+            .filter(|(key, _)| !matches!(*key, "packets" | "bytes"))
+            .map(|(key, value)| format!("{}={}", key, value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some(Flow {
+        key,
+        original: parse_direction(original)?,
+        reply: parse_direction(reply)?,
+    })
+}
+
+fn parse_direction(fields: &[(&str, &str)]) -> Option<FlowDirection> {
+    let field = |name: &str| fields.iter().find(|(key, _)| *key == name).map(|(_, value)| *value);
+    Some(FlowDirection {
+        src: field("src")?.parse().ok()?,
+        dst: field("dst")?.parse().ok()?,
+        packets: field("packets")?.parse().ok()?,
+        bytes: field("bytes")?.parse().ok()?,
+    })
+}
+
+/// Bytes seen in each direction of a flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowBytes {
+    pub original: u64,
+    pub reply: u64,
+}
+
+/// Flow counters at one point in time, persisted so one-shot runs can compute rates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConntrackSample {
+    pub taken_at: SystemTime,
+    pub flows: BTreeMap<String, FlowBytes>,
+}
+
+impl ConntrackSample {
+    /// File name of the previous sample inside the state directory
+    pub const FILE_NAME: &'static str = "conntrack.json";
+
+    pub fn new(taken_at: SystemTime, flows: &[Flow]) -> Self {
+        Self {
+            taken_at,
+            flows: flows
+                .iter()
+                .map(|flow| (flow.key.clone(), FlowBytes { original: flow.original.bytes, reply: flow.reply.bytes }))
+                .collect(),
+        }
+    }
+
+    /// Loads the sample saved by the previous run, if any
+    pub fn load(state_dir: &Path) -> Result<Option<Self>> {
+        state_file::load(&state_dir.join(Self::FILE_NAME))
+    }
+
+    /// Saves the sample for the next run, creating the state directory if needed
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        state_file::save(&state_dir.join(Self::FILE_NAME), self)
+    }
+}
+
+/// Totals per LAN address, with rates when an earlier sample is given
+/// Outbound flows belong to the original source; port-forwarded inbound flows
+/// to the reply source. Flows between two other hosts are ignored.
+pub fn device_traffic(
+    flows: &[Flow],
+    is_lan: impl Fn(&IpAddr) -> bool,
+    now: SystemTime,
+    previous: Option<&ConntrackSample>,
+) -> HashMap<IpAddr, DeviceTraffic> {
+    let elapsed = previous
+        .and_then(|previous| now.duration_since(previous.taken_at).ok())
+        .filter(|elapsed| !elapsed.is_zero() && *elapsed <= MAX_SAMPLE_AGE);
+
+    let mut totals: HashMap<IpAddr, DeviceTraffic> = HashMap::new();
+    let mut deltas: HashMap<IpAddr, (u64, u64)> = HashMap::new();
+
+    for flow in flows {
+        // (device, bytes it sent, bytes it received, orientation of the counters)
+        let (device, sent, received, outbound) = if is_lan(&flow.original.src) {
+            (flow.original.src, &flow.original, &flow.reply, true)
+        } else if is_lan(&flow.reply.src) {
+            (flow.reply.src, &flow.reply, &flow.original, false)
+        } else {
+            continue;
+        };
+
+        let total = totals.entry(device).or_default();
+        total.tx_bytes += sent.bytes;
+        total.rx_bytes += received.bytes;
+        total.packets += sent.packets + received.packets;
+
+        if elapsed.is_some() {
+            let before = previous
+                .and_then(|previous| previous.flows.get(&flow.key))
+                .map(|bytes| if outbound { (bytes.original, bytes.reply) } else { (bytes.reply, bytes.original) })
+                // Counters below the previous sample mean the flow was replaced
+                .filter(|(tx, rx)| *tx <= sent.bytes && *rx <= received.bytes)
+                .unwrap_or((0, 0));
+            let delta = deltas.entry(device).or_default();
+            delta.0 += sent.bytes - before.0;
+            delta.1 += received.bytes - before.1;
+        }
+    }
+
+    if let Some(elapsed) = elapsed {
+        let seconds = elapsed.as_secs_f64();
+        for (device, (tx, rx)) in deltas {
+            if let Some(total) = totals.get_mut(&device) {
+                total.rates = Some(Throughput {
+                    rx_bytes_per_sec: rx as f64 / seconds,
+                    tx_bytes_per_sec: tx as f64 / seconds,
+                });
+            }
+        }
+    }
+    totals
+}
+
+/// Attaches traffic from the conntrack table to devices and saves the sample for the next run
+pub fn record_device_traffic(root: &Path, state_dir: &Path, network_data: &mut NetworkData) -> Result<()> {
+    let path = root.join(CONNTRACK_PATH);
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let flows = parse_conntrack(&content);
+    let now = SystemTime::now();
+
+    // A corrupt sample is simply replaced; rates resume with the next run
+    let previous = ConntrackSample::load(state_dir).unwrap_or_default();
+    let device_ips: Vec<IpAddr> = network_data.devices.iter().map(|device| device.ip).collect();
+    let mut traffic = device_traffic(&flows, |ip| device_ips.contains(ip), now, previous.as_ref());
+
+    for device in &mut network_data.devices {
+        device.traffic = traffic.remove(&device.ip);
+    }
+    ConntrackSample::new(now, &flows).save(state_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const FIXTURE: &str = include_str!("fixtures/nf_conntrack");
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn is_lan(address: &IpAddr) -> bool {
+        match address {
+            IpAddr::V4(v4) => v4.octets()[..3] == [192, 168, 1],
+            IpAddr::V6(v6) => v6.segments()[0] == 0xfd00,
+        }
+    }
+
+    #[test]
+    fn test_parse_fixture() {
+        let flows = parse_conntrack(FIXTURE);
+        // The unreplied flow without accounting counters is skipped
+        assert_eq!(flows.len(), 7);
+
+        assert_eq!(flows[0].key, "tcp src=192.168.1.20 dst=93.184.216.34 sport=51234 dport=443");
+        assert_eq!(flows[0].original, FlowDirection {
+            src: ip("192.168.1.20"),
+            dst: ip("93.184.216.34"),
+            packets: 120,
+            bytes: 9600,
+        });
+        assert_eq!(flows[0].reply.bytes, 4_200_000);
+        assert_eq!(flows[4].key, "icmp src=192.168.1.30 dst=8.8.8.8 type=8 code=0 id=17");
+        assert_eq!(flows[5].original.src, ip("fd00::20"));
+    }
+
+    #[test]
+    fn test_totals_per_lan_device() {
+        let traffic = device_traffic(&parse_conntrack(FIXTURE), is_lan, SystemTime::now(), None);
+        assert_eq!(traffic.len(), 4);
+
+        let laptop = &traffic[&ip("192.168.1.20")];
+        assert_eq!((laptop.tx_bytes, laptop.rx_bytes, laptop.packets), (9672, 4_200_120, 422));
+        assert_eq!(laptop.rates, None);
+
+        // Port-forwarded server: the reply direction is what it sent
+        let server = &traffic[&ip("192.168.1.40")];
+        assert_eq!((server.tx_bytes, server.rx_bytes), (50_000, 800));
+
+        assert!(!traffic.contains_key(&ip("10.9.9.9")));
+    }
+
+    #[test]
+    fn test_rates_from_previous_sample() {
+        let flows = parse_conntrack(FIXTURE);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+
+        // Ten seconds ago the download had 2 MB less and the DNS lookup had not happened yet
+        let mut previous = ConntrackSample::new(now - Duration::from_secs(10), &flows);
+        previous.flows.get_mut(&flows[0].key).unwrap().reply -= 2_000_000;
+        previous.flows.remove(&flows[1].key);
+
+        let traffic = device_traffic(&flows, is_lan, now, Some(&previous));
+        assert_eq!(traffic[&ip("192.168.1.20")].rates, Some(Throughput {
+            rx_bytes_per_sec: 200_012.0,
+            tx_bytes_per_sec: 7.2,
+        }));
+        assert_eq!(traffic[&ip("192.168.1.30")].rates, Some(Throughput::default()));
+    }
+}
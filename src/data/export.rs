@@ -0,0 +1,917 @@
+//! Stable, versioned JSON export and import of network snapshots.
+//!
+//! The export format is independent of the internal domain types so that
+//! third-party tools can rely on it. Incompatible changes bump
+//! `SCHEMA_VERSION`; new optional fields and new values of the enumerated
+//! string fields may be added without a bump. The importer skips source
+//! reports, evidence and banners it does not recognise and treats unknown
+//! device types as "unknown", so older builds can read exports written by
+//! newer ones.
+
+use crate::domain::{
+    BannerKind, ConnectivityCheck, ConnectivityStatus, DeviceIdentity, DeviceTraffic, DeviceType,
+    DeviceTypeName, DiscoverySource, Evidence, EvidenceSource, FriendlyName, Gateway, GatewayHealth,
+    GatewayNat, Hostname, IgdStatus, InterfaceName, MacAddress, ManufacturerName, MarkerLevel,
+    ModelName, NatProtocol, NetworkData, NetworkDevice, NetworkInterface, NetworkSnapshot, PortMapping,
+    PrinterState, PrinterStatus, ScanMetadata, ServiceBanner, ServiceInfo, ServiceInstanceName,
+    ServiceType, SnmpInfo, SourceReport, Throughput, UpnpInfo, UpstreamCheck, WifiLink,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Current version of the export schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Top-level export document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportDocument {
+    pub schema_version: u32,
+    /// Present when the snapshot came from a live scan
+    pub scan: Option<ExportScan>,
+    pub interfaces: Vec<ExportInterface>,
+    pub gateway: Option<IpAddr>,
+    pub dns_servers: Vec<IpAddr>,
+    pub devices: Vec<ExportDevice>,
+    /// Gateway probe results, absent if the gateway was not probed
+    #[serde(default)]
+    pub gateway_health: Option<ExportGatewayHealth>,
+    /// Captive portal check result, absent unless a check URL was configured
+    #[serde(default)]
+    pub connectivity: Option<ExportConnectivity>,
+    /// Public address from the gateway, absent unless `--nat-pmp` got an answer
+    #[serde(default)]
+    pub gateway_nat: Option<ExportGatewayNat>,
+}
+
+/// When and how the scan was performed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportScan {
+    /// RFC3339 UTC timestamp
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub sources: Vec<ExportSource>,
+}
+
+/// One discovery source used in the scan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportSource {
+    /// One of: interfaces, ping_sweep, arp, mdns, ssdp, reverse_dns, route, resolv_conf, gateway_health,
+    /// connectivity, wifi, port_probe, banners, ipp, snmp, igd, nat_pmp
+    pub name: String,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Gateway round-trip time, packet loss and upstream reachability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportGatewayHealth {
+    /// Zero when the gateway could not be pinged
+    pub probes_sent: u32,
+    pub replies: u32,
+    pub latency_us: Option<u64>,
+    pub upstream: Option<ExportUpstream>,
+}
+
+/// Result of the upstream connection check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportUpstream {
+    /// Host or DNS name with port, as configured
+    pub target: String,
+    pub reachable: bool,
+    pub latency_us: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Public address reported by the gateway over NAT-PMP or PCP
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportGatewayNat {
+    /// One of: nat_pmp, pcp
+    pub protocol: String,
+    pub external_ip: IpAddr,
+    /// Time since the gateway's mapping state started
+    pub epoch_us: u64,
+}
+
+/// Result of the connectivity check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportConnectivity {
+    pub url: String,
+    /// One of: online, captive, offline
+    pub status: String,
+    pub portal_url: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// A local network interface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportInterface {
+    pub name: String,
+    pub ip: IpAddr,
+    pub mac: Option<String>,
+    /// Link details, present for connected wireless interfaces
+    #[serde(default)]
+    pub wifi: Option<ExportWifi>,
+    /// Rates since the previous scan, absent on the first one
+    #[serde(default)]
+    pub throughput: Option<ExportThroughput>,
+}
+
+/// Average receive and transmit rates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExportThroughput {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+impl From<Throughput> for ExportThroughput {
+    fn from(rates: Throughput) -> Self {
+        Self { rx_bytes_per_sec: rates.rx_bytes_per_sec, tx_bytes_per_sec: rates.tx_bytes_per_sec }
+    }
+}
+
+impl From<ExportThroughput> for Throughput {
+    fn from(rates: ExportThroughput) -> Self {
+        Self { rx_bytes_per_sec: rates.rx_bytes_per_sec, tx_bytes_per_sec: rates.tx_bytes_per_sec }
+    }
+}
+
+/// Wireless link details; fields the driver does not report are null
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportWifi {
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub frequency_mhz: Option<u32>,
+    pub signal_dbm: Option<i32>,
+    pub tx_bitrate_mbps: Option<f64>,
+}
+
+/// A discovered device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportDevice {
+    /// Stable identifier: lowercase MAC address without separators
+    pub id: String,
+    pub ip: IpAddr,
+    /// Uppercase, colon-separated MAC address
+    pub mac: String,
+    pub interface: String,
+    /// Resolved hostname, or null if resolution failed
+    pub hostname: Option<String>,
+    /// RFC3339 UTC timestamp
+    pub last_seen: String,
+    pub services: Vec<ExportService>,
+    pub upnp: Option<ExportUpnp>,
+    pub identity: ExportIdentity,
+    /// Connection totals, present when traffic is accounted with `--conntrack`
+    #[serde(default)]
+    pub traffic: Option<ExportTraffic>,
+    /// Present when `--grab-banners` was used
+    #[serde(default)]
+    pub banners: Vec<ExportBanner>,
+    /// Present for printers queried with `--printer-status`
+    #[serde(default)]
+    pub printer: Option<ExportPrinter>,
+    /// Present for devices that answered `--snmp`
+    #[serde(default)]
+    pub snmp: Option<ExportSnmp>,
+    /// Present for gateways queried with `--igd`
+    #[serde(default)]
+    pub igd: Option<ExportIgd>,
+}
+
+/// Printer status read over IPP
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportPrinter {
+    pub model: Option<String>,
+    /// One of: idle, processing, stopped
+    pub state: Option<String>,
+    /// IPP keywords such as "media-jam" or "toner-low-report"
+    pub state_reasons: Vec<String>,
+    pub markers: Vec<ExportMarker>,
+}
+
+/// Level of one ink or toner supply
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportMarker {
+    pub name: String,
+    /// Percent remaining, null when the printer cannot tell
+    pub level: Option<u8>,
+}
+
+/// SNMP system group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportSnmp {
+    pub sys_descr: Option<String>,
+    /// Dotted OID, e.g. "1.3.6.1.4.1.2435.2.3.9.1"
+    pub sys_object_id: Option<String>,
+    pub sys_name: Option<String>,
+}
+
+/// WAN connection and port mappings of a UPnP Internet gateway
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportIgd {
+    pub external_ip: Option<IpAddr>,
+    /// As reported by the router, e.g. "Connected"
+    pub connection_status: Option<String>,
+    pub uptime_us: Option<u64>,
+    pub port_mappings: Vec<ExportPortMapping>,
+}
+
+/// A port forwarding opened on the router through UPnP
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportPortMapping {
+    /// "TCP" or "UDP"
+    pub protocol: String,
+    pub external_port: u16,
+    /// LAN address the port is forwarded to, as reported by the router
+    pub internal_client: String,
+    pub internal_port: u16,
+    pub description: String,
+    pub enabled: bool,
+    /// Remote address the mapping is limited to; null for any
+    pub remote_host: Option<String>,
+    /// Remaining lease; 0 means permanent
+    pub lease_duration_us: u64,
+}
+
+/// A banner grabbed from one of the device's services
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportBanner {
+    pub port: u16,
+    /// One of: title, server, ssh
+    pub kind: String,
+    pub text: String,
+}
+
+/// Bytes and packets a device exchanged through tracked connections
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportTraffic {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub packets: u64,
+    /// Rates since the previous scan, absent on the first one
+    pub rates: Option<ExportThroughput>,
+}
+
+/// An advertised service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportService {
+    /// Full service type, e.g. "_ipp._tcp.local."
+    pub service_type: String,
+    pub instance_name: String,
+    pub port: u16,
+}
+
+/// UPnP description fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportUpnp {
+    pub friendly_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+    pub device_type: Option<String>,
+}
+
+/// Inferred device identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportIdentity {
+    /// One of: television, printer, router, computer, nas, mobile_device,
+    /// tablet, speaker, streaming_device, smart_home, unknown
+    pub device_type: String,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub friendly_name: Option<String>,
+    pub evidence: Vec<ExportEvidence>,
+}
+
+/// One piece of identity evidence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportEvidence {
+    /// Evidence source, e.g. "UPnP" or "Hostname"
+    pub source: String,
+    pub detail: String,
+}
+
+impl ExportDocument {
+    /// Converts a snapshot into the export format
+    pub fn from_snapshot(snapshot: &NetworkSnapshot) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            scan: snapshot.scan.as_ref().map(export_scan),
+            interfaces: snapshot.interfaces
+                .iter()
+                .map(|iface| ExportInterface {
+                    name: iface.name.to_string(),
+                    ip: iface.ip,
+                    mac: iface.mac.as_ref().map(|m| m.to_string()),
+                    wifi: iface.wifi.as_ref().map(|wifi| ExportWifi {
+                        ssid: wifi.ssid.clone(),
+                        bssid: wifi.bssid.as_ref().map(|m| m.to_string()),
+                        frequency_mhz: wifi.frequency_mhz,
+                        signal_dbm: wifi.signal_dbm,
+                        tx_bitrate_mbps: wifi.tx_bitrate_mbps,
+                    }),
+                    throughput: iface.throughput.map(ExportThroughput::from),
+                })
+                .collect(),
+            gateway: snapshot.gateway.map(|gw| gw.0),
+            dns_servers: snapshot.dns_servers.clone(),
+            devices: snapshot.devices.iter().map(export_device).collect(),
+            gateway_health: snapshot.gateway_health.as_ref().map(export_gateway_health),
+            connectivity: snapshot.connectivity.as_ref().map(|check| ExportConnectivity {
+                url: check.url.clone(),
+                status: check.status.as_str().to_string(),
+                portal_url: check.portal_url.clone(),
+                detail: check.detail.clone(),
+            }),
+            gateway_nat: snapshot.gateway_nat.as_ref().map(|nat| ExportGatewayNat {
+                protocol: nat.protocol.id().to_string(),
+                external_ip: nat.external_ip,
+                epoch_us: micros(nat.epoch),
+            }),
+        }
+    }
+
+    /// Converts the export format back into a snapshot
+    pub fn into_snapshot(self) -> Result<NetworkSnapshot> {
+        if self.schema_version > SCHEMA_VERSION {
+            anyhow::bail!(
+                "Unsupported export schema version {} (this build supports up to {})",
+                self.schema_version,
+                SCHEMA_VERSION
+            );
+        }
+
+        let interfaces = self.interfaces
+            .into_iter()
+            .map(|iface| {
+                let mac = iface.mac.map(MacAddress::new).transpose()?;
+                let wifi = iface.wifi.map(|wifi| WifiLink {
+                    ssid: wifi.ssid,
+                    bssid: wifi.bssid.and_then(|bssid| MacAddress::new(bssid).ok()),
+                    frequency_mhz: wifi.frequency_mhz,
+                    signal_dbm: wifi.signal_dbm,
+                    tx_bitrate_mbps: wifi.tx_bitrate_mbps,
+                });
+                let mut interface = NetworkInterface::new(InterfaceName::new(iface.name), iface.ip, mac).with_wifi(wifi);
+                interface.throughput = iface.throughput.map(Throughput::from);
+                Ok(interface)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let devices = self.devices
+            .into_iter()
+            .map(import_device)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut snapshot = NetworkSnapshot::new(interfaces, devices, self.gateway.map(Gateway::new), self.dns_servers);
+        if let Some(scan) = self.scan {
+            snapshot = snapshot.with_scan(import_scan(scan)?);
+        }
+        snapshot.gateway_health = self.gateway_health.map(import_gateway_health);
+        // A status added by a newer build is left out
+        snapshot.connectivity = self.connectivity.and_then(|check| Some(ConnectivityCheck {
+            status: ConnectivityStatus::ALL.into_iter().find(|status| status.as_str() == check.status)?,
+            url: check.url,
+            portal_url: check.portal_url,
+            detail: check.detail,
+        }));
+        snapshot.gateway_nat = self.gateway_nat.and_then(|nat| Some(GatewayNat {
+            protocol: NatProtocol::ALL.into_iter().find(|protocol| protocol.id() == nat.protocol)?,
+            external_ip: nat.external_ip,
+            epoch: Duration::from_micros(nat.epoch_us),
+        }));
+        Ok(snapshot)
+    }
+}
+
+/// Serializes a snapshot to pretty-printed export JSON
+pub fn to_export_json(snapshot: &NetworkData) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&ExportDocument::from_snapshot(snapshot))?)
+}
+
+/// Parses export JSON into a snapshot
+pub fn from_export_json(json: &str) -> Result<NetworkData> {
+    let document: ExportDocument = serde_json::from_str(json).context("Invalid export document")?;
+    document.into_snapshot()
+}
+
+/// Writes a snapshot to an export file
+pub fn export_snapshot(snapshot: &NetworkData, path: &Path) -> Result<()> {
+    std::fs::write(path, to_export_json(snapshot)? + "\n")
+        .with_context(|| format!("Failed to write export to {}", path.display()))
+}
+
+/// Reads a snapshot from an export file
+pub fn import_snapshot(path: &Path) -> Result<NetworkData> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    from_export_json(&json).with_context(|| format!("Failed to import {}", path.display()))
+}
+
+fn format_timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string()
+}
+
+fn parse_timestamp(value: &str) -> Result<SystemTime> {
+    humantime::parse_rfc3339_weak(value).with_context(|| format!("Invalid RFC3339 timestamp: {}", value))
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+fn export_gateway_health(health: &GatewayHealth) -> ExportGatewayHealth {
+    ExportGatewayHealth {
+        probes_sent: health.probes_sent,
+        replies: health.replies,
+        latency_us: health.latency.map(micros),
+        upstream: health.upstream.as_ref().map(|upstream| ExportUpstream {
+            target: upstream.target.clone(),
+            reachable: upstream.reachable,
+            latency_us: upstream.latency.map(micros),
+            error: upstream.error.clone(),
+        }),
+    }
+}
+
+fn import_gateway_health(health: ExportGatewayHealth) -> GatewayHealth {
+    GatewayHealth {
+        probes_sent: health.probes_sent,
+        replies: health.replies,
+        latency: health.latency_us.map(Duration::from_micros),
+        upstream: health.upstream.map(|upstream| UpstreamCheck {
+            target: upstream.target,
+            reachable: upstream.reachable,
+            latency: upstream.latency_us.map(Duration::from_micros),
+            error: upstream.error,
+        }),
+    }
+}
+
+fn export_scan(scan: &ScanMetadata) -> ExportScan {
+    ExportScan {
+        started_at: format_timestamp(scan.started_at),
+        duration_ms: scan.duration.as_millis() as u64,
+        sources: scan.sources
+            .iter()
+            .map(|report| ExportSource {
+                name: report.source.as_str().to_string(),
+                duration_ms: report.duration.as_millis() as u64,
+                error: report.error.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn import_scan(scan: ExportScan) -> Result<ScanMetadata> {
+    // Sources added by newer builds are skipped
+    let sources = scan.sources
+        .into_iter()
+        .filter_map(|source| {
+            let kind = DiscoverySource::ALL.into_iter().find(|s| s.as_str() == source.name)?;
+            Some(SourceReport {
+                source: kind,
+                duration: Duration::from_millis(source.duration_ms),
+                error: source.error,
+            })
+        })
+        .collect();
+
+    Ok(ScanMetadata {
+        started_at: parse_timestamp(&scan.started_at)?,
+        duration: Duration::from_millis(scan.duration_ms),
+        sources,
+    })
+}
+
+fn export_device(device: &NetworkDevice) -> ExportDevice {
+    let identity = &device.identity;
+    ExportDevice {
+        id: device.mac.to_compact(),
+        ip: device.ip,
+        mac: device.mac.to_string(),
+        interface: device.interface_name.to_string(),
+        hostname: match &device.hostname {
+            Hostname::Resolved(name) => Some(name.clone()),
+            _ => None,
+        },
+        last_seen: format_timestamp(device.last_seen),
+        services: device.services
+            .iter()
+            .map(|service| ExportService {
+                service_type: service.service_type.to_string(),
+                instance_name: service.instance_name.to_string(),
+                port: service.port,
+            })
+            .collect(),
+        upnp: device.upnp_info.as_ref().map(|upnp| ExportUpnp {
+            friendly_name: upnp.friendly_name.as_ref().map(|v| v.as_str().to_string()),
+            manufacturer: upnp.manufacturer.as_ref().map(|v| v.as_str().to_string()),
+            model_name: upnp.model_name.as_ref().map(|v| v.as_str().to_string()),
+            device_type: upnp.device_type.as_ref().map(|v| v.as_str().to_string()),
+        }),
+        identity: ExportIdentity {
+            device_type: identity.device_type.id().to_string(),
+            manufacturer: identity.manufacturer.as_ref().map(|v| v.as_str().to_string()),
+            model: identity.model.as_ref().map(|v| v.as_str().to_string()),
+            friendly_name: identity.friendly_name.as_ref().map(|v| v.as_str().to_string()),
+            evidence: identity.evidence
+                .iter()
+                .map(|e| ExportEvidence {
+                    source: e.source.as_str().to_string(),
+                    detail: e.detail.clone(),
+                })
+                .collect(),
+        },
+        traffic: device.traffic.map(|traffic| ExportTraffic {
+            rx_bytes: traffic.rx_bytes,
+            tx_bytes: traffic.tx_bytes,
+            packets: traffic.packets,
+            rates: traffic.rates.map(ExportThroughput::from),
+        }),
+        banners: device.banners
+            .iter()
+            .map(|banner| ExportBanner {
+                port: banner.port,
+                kind: banner.kind.as_str().to_string(),
+                text: banner.text.clone(),
+            })
+            .collect(),
+        printer: device.printer.as_ref().map(|printer| ExportPrinter {
+            model: printer.model.clone(),
+            state: printer.state.map(|state| state.as_str().to_string()),
+            state_reasons: printer.state_reasons.clone(),
+            markers: printer.markers
+                .iter()
+                .map(|marker| ExportMarker { name: marker.name.clone(), level: marker.level })
+                .collect(),
+        }),
+        snmp: device.snmp.as_ref().map(|snmp| ExportSnmp {
+            sys_descr: snmp.sys_descr.clone(),
+            sys_object_id: snmp.sys_object_id.clone(),
+            sys_name: snmp.sys_name.clone(),
+        }),
+        igd: device.igd.as_ref().map(|igd| ExportIgd {
+            external_ip: igd.external_ip,
+            connection_status: igd.connection_status.clone(),
+            uptime_us: igd.uptime.map(micros),
+            port_mappings: igd.port_mappings
+                .iter()
+                .map(|mapping| ExportPortMapping {
+                    protocol: mapping.protocol.clone(),
+                    external_port: mapping.external_port,
+                    internal_client: mapping.internal_client.clone(),
+                    internal_port: mapping.internal_port,
+                    description: mapping.description.clone(),
+                    enabled: mapping.enabled,
+                    remote_host: mapping.remote_host.clone(),
+                    lease_duration_us: micros(mapping.lease_duration),
+                })
+                .collect(),
+        }),
+    }
+}
+
+fn import_device(device: ExportDevice) -> Result<NetworkDevice> {
+    let mac = MacAddress::new(device.mac)?;
+    let mut imported = NetworkDevice::new(device.ip, mac, InterfaceName::new(device.interface));
+
+    imported.hostname = device.hostname.map(Hostname::resolved).unwrap_or(Hostname::Unknown);
+    imported.last_seen = parse_timestamp(&device.last_seen)?;
+    imported.services = device.services
+        .into_iter()
+        .map(|s| ServiceInfo::new(ServiceType::new(s.service_type), ServiceInstanceName::new(s.instance_name), s.port))
+        .collect();
+    imported.upnp_info = device.upnp.map(|upnp| UpnpInfo {
+        friendly_name: upnp.friendly_name.map(FriendlyName::new),
+        manufacturer: upnp.manufacturer.map(ManufacturerName::new),
+        model_name: upnp.model_name.map(ModelName::new),
+        device_type: upnp.device_type.map(DeviceTypeName::new),
+        location: None,
+    });
+
+    let identity = device.identity;
+    let evidence = identity.evidence
+        .into_iter()
+        .filter_map(|e| {
+            let source = EvidenceSource::ALL.into_iter().find(|s| s.as_str() == e.source)?;
+            Some(Evidence::new(source, e.detail))
+        })
+        .collect();
+    imported.identity = DeviceIdentity {
+        device_type: DeviceType::from_id(&identity.device_type).unwrap_or(DeviceType::Unknown),
+        manufacturer: identity.manufacturer.map(ManufacturerName::new),
+        model: identity.model.map(ModelName::new),
+        friendly_name: identity.friendly_name.map(FriendlyName::new),
+        evidence,
+    };
+    imported.traffic = device.traffic.map(|traffic| DeviceTraffic {
+        rx_bytes: traffic.rx_bytes,
+        tx_bytes: traffic.tx_bytes,
+        packets: traffic.packets,
+        rates: traffic.rates.map(Throughput::from),
+    });
+    imported.banners = device.banners
+        .into_iter()
+        .filter_map(|banner| {
+            let kind = BannerKind::ALL.into_iter().find(|k| k.as_str() == banner.kind)?;
+            Some(ServiceBanner { port: banner.port, kind, text: banner.text })
+        })
+        .collect();
+    imported.printer = device.printer.map(|printer| PrinterStatus {
+        model: printer.model,
+        state: printer.state.and_then(|state| PrinterState::ALL.into_iter().find(|s| s.as_str() == state)),
+        state_reasons: printer.state_reasons,
+        markers: printer.markers
+            .into_iter()
+            .map(|marker| MarkerLevel { name: marker.name, level: marker.level })
+            .collect(),
+    });
+    imported.snmp = device.snmp.map(|snmp| SnmpInfo {
+        sys_descr: snmp.sys_descr,
+        sys_object_id: snmp.sys_object_id,
+        sys_name: snmp.sys_name,
+    });
+    imported.igd = device.igd.map(|igd| IgdStatus {
+        external_ip: igd.external_ip,
+        connection_status: igd.connection_status,
+        uptime: igd.uptime_us.map(Duration::from_micros),
+        port_mappings: igd.port_mappings
+            .into_iter()
+            .map(|mapping| PortMapping {
+                protocol: mapping.protocol,
+                external_port: mapping.external_port,
+                internal_client: mapping.internal_client,
+                internal_port: mapping.internal_port,
+                description: mapping.description,
+                enabled: mapping.enabled,
+                remote_host: mapping.remote_host,
+                lease_duration: Duration::from_micros(mapping.lease_duration_us),
+            })
+            .collect(),
+    });
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn sample_snapshot() -> NetworkSnapshot {
+        let gateway_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let interface = NetworkInterface::new(
+            InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            Some(MacAddress::new("AA:BB:CC:DD:EE:00".to_string()).unwrap()),
+        );
+        let mut router = NetworkDevice::new(
+            gateway_ip,
+            MacAddress::new("00:11:22:33:44:55".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        router.hostname = Hostname::resolved("router.lan".to_string());
+        router.last_seen = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        router.services.push(ServiceInfo::new(
+            ServiceType::new("_http._tcp.local.".to_string()),
+            ServiceInstanceName::new("router._http._tcp.local.".to_string()),
+            80,
+        ));
+        router.build_identity();
+
+        let scan = ScanMetadata {
+            started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            duration: Duration::from_millis(5250),
+            sources: vec![SourceReport {
+                source: DiscoverySource::Mdns,
+                duration: Duration::from_millis(3000),
+                error: Some("no multicast".to_string()),
+            }],
+        };
+
+        NetworkSnapshot::new(vec![interface], vec![router], Some(Gateway::new(gateway_ip)), vec![gateway_ip])
+            .with_scan(scan)
+    }
+
+    #[test]
+    fn test_export_format() {
+        let json = to_export_json(&sample_snapshot()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["scan"]["started_at"], "2023-11-14T22:13:20.000Z");
+        assert_eq!(value["scan"]["duration_ms"], 5250);
+        assert_eq!(value["scan"]["sources"][0]["name"], "mdns");
+        assert_eq!(value["gateway"], "192.168.1.1");
+
+        let device = &value["devices"][0];
+        assert_eq!(device["id"], "001122334455");
+        assert_eq!(device["hostname"], "router.lan");
+        assert_eq!(device["last_seen"], "2023-11-14T22:13:20.123Z");
+        assert_eq!(device["identity"]["device_type"], "router");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original = sample_snapshot();
+        let imported = from_export_json(&to_export_json(&original).unwrap()).unwrap();
+
+        assert_eq!(imported.scan, original.scan);
+        assert_eq!(imported.gateway.map(|g| g.0), original.gateway.map(|g| g.0));
+        assert_eq!(imported.interfaces[0].mac, original.interfaces[0].mac);
+
+        let (a, b) = (&imported.devices[0], &original.devices[0]);
+        assert_eq!(a.mac, b.mac);
+        assert_eq!(a.hostname, b.hostname);
+        assert_eq!(a.last_seen, b.last_seen);
+        assert_eq!(a.services, b.services);
+        assert_eq!(a.identity, b.identity);
+    }
+
+    fn round_trip(snapshot: &NetworkSnapshot) -> NetworkSnapshot {
+        from_export_json(&to_export_json(snapshot).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_gateway_health_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.gateway_health = Some(GatewayHealth {
+            probes_sent: 3,
+            replies: 2,
+            latency: Some(Duration::from_micros(412)),
+            upstream: Some(UpstreamCheck {
+                target: "1.1.1.1:443".to_string(),
+                reachable: false,
+                latency: None,
+                error: Some("timed out".to_string()),
+            }),
+        });
+
+        assert_eq!(round_trip(&snapshot).gateway_health, snapshot.gateway_health);
+    }
+
+    #[test]
+    fn test_connectivity_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.connectivity = Some(ConnectivityCheck {
+            url: "http://connectivitycheck.gstatic.com/generate_204".to_string(),
+            status: ConnectivityStatus::Captive,
+            portal_url: Some("http://portal.example/login".to_string()),
+            detail: Some("Redirected with HTTP 302".to_string()),
+        });
+
+        assert_eq!(round_trip(&snapshot).connectivity, snapshot.connectivity);
+    }
+
+    #[test]
+    fn test_wifi_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.interfaces[0] = snapshot.interfaces[0].clone().with_wifi(Some(WifiLink {
+            ssid: Some("Home Net".to_string()),
+            bssid: Some(MacAddress::new("00:11:22:33:44:55".to_string()).unwrap()),
+            frequency_mhz: Some(5180),
+            signal_dbm: Some(-52),
+            tx_bitrate_mbps: Some(866.7),
+        }));
+
+        assert_eq!(round_trip(&snapshot).interfaces[0].wifi, snapshot.interfaces[0].wifi);
+    }
+
+    #[test]
+    fn test_throughput_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.interfaces[0].throughput = Some(Throughput { rx_bytes_per_sec: 1250.5, tx_bytes_per_sec: 80.0 });
+
+        assert_eq!(round_trip(&snapshot).interfaces[0].throughput, snapshot.interfaces[0].throughput);
+    }
+
+    #[test]
+    fn test_device_traffic_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.devices[0].traffic = Some(DeviceTraffic {
+            rx_bytes: 52_428_800,
+            tx_bytes: 1_048_576,
+            packets: 41_200,
+            rates: Some(Throughput { rx_bytes_per_sec: 2048.0, tx_bytes_per_sec: 96.5 }),
+        });
+
+        assert_eq!(round_trip(&snapshot).devices[0].traffic, snapshot.devices[0].traffic);
+    }
+
+    #[test]
+    fn test_banners_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.devices[0].banners = vec![
+            ServiceBanner { port: 80, kind: BannerKind::HttpTitle, text: "Pi-hole - pi.hole".to_string() },
+            ServiceBanner { port: 80, kind: BannerKind::HttpServer, text: "lighttpd/1.4.69".to_string() },
+            ServiceBanner { port: 22, kind: BannerKind::SshVersion, text: "OpenSSH_9.6p1 Debian-3".to_string() },
+        ];
+
+        assert_eq!(round_trip(&snapshot).devices[0].banners, snapshot.devices[0].banners);
+    }
+
+    #[test]
+    fn test_printer_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.devices[0].printer = Some(PrinterStatus {
+            model: Some("Brother HL-L2350DW".to_string()),
+            state: Some(PrinterState::Stopped),
+            state_reasons: vec!["media-jam-error".to_string()],
+            markers: vec![
+                MarkerLevel { name: "Black Toner".to_string(), level: Some(8) },
+                MarkerLevel { name: "Drum".to_string(), level: None },
+            ],
+        });
+
+        assert_eq!(round_trip(&snapshot).devices[0].printer, snapshot.devices[0].printer);
+    }
+
+    #[test]
+    fn test_snmp_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.devices[0].snmp = Some(SnmpInfo {
+            sys_descr: Some("RouterOS RB4011iGS+".to_string()),
+            sys_object_id: Some("1.3.6.1.4.1.14988.1".to_string()),
+            sys_name: None,
+        });
+
+        assert_eq!(round_trip(&snapshot).devices[0].snmp, snapshot.devices[0].snmp);
+    }
+
+    #[test]
+    fn test_igd_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.devices[0].igd = Some(IgdStatus {
+            external_ip: Some("203.0.113.5".parse().unwrap()),
+            connection_status: Some("Connected".to_string()),
+            uptime: Some(Duration::from_secs(273_600)),
+            port_mappings: vec![PortMapping {
+                protocol: "TCP".to_string(),
+                external_port: 51413,
+                internal_client: "192.168.1.20".to_string(),
+                internal_port: 51413,
+                description: "Transmission at 51413".to_string(),
+                enabled: true,
+                remote_host: None,
+                lease_duration: Duration::from_secs(3600),
+            }],
+        });
+
+        assert_eq!(round_trip(&snapshot).devices[0].igd, snapshot.devices[0].igd);
+    }
+
+    #[test]
+    fn test_gateway_nat_round_trip() {
+        let mut snapshot = sample_snapshot();
+        snapshot.gateway_nat = Some(GatewayNat {
+            protocol: NatProtocol::Pcp,
+            external_ip: "203.0.113.7".parse().unwrap(),
+            epoch: Duration::from_secs(268_048),
+        });
+
+        assert_eq!(round_trip(&snapshot).gateway_nat, snapshot.gateway_nat);
+    }
+
+    #[test]
+    fn test_import_skips_unknown_values() {
+        let mut document = ExportDocument::from_snapshot(&sample_snapshot());
+        let scan = document.scan.as_mut().unwrap();
+        scan.sources.push(ExportSource { name: "carrier_pigeon".to_string(), duration_ms: 1, error: None });
+        let identity = &mut document.devices[0].identity;
+        identity.device_type = "hologram".to_string();
+        identity.evidence.push(ExportEvidence { source: "Telepathy".to_string(), detail: "hunch".to_string() });
+        document.devices[0].banners.push(ExportBanner { port: 21, kind: "ftp".to_string(), text: "vsFTPd".to_string() });
+        let json = serde_json::to_string(&document).unwrap();
+
+        let imported = from_export_json(&json).unwrap();
+        let sources: Vec<_> = imported.scan.unwrap().sources.iter().map(|r| r.source).collect();
+        assert_eq!(sources, vec![DiscoverySource::Mdns]);
+        let identity = &imported.devices[0].identity;
+        assert_eq!(identity.device_type, DeviceType::Unknown);
+        assert!(identity.evidence.iter().all(|e| e.detail != "hunch"));
+        assert!(!identity.evidence.is_empty());
+        assert!(imported.devices[0].banners.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let mut document = ExportDocument::from_snapshot(&sample_snapshot());
+        document.schema_version = SCHEMA_VERSION + 1;
+        let json = serde_json::to_string(&document).unwrap();
+
+        let error = from_export_json(&json).unwrap_err();
+        assert!(format!("{:#}", error).contains("Unsupported export schema version"));
+    }
+
+    #[test]
+    fn test_export_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("waybar_lan_export_{}.json", std::process::id()));
+        export_snapshot(&sample_snapshot(), &path).unwrap();
+        let imported = import_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(imported.devices.len(), 1);
+    }
+}
@@ -0,0 +1,157 @@
+//! Gateway latency, packet loss and upstream reachability checks.
+
+use crate::data::proc_parsers;
+use crate::domain::{GatewayHealth, UpstreamCheck};
+use anyhow::{Context, Result};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Number of pings sent to the gateway per scan
+pub const GATEWAY_PROBES: u32 = 3;
+
+/// Port used when the upstream target does not name one
+pub const DEFAULT_UPSTREAM_PORT: u16 = 443;
+
+/// Time allowed for connecting to the upstream host
+pub const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Summary of a ping run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingStats {
+    pub transmitted: u32,
+    pub received: u32,
+    pub rtt: Option<Duration>,
+}
+
+/// Parses ping's summary
+/// iputils: "3 packets transmitted, 2 received, 33% packet loss, time 402ms"
+/// busybox: "3 packets transmitted, 2 packets received, 33% packet loss"
+pub fn parse_ping_stats(output: &str) -> Option<PingStats> {
+    let line = output.lines().find(|line| line.contains("packets transmitted"))?;
+    let mut fields = line.split(',');
+    let transmitted = fields.next()?.split_whitespace().next()?.parse().ok()?;
+    let received = fields.next()?.split_whitespace().next()?.parse().ok()?;
+    Some(PingStats {
+        transmitted,
+        received,
+        rtt: proc_parsers::parse_ping_rtt(output),
+    })
+}
+
+/// Pings a host a few times in quick succession
+pub fn ping_host(ip: IpAddr, count: u32) -> Result<PingStats> {
+    // -i 0.2 is the shortest interval allowed without root
+    let output = Command::new("ping")
+        .args(["-c", &count.to_string(), "-i", "0.2", "-W", "1", "-q", &ip.to_string()])
+        .output()
+        .context("Failed to run ping")?;
+    parse_ping_stats(&String::from_utf8_lossy(&output.stdout))
+        .with_context(|| format!("Unexpected ping output for {}", ip))
+}
+
+/// Resolves "host", "host:port", "ip", "ip:port" or "[ipv6]:port"
+fn resolve_target(target: &str) -> Result<SocketAddr> {
+    if let Ok(addr) = target.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DEFAULT_UPSTREAM_PORT));
+    }
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().with_context(|| format!("Invalid port in {}", target))?),
+        None => (target, DEFAULT_UPSTREAM_PORT),
+    };
+    (host, port)
+        .to_socket_addrs()
+        .with_context(|| format!("Failed to resolve {}", host))?
+        .next()
+        .with_context(|| format!("No address found for {}", host))
+}
+
+/// Checks that a TCP connection to the upstream target can be established
+pub fn check_upstream(target: &str, timeout: Duration) -> UpstreamCheck {
+    let start = Instant::now();
+    let result = resolve_target(target)
+        .and_then(|addr| TcpStream::connect_timeout(&addr, timeout).map_err(Into::into));
+
+    match result {
+        Ok(_) => UpstreamCheck {
+            target: target.to_string(),
+            reachable: true,
+            latency: Some(start.elapsed()),
+            error: None,
+        },
+        Err(e) => UpstreamCheck {
+            target: target.to_string(),
+            reachable: false,
+            latency: None,
+            error: Some(format!("{:#}", e)),
+        },
+    }
+}
+
+/// Combines the gateway ping results with a check of the upstream target, if configured
+/// The upstream is checked even when the gateway could not be pinged
+pub fn gateway_health(stats: Option<PingStats>, upstream: Option<&str>) -> GatewayHealth {
+    let stats = stats.unwrap_or(PingStats { transmitted: 0, received: 0, rtt: None });
+    GatewayHealth {
+        probes_sent: stats.transmitted,
+        replies: stats.received,
+        latency: stats.rtt,
+        upstream: upstream.map(|target| check_upstream(target, UPSTREAM_TIMEOUT)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_ping_stats() {
+        let iputils = "--- 192.168.1.1 ping statistics ---\n\
+            3 packets transmitted, 2 received, 33.3333% packet loss, time 402ms\n\
+            rtt min/avg/max/mdev = 0.345/0.412/0.480/0.050 ms\n";
+        assert_eq!(parse_ping_stats(iputils), Some(PingStats {
+            transmitted: 3,
+            received: 2,
+            rtt: Some(Duration::from_micros(412)),
+        }));
+
+        let busybox_down = "3 packets transmitted, 0 packets received, 100% packet loss\n";
+        assert_eq!(parse_ping_stats(busybox_down), Some(PingStats { transmitted: 3, received: 0, rtt: None }));
+
+        assert_eq!(parse_ping_stats("ping: unknown host"), None);
+    }
+
+    #[test]
+    fn test_check_upstream_with_local_responder() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let up = check_upstream(&addr.to_string(), Duration::from_secs(1));
+        assert!(up.reachable);
+        assert!(up.latency.is_some());
+
+        // Nothing listens on the port once the responder is gone
+        drop(listener);
+        let down = check_upstream(&addr.to_string(), Duration::from_secs(1));
+        assert!(!down.reachable);
+        assert!(down.error.is_some());
+
+        // A failed gateway ping does not skip the upstream check
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let health = gateway_health(None, Some(&listener.local_addr().unwrap().to_string()));
+        assert!(!health.was_probed());
+        assert!(health.upstream.is_some_and(|upstream| upstream.reachable));
+    }
+
+    #[test]
+    fn test_resolve_target() {
+        assert_eq!(resolve_target("10.0.0.1").unwrap(), "10.0.0.1:443".parse().unwrap());
+        assert_eq!(resolve_target("[::1]:53").unwrap(), "[::1]:53".parse().unwrap());
+        assert_eq!(resolve_target("localhost:8080").unwrap().port(), 8080);
+        assert!(resolve_target("localhost:http").is_err());
+    }
+}
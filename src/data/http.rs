@@ -0,0 +1,311 @@
+//! Minimal HTTP/1.1 client for plain-HTTP checks on the local network.
+
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Responses are cut off after this many bytes
+const MAX_RESPONSE_BYTES: u64 = 256 * 1024;
+
+/// A parsed `http://` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpUrl {
+    pub host: String,
+    pub port: u16,
+    /// Path including any query string, always starting with '/'
+    pub path: String,
+}
+
+impl HttpUrl {
+    /// Value for the Host header; the port is omitted when it is the default
+    fn host_header(&self) -> String {
+        match self.port {
+            80 => self.host.clone(),
+            port => format!("{}:{}", self.host, port),
+        }
+    }
+
+    fn socket_addr(&self) -> Result<SocketAddr> {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        (host, self.port)
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {}", self.host))?
+            .next()
+            .with_context(|| format!("No address found for {}", self.host))
+    }
+}
+
+impl FromStr for HttpUrl {
+    type Err = anyhow::Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            bail!("Only http:// URLs are supported, got '{}'", url);
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        // A colon after the closing bracket of an IPv6 literal separates the port
+        let port_separator = authority.rfind(':').filter(|&i| !authority[i..].contains(']'));
+        let (host, port) = match port_separator {
+            Some(index) => (
+                &authority[..index],
+                authority[index + 1..].parse().with_context(|| format!("Invalid port in '{}'", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            bail!("Missing host in '{}'", url);
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for HttpUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}{}", self.host_header(), self.path)
+    }
+}
+
+/// Status, headers and body of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// First header with the given name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A TCP connection whose reads and writes share one deadline, so a peer
+/// trickling bytes cannot keep it open past the time allowed
+pub struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    /// Connects to `addr`; `timeout` covers the connect and everything after it
+    pub fn connect(addr: &SocketAddr, timeout: Duration) -> Result<Self> {
+        let deadline = Instant::now() + timeout;
+        let stream = TcpStream::connect_timeout(addr, timeout)
+            .with_context(|| format!("Failed to connect to {}", addr))?;
+        Ok(Self { stream, deadline })
+    }
+
+    fn remaining(&self) -> io::Result<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Deadline passed"));
+        }
+        Ok(remaining)
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Sends a GET request and reads the whole response; redirects are not followed
+pub fn get(url: &HttpUrl, timeout: Duration) -> Result<HttpResponse> {
+    get_limited(url, timeout, MAX_RESPONSE_BYTES)
+}
+
+/// Like `get`, but stops reading after `max_bytes` of the response
+pub fn get_limited(url: &HttpUrl, timeout: Duration, max_bytes: u64) -> Result<HttpResponse> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: waybar_lan\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        url.path,
+        url.host_header()
+    );
+    send(url, request.as_bytes(), timeout, max_bytes)
+}
+
+/// Sends a POST request with the given headers (at least Content-Type) and body, and reads the whole response
+pub fn post(url: &HttpUrl, headers: &[(&str, &str)], body: &[u8], timeout: Duration) -> Result<HttpResponse> {
+    let mut request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: waybar_lan\r\n", url.path, url.host_header());
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    let mut request = request.into_bytes();
+    request.extend_from_slice(body);
+    send(url, &request, timeout, MAX_RESPONSE_BYTES)
+}
+
+/// Sends a request; `timeout` limits the whole exchange, not each read
+fn send(url: &HttpUrl, request: &[u8], timeout: Duration, max_bytes: u64) -> Result<HttpResponse> {
+    let mut stream = DeadlineStream::connect(&url.socket_addr()?, timeout)?;
+    stream.write_all(request)?;
+
+    let mut reader = BufReader::new(stream.take(max_bytes));
+    read_response(&mut reader)
+}
+
+/// Parses a response: status line, headers, then a plain or chunked body
+fn read_response(reader: &mut impl BufRead) -> Result<HttpResponse> {
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .with_context(|| format!("Invalid HTTP status line: {}", status_line.trim_end()))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let mut response = HttpResponse { status, headers, body: Vec::new() };
+    let is_chunked = response
+        .header("Transfer-Encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+    let content_length = response.header("Content-Length").and_then(|length| length.parse::<u64>().ok());
+
+    if is_chunked {
+        response.body = read_chunked(reader)?;
+    } else if let Some(length) = content_length {
+        reader.take(length).read_to_end(&mut response.body)?;
+    } else {
+        reader.read_to_end(&mut response.body)?;
+    }
+    Ok(response)
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        if reader.read_line(&mut size_line)? == 0 {
+            break;
+        }
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size_hex, 16)
+            .with_context(|| format!("Invalid chunk size: {}", size_hex))?;
+        if size == 0 {
+            break;
+        }
+        reader.take(size).read_to_end(&mut body)?;
+        let mut crlf = String::new();
+        reader.read_line(&mut crlf)?;
+    }
+    Ok(body)
+}
+
+/// Sends a canned reply to one connection on a local port and returns the port
+/// With `read_request` set, the reply waits until the request headers have been read
+#[cfg(test)]
+pub fn serve_once(reply: &'static str, read_request: bool) -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        if read_request {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+        }
+        stream.write_all(reply.as_bytes()).unwrap();
+    });
+    port
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serve_url(response: &'static str) -> HttpUrl {
+        format!("http://127.0.0.1:{}/generate_204", serve_once(response, true)).parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_url() {
+        let url: HttpUrl = "http://connectivitycheck.gstatic.com/generate_204".parse().unwrap();
+        assert_eq!(url.host, "connectivitycheck.gstatic.com");
+        assert_eq!(url.port, 80);
+        assert_eq!(url.path, "/generate_204");
+
+        let url: HttpUrl = "http://[fe80::1]:631".parse().unwrap();
+        assert_eq!((url.host.as_str(), url.port, url.path.as_str()), ("[fe80::1]", 631, "/"));
+        assert_eq!(url.to_string(), "http://[fe80::1]:631/");
+
+        assert!("https://example.com/".parse::<HttpUrl>().is_err());
+        assert!("http://:80/".parse::<HttpUrl>().is_err());
+    }
+
+    #[test]
+    fn test_get_with_content_length_and_chunked_bodies() {
+        let url = serve_url("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        let response = get(&url, Duration::from_secs(2)).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+
+        let url = serve_url("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n");
+        assert_eq!(get(&url, Duration::from_secs(2)).unwrap().body, b"abcde");
+
+        let url = serve_url("HTTP/1.1 302 Found\r\nlocation: http://portal.example/login\r\n\r\n");
+        let response = get(&url, Duration::from_secs(2)).unwrap();
+        assert_eq!(response.header("Location"), Some("http://portal.example/login"));
+    }
+
+    #[test]
+    fn test_trickling_response_hits_deadline() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: HttpUrl = format!("http://{}/", listener.local_addr().unwrap()).parse().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+            // One byte every 100 ms never trips a per-read timeout
+            for _ in 0..50 {
+                std::thread::sleep(Duration::from_millis(100));
+                if stream.write_all(b"x").is_err() {
+                    break;
+                }
+            }
+        });
+
+        let started = Instant::now();
+        assert!(get(&url, Duration::from_millis(500)).is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,510 @@
+//! UPnP Internet Gateway Device queries: WAN address, status and port mappings over SOAP.
+
+use crate::data::banner::decode_entities;
+use crate::data::http::{self, HttpUrl};
+use crate::data::workers;
+use crate::domain::{
+    DeviceTypeName, FriendlyName, IgdStatus, ManufacturerName, ModelName, NetworkDevice, PortMapping,
+};
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Time allowed for each request, from connecting to the last byte
+pub const IGD_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Port mapping entries read at most; the list ends earlier with an invalid-index fault
+const MAX_PORT_MAPPINGS: u16 = 256;
+
+/// UPnP error SpecifiedArrayIndexInvalid, which marks the end of the port mapping table
+const ARRAY_INDEX_INVALID: u16 = 713;
+
+/// Gateways queried at once
+const WORKERS: usize = 4;
+
+/// WAN connection services, preferred first
+const WAN_SERVICE_TYPES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// Error reply of a SOAP action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpnpFault {
+    pub action: String,
+    pub code: u16,
+    pub description: Option<String>,
+}
+
+impl UpnpFault {
+    /// UPnP error code, if the error is a fault
+    pub fn code_of(error: &anyhow::Error) -> Option<u16> {
+        error.downcast_ref::<Self>().map(|fault| fault.code)
+    }
+}
+
+impl fmt::Display for UpnpFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed with UPnP error {}", self.action, self.code)?;
+        if let Some(description) = &self.description {
+            write!(f, " ({})", description)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UpnpFault {}
+
+/// Root device fields and services from a UPnP device description
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceDescription {
+    pub device_type: Option<String>,
+    pub friendly_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+    /// Service types with their resolved control URLs, from all embedded devices
+    pub services: Vec<(String, HttpUrl)>,
+}
+
+impl DeviceDescription {
+    /// The most capable WAN connection service offered
+    pub fn wan_connection(&self) -> Option<WanConnection> {
+        WAN_SERVICE_TYPES.iter().find_map(|wanted| {
+            self.services
+                .iter()
+                .find(|(service_type, _)| service_type == wanted)
+                .map(|(service_type, control_url)| WanConnection {
+                    service_type: service_type.clone(),
+                    control_url: control_url.clone(),
+                })
+        })
+    }
+}
+
+/// Inner XML of every element with this local name, ignoring namespace prefixes
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        let tag_name = tag.split_whitespace().next().unwrap_or_default();
+        let local_name = tag_name.rsplit(':').next().unwrap_or_default();
+        if tag.starts_with(['/', '?', '!']) || tag.ends_with('/') || local_name != name {
+            continue;
+        }
+
+        let content = &rest[tag_end + 1..];
+        let close = format!("</{}>", tag_name);
+        let Some(end) = content.find(&close) else {
+            break;
+        };
+        found.push(&content[..end]);
+        rest = &content[end + close.len()..];
+    }
+    found
+}
+
+/// Decoded, trimmed text of the first element with this local name
+pub fn element_text(xml: &str, name: &str) -> Option<String> {
+    let text = decode_entities(elements(xml, name).first()?.trim());
+    (!text.is_empty()).then_some(text)
+}
+
+/// Resolves a URL from a description against the description's own URL
+fn resolve_url(base: &HttpUrl, reference: &str) -> Result<HttpUrl> {
+    if reference.starts_with("http://") {
+        return reference.parse();
+    }
+    let path = if reference.starts_with('/') {
+        reference.to_string()
+    } else {
+        let directory = &base.path[..=base.path.rfind('/').unwrap_or_default()];
+        format!("{}{}", directory, reference)
+    };
+    Ok(HttpUrl { path, ..base.clone() })
+}
+
+/// Parses a device description fetched from `location`
+pub fn parse_description(xml: &str, location: &HttpUrl) -> Result<DeviceDescription> {
+    // UPnP 1.0 descriptions may name a different base for relative URLs
+    let base = match element_text(xml, "URLBase") {
+        Some(url_base) => url_base.parse().with_context(|| format!("Invalid URLBase '{}'", url_base))?,
+        None => location.clone(),
+    };
+
+    let mut services = Vec::new();
+    for service in elements(xml, "service") {
+        let (Some(service_type), Some(control_url)) = (element_text(service, "serviceType"), element_text(service, "controlURL")) else {
+            continue;
+        };
+        services.push((service_type, resolve_url(&base, &control_url)?));
+    }
+
+    Ok(DeviceDescription {
+        device_type: element_text(xml, "deviceType"),
+        friendly_name: element_text(xml, "friendlyName"),
+        manufacturer: element_text(xml, "manufacturer"),
+        model_name: element_text(xml, "modelName"),
+        services,
+    })
+}
+
+/// Fetches and parses the device description at an SSDP location
+pub fn fetch_description(location: &str, timeout: Duration) -> Result<DeviceDescription> {
+    let url: HttpUrl = location.parse()?;
+    let response = http::get(&url, timeout)?;
+    if response.status != 200 {
+        bail!("HTTP {} from {}", response.status, url);
+    }
+    parse_description(&String::from_utf8_lossy(&response.body), &url)
+}
+
+/// A WAN connection service of a gateway, controlled over SOAP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WanConnection {
+    pub service_type: String,
+    pub control_url: HttpUrl,
+}
+
+impl WanConnection {
+    /// Invokes an action and returns the response body; UPnP faults become errors
+    pub fn call(&self, action: &str, arguments: &[(&str, String)], timeout: Duration) -> Result<String> {
+        let arguments: String = arguments
+            .iter()
+            .map(|(name, value)| format!("<{0}>{1}</{0}>", name, value))
+            .collect();
+        let body = format!(
+            "<?xml version=\"1.0\"?>\r\n\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+            s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+            <s:Body><u:{0} xmlns:u=\"{1}\">{2}</u:{0}></s:Body></s:Envelope>\r\n",
+            action, self.service_type, arguments
+        );
+        let soap_action = format!("\"{}#{}\"", self.service_type, action);
+        let headers = [("Content-Type", "text/xml; charset=\"utf-8\""), ("SOAPAction", soap_action.as_str())];
+
+        let response = http::post(&self.control_url, &headers, body.as_bytes(), timeout)?;
+        let body = String::from_utf8_lossy(&response.body).into_owned();
+        if response.status != 200 {
+            let Some(code) = element_text(&body, "errorCode").and_then(|code| code.parse().ok()) else {
+                bail!("{} failed with HTTP {}", action, response.status);
+            };
+            let description = element_text(&body, "errorDescription");
+            return Err(UpnpFault { action: action.to_string(), code, description }.into());
+        }
+        Ok(body)
+    }
+
+    /// External address, connection status, uptime and all port mappings
+    pub fn status(&self, timeout: Duration) -> Result<IgdStatus> {
+        let external = self.call("GetExternalIPAddress", &[], timeout)?;
+        // Some routers do not implement GetStatusInfo
+        let status_info = self.call("GetStatusInfo", &[], timeout).ok();
+
+        let mut port_mappings = Vec::new();
+        for index in 0..MAX_PORT_MAPPINGS {
+            match self.port_mapping(index, timeout) {
+                Ok(mapping) => port_mappings.push(mapping),
+                Err(e) if UpnpFault::code_of(&e) == Some(ARRAY_INDEX_INVALID) => break,
+                Err(e) => return Err(e.context(format!("Port mapping list cut off after {} entries", index))),
+            }
+        }
+
+        Ok(IgdStatus {
+            external_ip: element_text(&external, "NewExternalIPAddress").and_then(|ip| ip.parse().ok()),
+            connection_status: status_info.as_deref().and_then(|info| element_text(info, "NewConnectionStatus")),
+            uptime: status_info
+                .as_deref()
+                .and_then(|info| element_text(info, "NewUptime"))
+                .and_then(|seconds| seconds.parse().ok())
+                .map(Duration::from_secs),
+            port_mappings,
+        })
+    }
+
+    /// One entry of the port mapping table
+    pub fn port_mapping(&self, index: u16, timeout: Duration) -> Result<PortMapping> {
+        let entry = self.call("GetGenericPortMappingEntry", &[("NewPortMappingIndex", index.to_string())], timeout)?;
+        let field = |name: &str| element_text(&entry, name);
+        let port = |name: &str| -> Result<u16> {
+            field(name)
+                .and_then(|port| port.parse().ok())
+                .with_context(|| format!("Port mapping {} has no valid {}", index, name))
+        };
+
+        Ok(PortMapping {
+            protocol: field("NewProtocol").unwrap_or_default().to_uppercase(),
+            external_port: port("NewExternalPort")?,
+            internal_client: field("NewInternalClient").unwrap_or_default(),
+            internal_port: port("NewInternalPort")?,
+            description: field("NewPortMappingDescription").unwrap_or_default(),
+            enabled: field("NewEnabled").is_none_or(|enabled| enabled == "1" || enabled.eq_ignore_ascii_case("true")),
+            remote_host: field("NewRemoteHost"),
+            lease_duration: Duration::from_secs(field("NewLeaseDuration").and_then(|s| s.parse().ok()).unwrap_or(0)),
+        })
+    }
+}
+
+/// Completes a gateway's UPnP details from its description and reads its WAN status
+fn query_gateway(device: &mut NetworkDevice) -> Result<()> {
+    let Some(upnp) = device.upnp_info.as_mut() else {
+        return Ok(());
+    };
+    let Some(location) = upnp.location.clone() else {
+        return Ok(());
+    };
+    let description = fetch_description(&location, IGD_TIMEOUT)?;
+
+    // SSDP only tells the search target; the description has the real device type
+    if let Some(device_type) = &description.device_type {
+        upnp.device_type = Some(DeviceTypeName::new(device_type.clone()));
+    }
+    upnp.friendly_name = upnp.friendly_name.take().or(description.friendly_name.clone().map(FriendlyName::new));
+    upnp.manufacturer = upnp.manufacturer.take().or(description.manufacturer.clone().map(ManufacturerName::new));
+    upnp.model_name = upnp.model_name.take().or(description.model_name.clone().map(ModelName::new));
+
+    let Some(connection) = description.wan_connection() else {
+        bail!("No WAN connection service in {}", location);
+    };
+    device.igd = Some(connection.status(IGD_TIMEOUT)?);
+    Ok(())
+}
+
+/// Reads the WAN status of devices announced as an InternetGatewayDevice, a few at a time
+/// Other devices are left alone. Returns the failures, one per gateway, if any.
+pub fn query_gateways(devices: &mut [NetworkDevice]) -> Option<String> {
+    let errors = Mutex::new(Vec::new());
+    workers::for_each_device(devices, WORKERS, |device| {
+        if device.is_internet_gateway()
+            && let Err(e) = query_gateway(device)
+        {
+            errors.lock().unwrap().push(format!("{}: {:#}", device.ip, e));
+        }
+    });
+    let mut errors = errors.into_inner().unwrap();
+    errors.sort();
+    (!errors.is_empty()).then(|| errors.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DeviceType, InterfaceName, MacAddress, UpnpInfo};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{IpAddr, TcpListener};
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+    <friendlyName>FRITZ!Box 7590</friendlyName>
+    <manufacturer>AVM Berlin</manufacturer>
+    <modelName>FRITZ!Box 7590</modelName>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>
+        <controlURL>/ctl/L3F</controlURL>
+      </service>
+    </serviceList>
+    <deviceList>
+      <device>
+        <deviceType>urn:schemas-upnp-org:device:WANDevice:1</deviceType>
+        <deviceList>
+          <device>
+            <deviceType>urn:schemas-upnp-org:device:WANConnectionDevice:1</deviceType>
+            <serviceList>
+              <service>
+                <serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+                <controlURL>ctl/IPConn</controlURL>
+              </service>
+            </serviceList>
+          </device>
+        </deviceList>
+      </device>
+    </deviceList>
+  </device>
+</root>"#;
+
+    fn soap_response(action: &str, fields: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\"><s:Body>\
+            <u:{0}Response xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\">{1}</u:{0}Response>\
+            </s:Body></s:Envelope>",
+            action, fields
+        )
+    }
+
+    fn mapping_entry(index: usize) -> Option<String> {
+        let (protocol, port, client, description) = [
+            ("TCP", 51413, "192.168.1.20", "Transmission at 51413"),
+            ("UDP", 3074, "192.168.1.30", "Xbox &amp; friends"),
+        ]
+        .get(index)
+        .copied()?;
+        Some(soap_response("GetGenericPortMappingEntry", &format!(
+            "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort><NewProtocol>{protocol}</NewProtocol>\
+            <NewInternalPort>{port}</NewInternalPort><NewInternalClient>{client}</NewInternalClient>\
+            <NewEnabled>1</NewEnabled><NewPortMappingDescription>{description}</NewPortMappingDescription>\
+            <NewLeaseDuration>0</NewLeaseDuration>"
+        )))
+    }
+
+    /// Local stand-in for a router: serves the description and answers WANIPConnection actions
+    /// Reading the port mapping at `failing_entry` fails with error 501 (ActionFailed).
+    fn stand_in(failing_entry: Option<usize>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let (mut soap_action, mut content_length) = (String::new(), 0);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        match name.to_ascii_lowercase().as_str() {
+                            "soapaction" => soap_action = value.trim().trim_matches('"').to_string(),
+                            "content-length" => content_length = value.trim().parse().unwrap(),
+                            _ => {}
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let action = soap_action.rsplit('#').next().unwrap_or_default();
+                let reply = match (request_line.split_whitespace().nth(1).unwrap_or_default(), action) {
+                    ("/igd/desc.xml", _) => Ok(DESCRIPTION.to_string()),
+                    ("/igd/ctl/IPConn", "GetExternalIPAddress") => Ok(soap_response(action,
+                        "<NewExternalIPAddress>203.0.113.5</NewExternalIPAddress>")),
+                    ("/igd/ctl/IPConn", "GetStatusInfo") => Ok(soap_response(action,
+                        "<NewConnectionStatus>Connected</NewConnectionStatus><NewLastConnectionError>ERROR_NONE\
+                        </NewLastConnectionError><NewUptime>273600</NewUptime>")),
+                    ("/igd/ctl/IPConn", "GetGenericPortMappingEntry") => {
+                        let index = element_text(&body, "NewPortMappingIndex").unwrap().parse().unwrap();
+                        match failing_entry == Some(index) {
+                            true => Err((501, "ActionFailed")),
+                            false => mapping_entry(index).ok_or((713, "SpecifiedArrayIndexInvalid")),
+                        }
+                    }
+                    _ => Err((401, "InvalidAction")),
+                };
+                let (status, reply) = match reply {
+                    Ok(reply) => ("200 OK", reply),
+                    Err((code, description)) => ("500 Internal Server Error", format!(
+                        "<s:Envelope><s:Body><s:Fault><detail><UPnPError><errorCode>{}</errorCode>\
+                        <errorDescription>{}</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>",
+                        code, description
+                    )),
+                };
+                write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+                    status, reply.len(), reply).unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_parse_description() {
+        let location: HttpUrl = "http://192.168.1.1:49000/igd/desc.xml".parse().unwrap();
+        let description = parse_description(DESCRIPTION, &location).unwrap();
+
+        assert_eq!(description.device_type.as_deref(), Some("urn:schemas-upnp-org:device:InternetGatewayDevice:1"));
+        assert_eq!(description.manufacturer.as_deref(), Some("AVM Berlin"));
+        assert_eq!(description.services.len(), 2);
+        assert_eq!(description.services[0].1.to_string(), "http://192.168.1.1:49000/ctl/L3F");
+
+        let connection = description.wan_connection().unwrap();
+        assert_eq!(connection.service_type, "urn:schemas-upnp-org:service:WANIPConnection:1");
+        assert_eq!(connection.control_url.to_string(), "http://192.168.1.1:49000/igd/ctl/IPConn");
+    }
+
+    #[test]
+    fn test_element_text() {
+        assert_eq!(element_text("<u:R><NewX>a &amp; b</NewX></u:R>", "NewX"), Some("a & b".to_string()));
+        assert_eq!(element_text("<NewX/><NewX> </NewX>", "NewX"), None);
+        assert_eq!(element_text("<s:Body><u:Fault>x</u:Fault></s:Body>", "Fault"), Some("x".to_string()));
+    }
+
+    fn upnp_device(location: String, device_type: &str) -> NetworkDevice {
+        let mut device = NetworkDevice::new(
+            IpAddr::from([127, 0, 0, 1]),
+            MacAddress::new("AA:BB:CC:DD:EE:01".to_string()).unwrap(),
+            InterfaceName::new("lo".to_string()),
+        );
+        device.upnp_info = Some(UpnpInfo {
+            device_type: Some(DeviceTypeName::new(device_type.to_string())),
+            location: Some(location),
+            ..UpnpInfo::new()
+        });
+        device
+    }
+
+    #[test]
+    fn test_gateway_status_from_stand_in() {
+        let port = stand_in(None);
+        let location = format!("http://127.0.0.1:{}/igd/desc.xml", port);
+        let mut devices = vec![upnp_device(location, "urn:schemas-upnp-org:device:InternetGatewayDevice:1")];
+        assert_eq!(query_gateways(&mut devices), None);
+        devices[0].build_identity();
+
+        let device = &devices[0];
+        assert_eq!(device.identity.device_type, DeviceType::Router);
+        assert_eq!(device.upnp_info.as_ref().unwrap().friendly_name.as_ref().unwrap().as_str(), "FRITZ!Box 7590");
+        let igd = device.igd.as_ref().unwrap();
+        assert_eq!(igd.external_ip, Some("203.0.113.5".parse().unwrap()));
+        assert_eq!(igd.connection_status.as_deref(), Some("Connected"));
+        assert_eq!(igd.uptime, Some(Duration::from_secs(273_600)));
+
+        assert_eq!(igd.port_mappings.len(), 2);
+        assert_eq!(igd.port_mappings[0], PortMapping {
+            protocol: "TCP".to_string(),
+            external_port: 51413,
+            internal_client: "192.168.1.20".to_string(),
+            internal_port: 51413,
+            description: "Transmission at 51413".to_string(),
+            enabled: true,
+            remote_host: None,
+            lease_duration: Duration::ZERO,
+        });
+        assert_eq!(igd.port_mappings[1].description, "Xbox & friends");
+    }
+
+    #[test]
+    fn test_failed_port_mapping_is_reported() {
+        let port = stand_in(Some(1));
+        let location = format!("http://127.0.0.1:{}/igd/desc.xml", port);
+        let mut devices = vec![upnp_device(location, "urn:schemas-upnp-org:device:InternetGatewayDevice:1")];
+
+        let error = query_gateways(&mut devices).unwrap();
+        assert!(error.contains("cut off after 1 entries"), "{}", error);
+        assert!(error.contains("UPnP error 501 (ActionFailed)"), "{}", error);
+        assert!(devices[0].igd.is_none());
+    }
+
+    #[test]
+    fn test_only_gateways_are_queried() {
+        let port = stand_in(None);
+        let location = format!("http://127.0.0.1:{}/igd/desc.xml", port);
+        let mut devices = vec![upnp_device(location, "urn:schemas-upnp-org:device:MediaRenderer:1")];
+
+        assert_eq!(query_gateways(&mut devices), None);
+        let upnp = devices[0].upnp_info.as_ref().unwrap();
+        assert_eq!(upnp.device_type.as_ref().unwrap().as_str(), "urn:schemas-upnp-org:device:MediaRenderer:1");
+        assert!(upnp.friendly_name.is_none());
+        assert!(devices[0].igd.is_none());
+    }
+}
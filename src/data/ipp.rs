@@ -0,0 +1,418 @@
+//! Printer state and supply levels over IPP Get-Printer-Attributes (RFC 8011).
+//!
+//! Only plain `ipp://` is queried; `ipps://` needs TLS, which is not available.
+
+use crate::data::http::{self, HttpUrl};
+use crate::data::workers;
+use crate::domain::{MarkerLevel, NetworkDevice, PrinterState, PrinterStatus};
+use anyhow::{bail, Context, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// Time allowed for each request, from connecting to the last byte
+pub const IPP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Port of the IPP service when a printer only announces LPD
+pub const IPP_PORT: u16 = 631;
+
+/// Devices handled at once
+const WORKERS: usize = 8;
+
+/// Resource paths tried in turn; printers differ in where they accept requests
+const RESOURCE_PATHS: [&str; 3] = ["/ipp/print", "/ipp", "/"];
+
+/// Attributes asked for, so printers skip the rest of their long attribute lists
+const REQUESTED_ATTRIBUTES: [&str; 5] = [
+    "printer-make-and-model",
+    "printer-state",
+    "printer-state-reasons",
+    "marker-names",
+    "marker-levels",
+];
+
+const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+const OPERATION_ATTRIBUTES_TAG: u8 = 0x01;
+const END_OF_ATTRIBUTES_TAG: u8 = 0x03;
+const INTEGER_TAG: u8 = 0x21;
+const ENUM_TAG: u8 = 0x23;
+const TEXT_TAG: u8 = 0x41;
+const KEYWORD_TAG: u8 = 0x44;
+const URI_TAG: u8 = 0x45;
+const CHARSET_TAG: u8 = 0x47;
+const NATURAL_LANGUAGE_TAG: u8 = 0x48;
+
+/// Service types of printers that are queried: IPP, and LPD on the IPP port
+const IPP_SERVICE_TYPE: &str = "_ipp._tcp";
+const LPD_SERVICE_TYPE: &str = "_printer._tcp";
+
+/// One attribute value; types the status does not use are kept as raw bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IppValue {
+    Integer(i32),
+    Text(String),
+    Other(Vec<u8>),
+}
+
+/// A named attribute with all of its values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IppAttribute {
+    pub name: String,
+    pub values: Vec<IppValue>,
+}
+
+/// Status code and attributes of an IPP response, from all groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IppResponse {
+    pub status_code: u16,
+    pub attributes: Vec<IppAttribute>,
+}
+
+impl IppResponse {
+    /// Status codes below 0x0100 are the successful-ok range
+    pub fn is_success(&self) -> bool {
+        self.status_code < 0x0100
+    }
+
+    fn values(&self, name: &str) -> &[IppValue] {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .map_or(&[], |attribute| attribute.values.as_slice())
+    }
+
+    fn texts(&self, name: &str) -> Vec<String> {
+        self.values(name)
+            .iter()
+            .filter_map(|value| match value {
+                IppValue::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn integers(&self, name: &str) -> Vec<i32> {
+        self.values(name)
+            .iter()
+            .filter_map(|value| match value {
+                IppValue::Integer(number) => Some(*number),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Printer status from the requested attributes
+    pub fn printer_status(&self) -> PrinterStatus {
+        let state = self.integers("printer-state").first().and_then(|state| match state {
+            3 => Some(PrinterState::Idle),
+            4 => Some(PrinterState::Processing),
+            5 => Some(PrinterState::Stopped),
+            _ => None,
+        });
+        // Negative levels mean "unknown" or "some remaining"
+        let markers = self
+            .texts("marker-names")
+            .into_iter()
+            .zip(self.integers("marker-levels").into_iter().map(Some).chain(std::iter::repeat(None)))
+            .map(|(name, level)| MarkerLevel {
+                name,
+                level: level.and_then(|level| u8::try_from(level).ok()).filter(|level| *level <= 100),
+            })
+            .collect();
+
+        PrinterStatus {
+            model: self.texts("printer-make-and-model").into_iter().next(),
+            state,
+            state_reasons: self
+                .texts("printer-state-reasons")
+                .into_iter()
+                .filter(|reason| reason != "none")
+                .collect(),
+            markers,
+        }
+    }
+}
+
+/// Encodes a Get-Printer-Attributes request for the given printer URI
+pub fn encode_request(printer_uri: &str, request_id: u32) -> Vec<u8> {
+    let mut request = vec![2, 0];
+    request.extend_from_slice(&GET_PRINTER_ATTRIBUTES.to_be_bytes());
+    request.extend_from_slice(&request_id.to_be_bytes());
+    request.push(OPERATION_ATTRIBUTES_TAG);
+    push_attribute(&mut request, CHARSET_TAG, "attributes-charset", b"utf-8");
+    push_attribute(&mut request, NATURAL_LANGUAGE_TAG, "attributes-natural-language", b"en");
+    push_attribute(&mut request, URI_TAG, "printer-uri", printer_uri.as_bytes());
+    for (index, attribute) in REQUESTED_ATTRIBUTES.iter().enumerate() {
+        // Additional values of a set carry an empty name
+        let name = if index == 0 { "requested-attributes" } else { "" };
+        push_attribute(&mut request, KEYWORD_TAG, name, attribute.as_bytes());
+    }
+    request.push(END_OF_ATTRIBUTES_TAG);
+    request
+}
+
+fn push_attribute(buffer: &mut Vec<u8>, tag: u8, name: &str, value: &[u8]) {
+    buffer.push(tag);
+    buffer.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(name.as_bytes());
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+/// Decodes an IPP response message
+pub fn parse_response(message: &[u8]) -> Result<IppResponse> {
+    let mut reader = ByteReader { bytes: message, position: 0 };
+    reader.take(2).context("Truncated IPP version")?;
+    let status_code = reader.u16().context("Truncated IPP status code")?;
+    reader.take(4).context("Truncated IPP request id")?;
+
+    let mut attributes: Vec<IppAttribute> = Vec::new();
+    loop {
+        let tag = reader.take(1).context("IPP response has no end-of-attributes tag")?[0];
+        if tag == END_OF_ATTRIBUTES_TAG {
+            break;
+        }
+        // Other delimiter tags start a new attribute group
+        if tag < 0x10 {
+            continue;
+        }
+
+        let name_length = reader.u16().context("Truncated IPP attribute")?;
+        let name = String::from_utf8_lossy(reader.take(name_length.into()).context("Truncated IPP attribute name")?);
+        let value_length = reader.u16().context("Truncated IPP attribute")?;
+        let value = reader.take(value_length.into()).context("Truncated IPP attribute value")?;
+
+        let value = match tag {
+            INTEGER_TAG | ENUM_TAG if value.len() == 4 => {
+                IppValue::Integer(i32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+            }
+            TEXT_TAG..=NATURAL_LANGUAGE_TAG => IppValue::Text(String::from_utf8_lossy(value).into_owned()),
+            _ => IppValue::Other(value.to_vec()),
+        };
+        match attributes.last_mut() {
+            Some(attribute) if name.is_empty() => attribute.values.push(value),
+            _ if name.is_empty() => bail!("IPP value without an attribute name"),
+            _ => attributes.push(IppAttribute { name: name.into_owned(), values: vec![value] }),
+        }
+    }
+    Ok(IppResponse { status_code, attributes })
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.position..self.position + length)?;
+        self.position += length;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+/// Queries a printer's status, trying the usual resource paths in turn
+pub fn query_printer(ip: IpAddr, port: u16, timeout: Duration) -> Result<PrinterStatus> {
+    let addr = SocketAddr::new(ip, port);
+    let mut last_error = None;
+    for (request_id, path) in (1..).zip(RESOURCE_PATHS) {
+        let url: HttpUrl = format!("http://{}{}", addr, path).parse()?;
+        let request = encode_request(&format!("ipp://{}{}", addr, path), request_id);
+        let result = http::post(&url, &[("Content-Type", "application/ipp")], &request, timeout).and_then(|response| {
+            if response.status != 200 {
+                bail!("HTTP {} from {}", response.status, url);
+            }
+            let response = parse_response(&response.body)?;
+            if !response.is_success() {
+                bail!("IPP status {:#06x} from {}", response.status_code, url);
+            }
+            Ok(response.printer_status())
+        });
+        match result {
+            Ok(status) => return Ok(status),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No IPP resource paths to try")))
+}
+
+/// IPP ports of a device's printer services; LPD-only printers are tried on the IPP port
+fn printer_ports(device: &NetworkDevice) -> Vec<u16> {
+    let mut ports: Vec<u16> = device
+        .services
+        .iter()
+        .filter_map(|service| {
+            let service_type = service.service_type.as_str();
+            if service_type.starts_with(IPP_SERVICE_TYPE) {
+                Some(service.port)
+            } else if service_type.starts_with(LPD_SERVICE_TYPE) {
+                Some(IPP_PORT)
+            } else {
+                None
+            }
+        })
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// Queries all devices that announce printer services, a few at a time; failures are skipped
+pub fn query_printers(devices: &mut [NetworkDevice]) {
+    workers::for_each_device(devices, WORKERS, |device| {
+        device.printer = printer_ports(device)
+            .into_iter()
+            .find_map(|port| query_printer(device.ip, port, IPP_TIMEOUT).ok());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{InterfaceName, MacAddress, ServiceInfo, ServiceInstanceName, ServiceType};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    const NAME_TAG: u8 = 0x42;
+
+    /// Get-Printer-Attributes response of a laser printer with low black toner
+    fn printer_response() -> Vec<u8> {
+        let mut message = vec![2, 0, 0, 0, 0, 0, 0, 1, OPERATION_ATTRIBUTES_TAG];
+        push_attribute(&mut message, CHARSET_TAG, "attributes-charset", b"utf-8");
+        message.push(0x04);
+        push_attribute(&mut message, TEXT_TAG, "printer-make-and-model", b"Brother HL-L2350DW");
+        push_attribute(&mut message, ENUM_TAG, "printer-state", &3i32.to_be_bytes());
+        push_attribute(&mut message, KEYWORD_TAG, "printer-state-reasons", b"toner-low-report");
+        push_attribute(&mut message, NAME_TAG, "marker-names", b"Black Toner");
+        push_attribute(&mut message, NAME_TAG, "", b"Drum");
+        push_attribute(&mut message, INTEGER_TAG, "marker-levels", &8i32.to_be_bytes());
+        push_attribute(&mut message, INTEGER_TAG, "", &(-3i32).to_be_bytes());
+        message.push(END_OF_ATTRIBUTES_TAG);
+        message
+    }
+
+    /// Stub IPP server: answers requests to `path` with `body`, other paths with 404
+    fn stub_server(path: &'static str, body: Vec<u8>) -> (u16, std::thread::JoinHandle<Vec<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for stream in listener.incoming().take(RESOURCE_PATHS.len()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some(length) = line.strip_prefix("Content-Length: ") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                }
+                let mut request = vec![0; content_length];
+                reader.read_exact(&mut request).unwrap();
+                requests.push(request);
+
+                if request_line.split_whitespace().nth(1) == Some(path) {
+                    write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+                        body.len()).unwrap();
+                    stream.write_all(&body).unwrap();
+                    break;
+                }
+                stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").unwrap();
+            }
+            requests
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_encode_request() {
+        let request = encode_request("ipp://192.168.1.30:631/ipp/print", 7);
+        assert_eq!(&request[..8], &[2, 0, 0, 0x0B, 0, 0, 0, 7]);
+        assert_eq!(request.last(), Some(&END_OF_ATTRIBUTES_TAG));
+
+        // The request is well-formed enough to decode with the response parser
+        let decoded = parse_response(&request).unwrap();
+        assert_eq!(decoded.texts("printer-uri"), vec!["ipp://192.168.1.30:631/ipp/print"]);
+        assert_eq!(decoded.texts("requested-attributes"), REQUESTED_ATTRIBUTES);
+    }
+
+    #[test]
+    fn test_parse_printer_status() {
+        let response = parse_response(&printer_response()).unwrap();
+        assert!(response.is_success());
+
+        let status = response.printer_status();
+        assert_eq!(status.model.as_deref(), Some("Brother HL-L2350DW"));
+        assert_eq!(status.state, Some(PrinterState::Idle));
+        assert_eq!(status.state_reasons, vec!["toner-low-report"]);
+        assert_eq!(status.markers, vec![
+            MarkerLevel { name: "Black Toner".to_string(), level: Some(8) },
+            MarkerLevel { name: "Drum".to_string(), level: None },
+        ]);
+        assert!(status.needs_attention());
+
+        assert!(parse_response(&printer_response()[..20]).is_err());
+    }
+
+    #[test]
+    fn test_query_stub_server() {
+        let (port, server) = stub_server("/ipp", printer_response());
+        let status = query_printer(IpAddr::from([127, 0, 0, 1]), port, IPP_TIMEOUT).unwrap();
+        assert_eq!(status.state, Some(PrinterState::Idle));
+
+        // /ipp/print was tried first and answered 404
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(parse_response(&requests[1]).unwrap().texts("printer-uri"),
+            vec![format!("ipp://127.0.0.1:{}/ipp", port)]);
+    }
+
+    #[test]
+    fn test_query_printers_feeds_identity() {
+        let (port, _server) = stub_server("/ipp/print", printer_response());
+        let mut device = NetworkDevice::new(
+            IpAddr::from([127, 0, 0, 1]),
+            MacAddress::new("AA:BB:CC:DD:EE:30".to_string()).unwrap(),
+            InterfaceName::new("lo".to_string()),
+        );
+        device.services.push(ServiceInfo::new(
+            ServiceType::new("_ipp._tcp.local.".to_string()),
+            ServiceInstanceName::new("Brother HL-L2350DW._ipp._tcp.local.".to_string()),
+            port,
+        ));
+
+        let mut devices = vec![device];
+        query_printers(&mut devices);
+        devices[0].build_identity();
+
+        assert!(devices[0].printer.as_ref().is_some_and(PrinterStatus::needs_attention));
+        assert_eq!(devices[0].identity.model.as_ref().map(|model| model.as_str()), Some("Brother HL-L2350DW"));
+    }
+
+    #[test]
+    fn test_printer_ports_are_unique() {
+        let mut device = NetworkDevice::new(
+            IpAddr::from([192, 168, 1, 30]),
+            MacAddress::new("AA:BB:CC:DD:EE:30".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        for (service_type, port) in [("_ipp._tcp.local.", 631), ("_ipp._tcp.local.", 8631), ("_printer._tcp.local.", 515)] {
+            device.services.push(ServiceInfo::new(
+                ServiceType::new(service_type.to_string()),
+                ServiceInstanceName::new("Office._tcp.local.".to_string()),
+                port,
+            ));
+        }
+
+        assert_eq!(printer_ports(&device), vec![631, 8631]);
+    }
+}
@@ -0,0 +1,328 @@
+//! External address and epoch from the gateway over NAT-PMP (RFC 6886) or PCP (RFC 6887).
+
+use crate::domain::{GatewayNat, NatProtocol};
+use anyhow::{bail, Context, Result};
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// UDP port NAT-PMP and PCP servers listen on
+pub const NAT_PMP_PORT: u16 = 5351;
+
+/// Time to wait for an answer per protocol
+pub const NAT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A PCP mapping made only to learn the external address lives this long, and is released right away
+pub const PCP_PROBE_LIFETIME: Duration = Duration::from_secs(30);
+
+const NAT_PMP_VERSION: u8 = 0;
+const PCP_VERSION: u8 = 2;
+/// Opcode of the NAT-PMP external address request
+const EXTERNAL_ADDRESS_OPCODE: u8 = 0;
+/// Opcode of the PCP MAP request
+const MAP_OPCODE: u8 = 1;
+/// Set on the opcode of responses
+const RESPONSE_BIT: u8 = 0x80;
+/// Result code of both protocols for a version the server does not speak
+const UNSUPPORTED_VERSION: u8 = 1;
+const UDP: u8 = 17;
+/// PCP common header plus MAP opcode data
+const PCP_MAP_LENGTH: usize = 60;
+
+/// NAT-PMP request for the external address: version and opcode only
+pub fn encode_nat_pmp_request() -> [u8; 2] {
+    [NAT_PMP_VERSION, EXTERNAL_ADDRESS_OPCODE]
+}
+
+/// Parses a NAT-PMP external address response
+/// Ok(None) for datagrams that are not such a response; error replies are errors.
+///
+/// ```text
+/// version(1) opcode(1) result(2) epoch seconds(4) external IPv4 address(4)
+/// ```
+pub fn parse_nat_pmp_response(response: &[u8]) -> Result<Option<GatewayNat>> {
+    // A PCP-only server answers with its own version and UNSUPP_VERSION
+    if let &[PCP_VERSION, opcode, _, UNSUPPORTED_VERSION, ..] = response
+        && opcode == RESPONSE_BIT | EXTERNAL_ADDRESS_OPCODE
+    {
+        bail!("Gateway speaks PCP, not NAT-PMP");
+    }
+    let &[NAT_PMP_VERSION, opcode, r0, r1, ..] = response else {
+        return Ok(None);
+    };
+    if opcode != RESPONSE_BIT | EXTERNAL_ADDRESS_OPCODE {
+        return Ok(None);
+    }
+    let result_code = u16::from_be_bytes([r0, r1]);
+    if result_code != 0 {
+        bail!("NAT-PMP result code {}", result_code);
+    }
+    let &[_, _, _, _, e0, e1, e2, e3, a, b, c, d] = response else {
+        bail!("NAT-PMP response has invalid length {}", response.len());
+    };
+    Ok(Some(GatewayNat {
+        protocol: NatProtocol::NatPmp,
+        external_ip: IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+        epoch: Duration::from_secs(u32::from_be_bytes([e0, e1, e2, e3]).into()),
+    }))
+}
+
+/// PCP MAP request for a UDP port of the client (IPv4 addresses are sent IPv4-mapped)
+/// A lifetime of zero deletes the mapping made with the same nonce.
+///
+/// ```text
+/// version(1) R|opcode(1) reserved(2) lifetime(4) client address(16)
+/// nonce(12) protocol(1) reserved(3) internal port(2) suggested external port(2) suggested external address(16)
+/// ```
+pub fn encode_pcp_map(client: SocketAddr, nonce: [u8; 12], lifetime: Duration) -> [u8; PCP_MAP_LENGTH] {
+    let mut request = [0u8; PCP_MAP_LENGTH];
+    request[0] = PCP_VERSION;
+    request[1] = MAP_OPCODE;
+    request[4..8].copy_from_slice(&u32::try_from(lifetime.as_secs()).unwrap_or(u32::MAX).to_be_bytes());
+    request[8..24].copy_from_slice(&ipv6_octets(client.ip()));
+    request[24..36].copy_from_slice(&nonce);
+    request[36] = UDP;
+    request[40..42].copy_from_slice(&client.port().to_be_bytes());
+    // No suggestion: external port 0 and the all-zeros address of the client's family
+    let any = match client.ip() {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    request[44..60].copy_from_slice(&ipv6_octets(any));
+    request
+}
+
+/// Parses the response to the PCP MAP request with this nonce
+/// Ok(None) for datagrams that are not such a response; error replies are errors.
+///
+/// ```text
+/// version(1) R|opcode(1) reserved(1) result(1) lifetime(4) epoch seconds(4) reserved(12)
+/// nonce(12) protocol(1) reserved(3) internal port(2) assigned external port(2) assigned external address(16)
+/// ```
+pub fn parse_pcp_map_response(response: &[u8], nonce: &[u8; 12]) -> Result<Option<GatewayNat>> {
+    // A NAT-PMP-only server answers with its own version and UNSUPP_VERSION
+    if let &[NAT_PMP_VERSION, opcode, 0, UNSUPPORTED_VERSION, ..] = response
+        && opcode & RESPONSE_BIT != 0
+    {
+        bail!("Gateway speaks NAT-PMP, not PCP");
+    }
+    if response.len() < 4 || response[0] != PCP_VERSION || response[1] != RESPONSE_BIT | MAP_OPCODE {
+        return Ok(None);
+    }
+    // Error replies repeat the request's opcode data when they carry any
+    if response.len() >= PCP_MAP_LENGTH && response[24..36] != nonce[..] {
+        return Ok(None);
+    }
+    if response[3] != 0 {
+        bail!("PCP result code {}", response[3]);
+    }
+    if response.len() < PCP_MAP_LENGTH || !response.len().is_multiple_of(4) {
+        bail!("PCP response has invalid length {}", response.len());
+    }
+    let epoch = u32::from_be_bytes([response[8], response[9], response[10], response[11]]);
+    let mut external = [0u8; 16];
+    external.copy_from_slice(&response[44..60]);
+    let external = Ipv6Addr::from(external);
+    Ok(Some(GatewayNat {
+        protocol: NatProtocol::Pcp,
+        external_ip: external.to_ipv4_mapped().map_or(IpAddr::V6(external), IpAddr::V4),
+        epoch: Duration::from_secs(epoch.into()),
+    }))
+}
+
+fn ipv6_octets(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped().octets(),
+        IpAddr::V6(v6) => v6.octets(),
+    }
+}
+
+/// Random nonce tying a PCP response to its request
+fn mapping_nonce() -> [u8; 12] {
+    let random = || RandomState::new().build_hasher().finish().to_be_bytes();
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(&random());
+    nonce[8..].copy_from_slice(&random()[..4]);
+    nonce
+}
+
+/// Sends one request and waits for the answer `parse` recognises
+/// An error reply ends the wait at once; datagrams `parse` does not recognise are skipped.
+fn exchange(socket: &UdpSocket, request: &[u8], timeout: Duration,
+    parse: impl Fn(&[u8]) -> Result<Option<GatewayNat>>) -> Result<GatewayNat> {
+    let server = socket.peer_addr()?;
+    socket.send(request).with_context(|| format!("Failed to send to {}", server))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 1100];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            bail!("No answer from {}", server);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let Ok(length) = socket.recv(&mut buffer) else {
+            bail!("No answer from {}", server);
+        };
+        if let Some(nat) = parse(&buffer[..length])? {
+            return Ok(nat);
+        }
+    }
+}
+
+fn local_unspecified(remote: IpAddr) -> IpAddr {
+    match remote {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    }
+}
+
+/// Asks the gateway over NAT-PMP, then over PCP if NAT-PMP gets no usable answer
+/// PCP only tells the external address along with a mapping, so a short-lived one is
+/// made for the query's own UDP port and deleted again.
+pub fn query_gateway(gateway: IpAddr, port: u16, timeout: Duration) -> Result<GatewayNat> {
+    let server = SocketAddr::new(gateway, port);
+    let socket = UdpSocket::bind(SocketAddr::new(local_unspecified(gateway), 0))
+        .context("Failed to bind NAT-PMP socket")?;
+    socket.connect(server)?;
+
+    let nat_pmp = exchange(&socket, &encode_nat_pmp_request(), timeout, parse_nat_pmp_response);
+    if nat_pmp.is_ok() {
+        return nat_pmp;
+    }
+
+    // The connected socket's address is the one the gateway sees
+    let client = socket.local_addr()?;
+    let nonce = mapping_nonce();
+    let map = encode_pcp_map(client, nonce, PCP_PROBE_LIFETIME);
+    let pcp = exchange(&socket, &map, timeout, |response| parse_pcp_map_response(response, &nonce));
+    if pcp.is_ok() {
+        // Best effort; the mapping expires on its own otherwise
+        let _ = socket.send(&encode_pcp_map(client, nonce, Duration::ZERO));
+    }
+    pcp.map_err(|e| {
+        let nat_pmp_error = nat_pmp.unwrap_err();
+        e.context(format!("{} answered neither NAT-PMP ({:#}) nor PCP", server, nat_pmp_error))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How the local stand-in for a gateway answers
+    #[derive(Clone, Copy, PartialEq)]
+    enum Gateway {
+        NatPmp,
+        /// Refuses NAT-PMP with a PCP UNSUPP_VERSION reply and grants PCP mappings
+        PcpOnly,
+        /// Fails NAT-PMP with "network failure" and PCP with NOT_AUTHORIZED
+        Refusing,
+    }
+
+    /// Local UDP responder; returns its port and a channel of the PCP lifetimes it was asked for
+    fn responder(gateway: Gateway) -> (u16, std::sync::mpsc::Receiver<u32>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let (lifetimes, requested) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1100];
+            loop {
+                let (length, from) = socket.recv_from(&mut buffer).unwrap();
+                let request = &buffer[..length];
+                let response = match (gateway, request) {
+                    (Gateway::NatPmp, [NAT_PMP_VERSION, EXTERNAL_ADDRESS_OPCODE]) => {
+                        vec![0, 128, 0, 0, 0, 4, 23, 16, 203, 0, 113, 5]
+                    }
+                    (Gateway::PcpOnly, [NAT_PMP_VERSION, ..]) => {
+                        let mut response = vec![0u8; 24];
+                        response[..4].copy_from_slice(&[PCP_VERSION, 0x80, 0, UNSUPPORTED_VERSION]);
+                        response
+                    }
+                    (Gateway::Refusing, [NAT_PMP_VERSION, ..]) => vec![0, 128, 0, 3, 0, 0, 0, 1, 0, 0, 0, 0],
+                    (_, [PCP_VERSION, MAP_OPCODE, ..]) if length == PCP_MAP_LENGTH => {
+                        // Client address must be the IPv4-mapped loopback address
+                        assert_eq!(&request[8..24], &Ipv4Addr::LOCALHOST.to_ipv6_mapped().octets());
+                        assert_eq!(request[36], UDP);
+                        // Only some tests listen for the lifetimes
+                        let _ = lifetimes.send(u32::from_be_bytes(request[4..8].try_into().unwrap()));
+                        let mut response = request.to_vec();
+                        response[1] |= RESPONSE_BIT;
+                        if gateway == Gateway::Refusing {
+                            response[3] = 2;
+                        } else {
+                            response[8..12].copy_from_slice(&3_600u32.to_be_bytes());
+                            response[12..24].fill(0);
+                            response[42..44].copy_from_slice(&40_000u16.to_be_bytes());
+                            response[44..60].copy_from_slice(&Ipv4Addr::new(203, 0, 113, 7).to_ipv6_mapped().octets());
+                        }
+                        response
+                    }
+                    _ => continue,
+                };
+                socket.send_to(&response, from).unwrap();
+            }
+        });
+        (port, requested)
+    }
+
+    #[test]
+    fn test_parse_nat_pmp_response() {
+        let nat = parse_nat_pmp_response(&[0, 128, 0, 0, 0, 4, 23, 16, 203, 0, 113, 5]).unwrap().unwrap();
+        assert_eq!(nat.external_ip, IpAddr::from([203, 0, 113, 5]));
+        assert_eq!(nat.epoch, Duration::from_secs(268_048));
+
+        // Result code 3: network failure, e.g. no WAN address yet
+        assert!(parse_nat_pmp_response(&[0, 128, 0, 3, 0, 0, 0, 1, 0, 0, 0, 0]).is_err());
+        assert!(parse_nat_pmp_response(&[0, 128, 0, 0]).is_err());
+        // Not a response to the request
+        assert!(parse_nat_pmp_response(&[0, 0]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_pcp_map_request_layout() {
+        let client = SocketAddr::from(([192, 168, 1, 10], 50_000));
+        let request = encode_pcp_map(client, [7; 12], PCP_PROBE_LIFETIME);
+        assert_eq!(&request[..8], &[PCP_VERSION, MAP_OPCODE, 0, 0, 0, 0, 0, 30]);
+        assert_eq!(&request[8..24], &Ipv4Addr::new(192, 168, 1, 10).to_ipv6_mapped().octets());
+        assert_eq!(&request[24..36], &[7; 12]);
+        assert_eq!(&request[40..44], &[0xC3, 0x50, 0, 0]);
+        assert_eq!(&request[44..60], &Ipv4Addr::UNSPECIFIED.to_ipv6_mapped().octets());
+    }
+
+    #[test]
+    fn test_query_nat_pmp_responder() {
+        let (port, _) = responder(Gateway::NatPmp);
+        let nat = query_gateway(IpAddr::from([127, 0, 0, 1]), port, Duration::from_millis(500)).unwrap();
+        assert_eq!(nat.protocol, NatProtocol::NatPmp);
+        assert_eq!(nat.external_ip, IpAddr::from([203, 0, 113, 5]));
+    }
+
+    #[test]
+    fn test_falls_back_to_pcp_mapping() {
+        let (port, lifetimes) = responder(Gateway::PcpOnly);
+        let nat = query_gateway(IpAddr::from([127, 0, 0, 1]), port, Duration::from_secs(2)).unwrap();
+        assert_eq!(nat, GatewayNat {
+            protocol: NatProtocol::Pcp,
+            external_ip: IpAddr::from([203, 0, 113, 7]),
+            epoch: Duration::from_secs(3_600),
+        });
+
+        // The probe mapping is short-lived and deleted again
+        let timeout = Duration::from_secs(2);
+        assert_eq!(lifetimes.recv_timeout(timeout).unwrap(), 30);
+        assert_eq!(lifetimes.recv_timeout(timeout).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_error_replies_end_the_wait() {
+        let (port, _) = responder(Gateway::Refusing);
+        let started = Instant::now();
+        let error = query_gateway(IpAddr::from([127, 0, 0, 1]), port, Duration::from_secs(5)).unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let message = format!("{:#}", error);
+        assert!(message.contains("NAT-PMP result code 3"), "{}", message);
+        assert!(message.contains("PCP result code 2"), "{}", message);
+    }
+}
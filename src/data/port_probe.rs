@@ -0,0 +1,221 @@
+//! Opt-in TCP connect probes for devices that advertise nothing over mDNS or SSDP.
+
+use crate::domain::{ServiceInfo, ServiceInstanceName, ServiceType};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Ports probed when `--probe-ports` is given without a list
+pub const DEFAULT_PORTS: &str = "22,80,443,445,515,548,554,631,3389,8080,9100";
+
+/// Connection attempts in flight at once unless configured otherwise
+pub const DEFAULT_CONCURRENCY: usize = 32;
+
+/// Connection attempts per second unless configured otherwise
+pub const DEFAULT_RATE: u32 = 200;
+
+/// Instance name of services found by probing rather than announced
+pub const PROBE_INSTANCE_NAME: &str = "port probe";
+
+/// DNS-SD service type for a well-known port, so probed ports match mDNS services
+pub fn service_type_for_port(port: u16) -> String {
+    let name = match port {
+        22 => "ssh",
+        80 | 8000 | 8080 => "http",
+        443 | 8443 => "https",
+        445 => "smb",
+        515 => "printer",
+        548 => "afpovertcp",
+        554 => "rtsp",
+        631 => "ipp",
+        3389 => "rdp",
+        9100 => "pdl-datastream",
+        port => return format!("_port-{}._tcp.local.", port),
+    };
+    format!("_{}._tcp.local.", name)
+}
+
+/// Parses a comma-separated port list with optional ranges, e.g. "22,80,8000-8010"
+pub fn parse_port_list(value: &str) -> Result<Vec<u16>> {
+    let mut ports = Vec::new();
+    for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let parse = |port: &str| -> Result<u16> {
+            match port.parse() {
+                Ok(0) | Err(_) => Err(anyhow!("Invalid port '{}' in '{}'", port, value)),
+                Ok(port) => Ok(port),
+            }
+        };
+        match item.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    bail!("Invalid port range '{}'", item);
+                }
+                ports.extend(start..=end);
+            }
+            None => ports.push(parse(item)?),
+        }
+    }
+    if ports.is_empty() {
+        bail!("Empty port list");
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
+}
+
+/// Ports to probe and the limits applied across all devices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortProbe {
+    pub ports: Vec<u16>,
+    /// Connection attempts in flight at once
+    pub concurrency: usize,
+    /// Connection attempts started per second
+    pub rate: u32,
+    pub timeout: Duration,
+}
+
+impl PortProbe {
+    /// Creates a PortProbe with default limits
+    pub fn new(ports: Vec<u16>) -> Self {
+        Self {
+            ports,
+            concurrency: DEFAULT_CONCURRENCY,
+            rate: DEFAULT_RATE,
+            timeout: Duration::from_millis(500),
+        }
+    }
+
+    /// Open ports per host, probed with connect() under the concurrency and rate limits
+    pub fn scan(&self, hosts: &[IpAddr]) -> HashMap<IpAddr, Vec<u16>> {
+        let targets: Vec<SocketAddr> = hosts
+            .iter()
+            .flat_map(|ip| self.ports.iter().map(move |port| SocketAddr::new(*ip, *port)))
+            .collect();
+        let next_target = Mutex::new(targets.into_iter());
+        let limiter = RateLimiter::new(self.rate);
+        let open = Mutex::new(HashMap::<IpAddr, Vec<u16>>::new());
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.concurrency.max(1) {
+                scope.spawn(|| loop {
+                    let Some(target) = next_target.lock().unwrap().next() else {
+                        break;
+                    };
+                    limiter.wait();
+                    if TcpStream::connect_timeout(&target, self.timeout).is_ok() {
+                        open.lock().unwrap().entry(target.ip()).or_default().push(target.port());
+                    }
+                });
+            }
+        });
+
+        let mut open = open.into_inner().unwrap();
+        open.values_mut().for_each(|ports| ports.sort_unstable());
+        open
+    }
+}
+
+/// Spaces out events evenly across threads
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Blocks until the caller's slot comes up
+    fn wait(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        std::thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Service entries for open ports, skipping ports a device already announced
+pub fn probed_services(open_ports: &[u16], announced: &[ServiceInfo]) -> Vec<ServiceInfo> {
+    open_ports
+        .iter()
+        .filter(|port| !announced.iter().any(|service| service.port == **port))
+        .map(|port| ServiceInfo::new(
+            ServiceType::new(service_type_for_port(*port)),
+            ServiceInstanceName::new(PROBE_INSTANCE_NAME.to_string()),
+            *port,
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DeviceType, InterfaceName, MacAddress, NetworkDevice};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_port_list() {
+        assert_eq!(parse_port_list("443, 22,8000-8002,22").unwrap(), vec![22, 443, 8000, 8001, 8002]);
+        assert!(parse_port_list("0").is_err());
+        assert!(parse_port_list("80-70").is_err());
+        assert!(parse_port_list("http").is_err());
+        assert!(parse_port_list("").is_err());
+    }
+
+    #[test]
+    fn test_scan_finds_listening_ports() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let closed_port = {
+            let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+            closed.local_addr().unwrap().port()
+        };
+
+        let probe = PortProbe { concurrency: 2, ..PortProbe::new(vec![open_port, closed_port]) };
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let open = probe.scan(&[localhost]);
+        assert_eq!(open.get(&localhost), Some(&vec![open_port]));
+    }
+
+    #[test]
+    fn test_rate_limit_spaces_out_attempts() {
+        let probe = PortProbe { rate: 50, ..PortProbe::new((1..=5).collect()) };
+        let start = Instant::now();
+        probe.scan(&[IpAddr::from([127, 0, 0, 1])]);
+        // Five attempts at 50/s need at least four 20 ms gaps
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[test]
+    fn test_probed_services_feed_classification() {
+        let mut device = NetworkDevice::new(
+            IpAddr::from([192, 168, 1, 70]),
+            MacAddress::new("AA:BB:CC:DD:EE:70".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        device.services.push(ServiceInfo::new(
+            ServiceType::new("_http._tcp.local.".to_string()),
+            ServiceInstanceName::new("printer._http._tcp.local.".to_string()),
+            80,
+        ));
+
+        let probed = probed_services(&[80, 9100], &device.services);
+        assert_eq!(probed.len(), 1);
+        assert_eq!(probed[0].service_type.as_str(), "_pdl-datastream._tcp.local.");
+
+        device.services.extend(probed);
+        device.build_identity();
+        assert_eq!(device.identity.device_type, DeviceType::Printer);
+        assert_eq!(service_type_for_port(5555), "_port-5555._tcp.local.");
+    }
+}
@@ -3,18 +3,16 @@
 use crate::domain::{Gateway, Hostname, InterfaceName, MacAddress, NetworkDevice, NetworkInterface};
 use anyhow::{Context, Result};
 use network_interface::{NetworkInterface as NetIface, NetworkInterfaceConfig};
-use std::collections::HashSet;
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
 use std::net::{IpAddr, Ipv4Addr};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
 
 /// Parses /proc/net/arp to get neighbor table entries
 /// Format: IP address  HW type  Flags  HW address  Mask  Device
 /// Flag 0x2 = complete entry, 0x0 = incomplete
-pub fn parse_arp_table() -> Result<Vec<NetworkDevice>> {
-    let content = fs::read_to_string("/proc/net/arp")
-        .context("Failed to read /proc/net/arp")?;
-
+pub fn parse_arp_table(content: &str) -> Vec<NetworkDevice> {
     let mut devices = Vec::new();
 
     for line in content.lines().skip(1) {
@@ -49,39 +47,59 @@ pub fn parse_arp_table() -> Result<Vec<NetworkDevice>> {
         devices.push(NetworkDevice::new(ip, mac, InterfaceName::new(interface_name)));
     }
 
-    Ok(devices)
+    devices
 }
 
-/// Parses /proc/net/route to find the default gateway
-/// Format: Iface  Destination  Gateway  Flags  RefCnt  Use  Metric  Mask  MTU  Window  IRTT
-/// Gateway is in hex, little-endian format
-/// Destination 00000000 = default route
-pub fn parse_default_gateway() -> Result<Option<Gateway>> {
-    let content = fs::read_to_string("/proc/net/route")
-        .context("Failed to read /proc/net/route")?;
-
-    for line in content.lines().skip(1) {
-        // Skip header line
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 3 {
-            continue;
-        }
-
-        let destination = parts[1];
-        let gateway_hex = parts[2];
+/// One IPv4 route from /proc/net/route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub interface: InterfaceName,
+    pub destination: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    pub mask: Ipv4Addr,
+}
 
-        // Look for default route (destination = 00000000)
-        if destination != "00000000" {
-            continue;
-        }
+impl Route {
+    /// Whether this is a default route (destination 0.0.0.0/0)
+    pub fn is_default(&self) -> bool {
+        self.destination.is_unspecified() && self.mask.is_unspecified()
+    }
 
-        // Parse hex gateway address (little-endian)
-        if let Ok(ip) = parse_hex_ip(gateway_hex) {
-            return Ok(Some(Gateway::new(IpAddr::V4(ip))));
-        }
+    /// Whether the route's destination network contains the address
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & u32::from(self.mask) == u32::from(self.destination)
     }
+}
+
+/// Parses /proc/net/route
+/// Format: Iface  Destination  Gateway  Flags  RefCnt  Use  Metric  Mask  MTU  Window  IRTT
+/// Addresses are in hex, little-endian format
+pub fn parse_routes(content: &str) -> Vec<Route> {
+    content
+        .lines()
+        .skip(1) // Skip header line
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 8 {
+                return None;
+            }
+            Some(Route {
+                interface: InterfaceName::new(parts[0].to_string()),
+                destination: parse_hex_ip(parts[1]).ok()?,
+                gateway: parse_hex_ip(parts[2]).ok()?,
+                mask: parse_hex_ip(parts[7]).ok()?,
+            })
+        })
+        .collect()
+}
 
-    Ok(None)
+/// Finds the default gateway in /proc/net/route
+/// Destination and mask 00000000 = default route
+pub fn parse_default_gateway(content: &str) -> Option<Gateway> {
+    parse_routes(content)
+        .into_iter()
+        .find(Route::is_default)
+        .map(|route| Gateway::new(IpAddr::V4(route.gateway)))
 }
 
 /// Converts hex IP address from /proc/net/route to Ipv4Addr
@@ -103,6 +121,27 @@ fn parse_hex_ip(hex: &str) -> Result<Ipv4Addr> {
     Ok(Ipv4Addr::new(a, b, c, d))
 }
 
+/// Parses the addresses assigned to this host from /proc/net/fib_trie
+/// Each address is a leaf ("|-- 192.168.1.20") followed by a "/32 host LOCAL" entry
+pub fn parse_local_addresses(content: &str) -> Vec<Ipv4Addr> {
+    let mut addresses = Vec::new();
+    let mut leaf = None;
+
+    for line in content.lines().map(str::trim) {
+        if let Some(address) = line.strip_prefix("|-- ") {
+            leaf = address.parse::<Ipv4Addr>().ok();
+        } else if line.starts_with("/32 host LOCAL")
+            && let Some(address) = leaf
+            && !addresses.contains(&address)
+        {
+            // The trie lists the local table after the main one; keep each address once
+            addresses.push(address);
+        }
+    }
+
+    addresses
+}
+
 /// Enumerates all network interfaces on the system
 pub fn get_network_interfaces() -> Result<Vec<NetworkInterface>> {
     let system_interfaces = NetIface::show()
@@ -143,10 +182,7 @@ pub fn reverse_dns_lookup(ip: &IpAddr) -> Hostname {
 
 /// Parses /etc/resolv.conf to get DNS servers
 /// Format: nameserver <IP address>
-pub fn parse_dns_servers() -> Result<Vec<IpAddr>> {
-    let content = fs::read_to_string("/etc/resolv.conf")
-        .context("Failed to read /etc/resolv.conf")?;
-
+pub fn parse_dns_servers(content: &str) -> Vec<IpAddr> {
     let mut dns_servers = Vec::new();
 
     for line in content.lines() {
@@ -164,7 +200,7 @@ pub fn parse_dns_servers() -> Result<Vec<IpAddr>> {
         }
     }
 
-    Ok(dns_servers)
+    dns_servers
 }
 
 /// Generates all IPs in a /24 subnet from a base IP
@@ -176,14 +212,27 @@ fn generate_subnet_ips(base_ip: &Ipv4Addr) -> Vec<Ipv4Addr> {
         .collect()
 }
 
-/// Performs parallel ping sweep of subnet to populate ARP table
-/// Spawns concurrent ping processes for all IPs in the /24 subnet
-/// Does not parse output - relies on kernel updating ARP table
-/// Deduplicates subnets - only scans each unique /24 once
-pub fn ping_sweep_subnet(interfaces: &[NetworkInterface]) -> Result<()> {
+/// Upper bound on ping processes alive at once, each holding a pipe for its output
+const MAX_CONCURRENT_PINGS: usize = 128;
+
+/// How often the sweep checks for finished pings
+const PING_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Parses the average round-trip time from ping's summary line
+/// iputils: "rtt min/avg/max/mdev = 0.345/0.412/0.480/0.050 ms"
+/// busybox: "round-trip min/avg/max = 0.345/0.412/0.480 ms"
+pub fn parse_ping_rtt(output: &str) -> Option<Duration> {
+    let line = output.lines().find(|line| line.contains("min/avg/max"))?;
+    let values = line.split('=').nth(1)?.trim();
+    let avg_ms: f64 = values.split('/').nth(1)?.trim().parse().ok()?;
+    Some(Duration::from_micros((avg_ms * 1000.0).round() as u64))
+}
+
+/// One address per unique /24 subnet of the non-loopback IPv4 interfaces
+fn unique_subnets(interfaces: &[NetworkInterface]) -> Vec<Ipv4Addr> {
     // Track unique /24 subnets by first 3 octets to avoid duplicate scans
     let mut seen_subnets = HashSet::new();
-    let mut subnets_to_scan = Vec::new();
+    let mut subnets = Vec::new();
 
     // Collect unique /24 subnets from all IPv4 interfaces
     for iface in interfaces {
@@ -199,58 +248,91 @@ pub fn ping_sweep_subnet(interfaces: &[NetworkInterface]) -> Result<()> {
 
             // Only add if we haven't seen this /24 subnet before
             if seen_subnets.insert(subnet_prefix) {
-                subnets_to_scan.push(ipv4);
+                subnets.push(ipv4);
             }
         }
     }
 
-    // For each unique subnet, spawn ping processes for all 254 IPs
-    for base_ip in subnets_to_scan {
-        let ips = generate_subnet_ips(&base_ip);
+    subnets
+}
 
-        for ip in ips {
-            // Spawn ping process in background
+/// Performs parallel ping sweep of subnet to populate ARP table
+/// Pings every IP in the /24 subnet, at most `MAX_CONCURRENT_PINGS` at a time, and
+/// returns once all have finished, so the ARP table is complete when it is read next
+/// Returns the average round-trip time of each responding host
+/// Deduplicates subnets - only scans each unique /24 once
+pub fn ping_sweep_subnet(interfaces: &[NetworkInterface]) -> HashMap<IpAddr, Duration> {
+    let targets: Vec<Ipv4Addr> = unique_subnets(interfaces)
+        .iter()
+        .flat_map(generate_subnet_ips)
+        .collect();
+    run_pings(targets)
+}
+
+fn run_pings(targets: Vec<Ipv4Addr>) -> HashMap<IpAddr, Duration> {
+    let mut pending = targets.into_iter();
+    let mut running: Vec<(Ipv4Addr, Child)> = Vec::new();
+    let mut round_trip_times = HashMap::new();
+
+    loop {
+        while running.len() < MAX_CONCURRENT_PINGS
+            && let Some(ip) = pending.next()
+        {
             // -c 1: send 1 packet
             // -W 1: timeout 1 second
-            // -q: quiet mode (no output)
-            // We don't wait for completion - just spawn and let them populate ARP table
-            let _ = Command::new("ping")
+            // -q: quiet mode (summary only)
+            let child = Command::new("ping")
                 .args(["-c", "1", "-W", "1", "-q", &ip.to_string()])
-                .stdout(Stdio::null())
+                .stdout(Stdio::piped())
                 .stderr(Stdio::null())
                 .spawn();
             // Ignore errors - some IPs won't respond
+            if let Ok(child) = child {
+                running.push((ip, child));
+            }
+        }
+        if running.is_empty() {
+            return round_trip_times;
         }
-    }
-
-    // Give pings a brief moment to start populating ARP table
-    // This is a small delay to catch quick responses
-    std::thread::sleep(std::time::Duration::from_millis(200));
 
-    Ok(())
+        std::thread::sleep(PING_POLL_INTERVAL);
+        running.retain_mut(|(ip, child)| {
+            if let Ok(None) = child.try_wait() {
+                return true;
+            }
+            // The summary is a few lines, so it is already waiting in the pipe
+            let mut output = String::new();
+            if let Some(mut stdout) = child.stdout.take()
+                && stdout.read_to_string(&mut output).is_ok()
+                && let Some(rtt) = parse_ping_rtt(&output)
+            {
+                round_trip_times.insert(IpAddr::V4(*ip), rtt);
+            }
+            false
+        });
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_parse_arp_line_complete() {
-        let line = "192.168.1.50     0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0";
-        let parts: Vec<&str> = line.split_whitespace().collect();
-
-        assert_eq!(parts[0], "192.168.1.50");
-        assert_eq!(parts[2], "0x2");
-        assert_eq!(parts[3], "aa:bb:cc:dd:ee:ff");
-        assert_eq!(parts[5], "eth0");
-    }
+    const PROC_NET_ARP: &str = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.50     0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0
+192.168.1.51     0x1         0x0         00:00:00:00:00:00     *        eth0
+192.168.1.52     0x1         0x2         not-a-mac             *        eth0
+";
 
     #[test]
-    fn test_parse_arp_line_incomplete() {
-        let line = "192.168.1.51     0x1         0x0         00:00:00:00:00:00     *        eth0";
-        let parts: Vec<&str> = line.split_whitespace().collect();
-
-        assert_eq!(parts[2], "0x0"); // Incomplete flag
+    fn test_parse_arp_table() {
+        let devices = parse_arp_table(PROC_NET_ARP);
+
+        // Incomplete entries and invalid MACs are skipped
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].ip, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)));
+        assert_eq!(devices[0].mac.to_string(), "AA:BB:CC:DD:EE:FF");
+        assert_eq!(devices[0].interface_name.as_str(), "eth0");
     }
 
     #[test]
@@ -287,12 +369,63 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_route_line() {
-        let line = "eno1\t00000000\t0101A8C0\t0003\t0\t0\t1002\t00000000\t0\t0\t0";
-        let parts: Vec<&str> = line.split_whitespace().collect();
+    fn test_parse_routes() {
+        let content = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+            eno1\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+            eno1\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+        let routes = parse_routes(content);
+
+        assert_eq!(routes.len(), 2);
+        assert!(routes[0].is_default());
+        assert_eq!(routes[1].destination, Ipv4Addr::new(192, 168, 1, 0));
+        assert!(routes[1].contains(Ipv4Addr::new(192, 168, 1, 20)));
+        assert!(!routes[1].contains(Ipv4Addr::new(192, 168, 2, 20)));
+
+        let gateway = parse_default_gateway(content).unwrap();
+        assert_eq!(gateway.0, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        assert!(parse_default_gateway("Iface\tDestination\n").is_none());
+    }
 
-        assert_eq!(parts[1], "00000000"); // Default route
-        assert_eq!(parts[2], "0101A8C0"); // Gateway hex
+    #[test]
+    fn test_parse_local_addresses() {
+        let fib_trie = "\
+Main:
+  +-- 0.0.0.0/0 3 0 5
+     |-- 0.0.0.0
+        /0 universe UNICAST
+     +-- 127.0.0.0/8 2 0 2
+        |-- 127.0.0.1
+           /32 host LOCAL
+     +-- 192.168.1.0/24 2 0 2
+        |-- 192.168.1.0
+           /24 link UNICAST
+        |-- 192.168.1.20
+           /32 host LOCAL
+        |-- 192.168.1.255
+           /32 link BROADCAST
+Local:
+  +-- 192.168.1.0/24 2 0 2
+        |-- 192.168.1.20
+           /32 host LOCAL
+";
+        assert_eq!(parse_local_addresses(fib_trie),
+            vec![Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(192, 168, 1, 20)]);
+    }
+
+    #[test]
+    fn test_parse_ping_rtt() {
+        let iputils = "PING 192.168.1.1 (192.168.1.1) 56(84) bytes of data.\n\n\
+            --- 192.168.1.1 ping statistics ---\n\
+            1 packets transmitted, 1 received, 0% packet loss, time 0ms\n\
+            rtt min/avg/max/mdev = 0.345/0.412/0.480/0.050 ms\n";
+        assert_eq!(parse_ping_rtt(iputils), Some(Duration::from_micros(412)));
+
+        let busybox = "1 packets transmitted, 1 packets received, 0% packet loss\n\
+            round-trip min/avg/max = 1.500/1.500/1.500 ms\n";
+        assert_eq!(parse_ping_rtt(busybox), Some(Duration::from_micros(1500)));
+
+        let no_reply = "1 packets transmitted, 0 received, 100% packet loss, time 0ms\n";
+        assert_eq!(parse_ping_rtt(no_reply), None);
     }
 
     #[test]
@@ -331,50 +464,25 @@ mod tests {
             None,
         );
 
-        let interfaces = vec![iface1, iface2, iface3];
-
-        // Extract unique subnets using same logic as ping_sweep_subnet
-        let mut seen_subnets = HashSet::new();
-        let mut unique_subnets = Vec::new();
-
-        for iface in &interfaces {
-            if let IpAddr::V4(ipv4) = iface.ip {
-                if !ipv4.is_loopback() {
-                    let octets = ipv4.octets();
-                    let subnet_prefix = (octets[0], octets[1], octets[2]);
-                    if seen_subnets.insert(subnet_prefix) {
-                        unique_subnets.push(subnet_prefix);
-                    }
-                }
-            }
-        }
+        let loopback = NetworkInterface::new(
+            InterfaceName::new("lo".to_string()),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            None,
+        );
+
+        let interfaces = vec![iface1, iface2, iface3, loopback];
 
         // Should only have 2 unique subnets: 192.168.1 and 10.0.0
-        assert_eq!(unique_subnets.len(), 2);
-        assert!(unique_subnets.contains(&(192, 168, 1)));
-        assert!(unique_subnets.contains(&(10, 0, 0)));
+        assert_eq!(unique_subnets(&interfaces), vec![
+            Ipv4Addr::new(192, 168, 1, 100),
+            Ipv4Addr::new(10, 0, 0, 50),
+        ]);
     }
 
     #[test]
     fn test_parse_resolv_conf() {
-        use std::net::{IpAddr, Ipv4Addr};
-
-        // Simulate resolv.conf content
         let content = "# Generated by resolvconf\ndomain lan\nnameserver 192.168.1.1\nnameserver 8.8.8.8\nnameserver fd25:a234:e8f7::1\noptions edns0\n";
-
-        let mut dns_servers = Vec::new();
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 && parts[0] == "nameserver" {
-                if let Ok(ip) = parts[1].parse::<IpAddr>() {
-                    dns_servers.push(ip);
-                }
-            }
-        }
+        let dns_servers = parse_dns_servers(content);
 
         assert_eq!(dns_servers.len(), 3);
         assert_eq!(dns_servers[0], IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
@@ -0,0 +1,426 @@
+//! Opt-in SNMP v2c GET of the system group (sysDescr, sysObjectID, sysName).
+
+use crate::data::workers;
+use crate::domain::{NetworkDevice, SnmpInfo};
+use anyhow::{anyhow, bail, Context, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Community tried when `--snmp` is given without a list
+pub const DEFAULT_COMMUNITY: &str = "public";
+
+/// UDP port of SNMP agents
+pub const SNMP_PORT: u16 = 161;
+
+pub const SYS_DESCR: &str = "1.3.6.1.2.1.1.1.0";
+pub const SYS_OBJECT_ID: &str = "1.3.6.1.2.1.1.2.0";
+pub const SYS_NAME: &str = "1.3.6.1.2.1.1.5.0";
+
+/// Devices queried at once
+const WORKERS: usize = 16;
+
+const SNMP_V2C: i64 = 1;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const GET_REQUEST: u8 = 0xA0;
+const GET_RESPONSE: u8 = 0xA2;
+
+/// Value of a variable binding; types not needed here keep only their tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnmpValue {
+    Null,
+    Text(String),
+    ObjectId(String),
+    /// Any other type, including the noSuchObject and noSuchInstance exceptions
+    Other(u8),
+}
+
+/// A decoded SNMP message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnmpMessage {
+    pub community: String,
+    pub pdu_type: u8,
+    pub request_id: i32,
+    pub error_status: i64,
+    pub bindings: Vec<(String, SnmpValue)>,
+}
+
+/// Encodes an SNMP v2c message; requests carry Null values
+pub fn encode_message(community: &str, pdu_type: u8, request_id: i32, bindings: &[(&str, SnmpValue)]) -> Result<Vec<u8>> {
+    let mut varbinds = Vec::new();
+    for (oid, value) in bindings {
+        let mut binding = tlv(OBJECT_IDENTIFIER, &encode_oid(oid)?);
+        binding.extend(match value {
+            SnmpValue::Null => tlv(NULL, &[]),
+            SnmpValue::Text(text) => tlv(OCTET_STRING, text.as_bytes()),
+            SnmpValue::ObjectId(oid) => tlv(OBJECT_IDENTIFIER, &encode_oid(oid)?),
+            SnmpValue::Other(tag) => tlv(*tag, &[]),
+        });
+        varbinds.extend(tlv(SEQUENCE, &binding));
+    }
+
+    let mut pdu = tlv(INTEGER, &encode_integer(request_id.into()));
+    pdu.extend(tlv(INTEGER, &encode_integer(0)));
+    pdu.extend(tlv(INTEGER, &encode_integer(0)));
+    pdu.extend(tlv(SEQUENCE, &varbinds));
+
+    let mut message = tlv(INTEGER, &encode_integer(SNMP_V2C));
+    message.extend(tlv(OCTET_STRING, community.as_bytes()));
+    message.extend(tlv(pdu_type, &pdu));
+    Ok(tlv(SEQUENCE, &message))
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    match content.len() {
+        length @ 0..0x80 => encoded.push(length as u8),
+        length @ 0x80..0x100 => encoded.extend([0x81, length as u8]),
+        length => encoded.extend([0x82, (length >> 8) as u8, length as u8]),
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+/// Shortest two's complement big-endian form
+fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+fn encode_oid(oid: &str) -> Result<Vec<u8>> {
+    let arcs = oid
+        .split('.')
+        .map(|arc| arc.parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid OID '{}'", oid))?;
+    let [first, second, rest @ ..] = arcs.as_slice() else {
+        bail!("OID '{}' needs at least two arcs", oid);
+    };
+
+    let mut encoded = Vec::new();
+    for arc in std::iter::once(first * 40 + second).chain(rest.iter().copied()) {
+        // Base 128, most significant group first, continuation bit on all but the last
+        let mut groups = vec![(arc & 0x7F) as u8];
+        let mut remaining = arc >> 7;
+        while remaining > 0 {
+            groups.push((remaining & 0x7F) as u8 | 0x80);
+            remaining >>= 7;
+        }
+        encoded.extend(groups.iter().rev());
+    }
+    Ok(encoded)
+}
+
+fn decode_oid(bytes: &[u8]) -> Result<String> {
+    let mut arcs = Vec::new();
+    let mut arc: u32 = 0;
+    for byte in bytes {
+        arc = arc.checked_mul(128).context("OID arc too large")? | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    let Some((&first, rest)) = arcs.split_first() else {
+        bail!("Empty OID");
+    };
+    let (top, second) = match first {
+        0..40 => (0, first),
+        40..80 => (1, first - 40),
+        _ => (2, first - 80),
+    };
+    Ok([top, second].iter().chain(rest).map(u32::to_string).collect::<Vec<_>>().join("."))
+}
+
+fn decode_integer(bytes: &[u8]) -> Result<i64> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        bail!("Invalid integer length {}", bytes.len());
+    }
+    let sign = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(bytes.iter().fold(sign, |value, byte| (value << 8) | i64::from(*byte)))
+}
+
+/// Reads BER type-length-value elements one after another
+struct BerReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BerReader<'a> {
+    fn read(&mut self) -> Result<(u8, &'a [u8])> {
+        let [tag, first_length, rest @ ..] = self.bytes else {
+            bail!("Truncated BER element");
+        };
+        let (length, rest) = match *first_length {
+            length @ 0..=0x7F => (usize::from(length), rest),
+            0x81..=0x84 => {
+                let count = usize::from(first_length & 0x7F);
+                let length_bytes = rest.get(..count).context("Truncated BER length")?;
+                (length_bytes.iter().fold(0, |length, byte| (length << 8) | usize::from(*byte)), &rest[count..])
+            }
+            _ => bail!("Unsupported BER length {:#04x}", first_length),
+        };
+        let content = rest.get(..length).context("Truncated BER content")?;
+        self.bytes = &rest[length..];
+        Ok((*tag, content))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<&'a [u8]> {
+        match self.read()? {
+            (tag, content) if tag == expected => Ok(content),
+            (tag, _) => bail!("Expected BER tag {:#04x}, found {:#04x}", expected, tag),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// Decodes an SNMP v1 or v2c message
+pub fn parse_message(bytes: &[u8]) -> Result<SnmpMessage> {
+    let mut message = BerReader { bytes: BerReader { bytes }.expect(SEQUENCE)? };
+    decode_integer(message.expect(INTEGER)?)?;
+    let community = String::from_utf8_lossy(message.expect(OCTET_STRING)?).into_owned();
+    let (pdu_type, pdu) = message.read()?;
+
+    let mut pdu = BerReader { bytes: pdu };
+    let request_id = i32::try_from(decode_integer(pdu.expect(INTEGER)?)?).context("Request id out of range")?;
+    let error_status = decode_integer(pdu.expect(INTEGER)?)?;
+    decode_integer(pdu.expect(INTEGER)?)?;
+
+    let mut varbinds = BerReader { bytes: pdu.expect(SEQUENCE)? };
+    let mut bindings = Vec::new();
+    while !varbinds.is_empty() {
+        let mut binding = BerReader { bytes: varbinds.expect(SEQUENCE)? };
+        let oid = decode_oid(binding.expect(OBJECT_IDENTIFIER)?)?;
+        let value = match binding.read()? {
+            (NULL, _) => SnmpValue::Null,
+            (OCTET_STRING, text) => SnmpValue::Text(String::from_utf8_lossy(text).into_owned()),
+            (OBJECT_IDENTIFIER, oid) => SnmpValue::ObjectId(decode_oid(oid)?),
+            (tag, _) => SnmpValue::Other(tag),
+        };
+        bindings.push((oid, value));
+    }
+
+    Ok(SnmpMessage { community, pdu_type, request_id, error_status, bindings })
+}
+
+/// Collapses whitespace and drops control characters; sysDescr often spans lines
+fn clean(text: &str) -> Option<String> {
+    let text: String = text.split_whitespace().collect::<Vec<_>>().join(" ").chars().filter(|c| !c.is_control()).collect();
+    (!text.is_empty()).then_some(text)
+}
+
+/// System group from the bindings of a GetResponse
+pub fn system_info(bindings: &[(String, SnmpValue)]) -> SnmpInfo {
+    let mut info = SnmpInfo::default();
+    for (oid, value) in bindings {
+        match (oid.as_str(), value) {
+            (SYS_DESCR, SnmpValue::Text(text)) => info.sys_descr = clean(text),
+            (SYS_OBJECT_ID, SnmpValue::ObjectId(oid)) => info.sys_object_id = Some(oid.clone()),
+            (SYS_NAME, SnmpValue::Text(text)) => info.sys_name = clean(text),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Communities, port and timeout used for every device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnmpProbe {
+    /// Tried in order until one is answered
+    pub communities: Vec<String>,
+    pub port: u16,
+    /// Time to wait for an answer per community
+    pub timeout: Duration,
+}
+
+impl SnmpProbe {
+    /// Creates an SnmpProbe for the standard port
+    pub fn new(communities: Vec<String>) -> Self {
+        Self {
+            communities,
+            port: SNMP_PORT,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    /// Gets the system group from one agent with one community
+    pub fn get(&self, ip: IpAddr, community: &str, request_id: i32) -> Result<SnmpInfo> {
+        let agent = SocketAddr::new(ip, self.port);
+        let local: IpAddr = match ip {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let socket = UdpSocket::bind((local, 0)).context("Failed to bind SNMP socket")?;
+        let request = encode_message(community, GET_REQUEST, request_id, &[
+            (SYS_DESCR, SnmpValue::Null),
+            (SYS_OBJECT_ID, SnmpValue::Null),
+            (SYS_NAME, SnmpValue::Null),
+        ])?;
+        socket.send_to(&request, agent).with_context(|| format!("Failed to send SNMP request to {}", agent))?;
+
+        // Skip stray datagrams until the matching response or the deadline
+        let deadline = Instant::now() + self.timeout;
+        let mut buffer = [0u8; 1500];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                bail!("No SNMP response from {}", agent);
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            let (length, from) = socket
+                .recv_from(&mut buffer)
+                .map_err(|_| anyhow!("No SNMP response from {}", agent))?;
+            if from != agent {
+                continue;
+            }
+            let Ok(response) = parse_message(&buffer[..length]) else {
+                continue;
+            };
+            if response.pdu_type != GET_RESPONSE || response.request_id != request_id {
+                continue;
+            }
+            if response.error_status != 0 {
+                bail!("SNMP error status {} from {}", response.error_status, agent);
+            }
+            return Ok(system_info(&response.bindings));
+        }
+    }
+
+    /// System group from the first community that is answered
+    pub fn query(&self, ip: IpAddr) -> Result<SnmpInfo> {
+        let mut last_error = anyhow!("No SNMP communities configured");
+        for (request_id, community) in (1..).zip(&self.communities) {
+            match self.get(ip, community, request_id) {
+                Ok(info) => return Ok(info),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Queries all devices, a few at a time; devices that do not answer are left as they are
+    pub fn query_devices(&self, devices: &mut [NetworkDevice]) {
+        workers::for_each_device(devices, WORKERS, |device| device.snmp = self.query(device.ip).ok());
+    }
+}
+
+/// Parses a comma-separated community list, e.g. "public,private"
+pub fn parse_community_list(value: &str) -> Result<Vec<String>> {
+    let communities: Vec<String> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|community| !community.is_empty())
+        .map(str::to_string)
+        .collect();
+    if communities.is_empty() {
+        bail!("Empty community list");
+    }
+    Ok(communities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DeviceType, InterfaceName, MacAddress};
+
+    /// Stub agent answering GetRequests for `community` like a Brother printer; others are ignored
+    fn stub_agent(community: &'static str, datagrams: usize) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            for _ in 0..datagrams {
+                let (length, from) = socket.recv_from(&mut buffer).unwrap();
+                let request = parse_message(&buffer[..length]).unwrap();
+                if request.community != community {
+                    continue;
+                }
+                let response = encode_message(community, GET_RESPONSE, request.request_id, &[
+                    (SYS_DESCR, SnmpValue::Text("Brother NC-8300w, Firmware Ver.1.14\r\n(14.04.01)".to_string())),
+                    (SYS_OBJECT_ID, SnmpValue::ObjectId("1.3.6.1.4.1.2435.2.3.9.1".to_string())),
+                    (SYS_NAME, SnmpValue::Text("BRN3C2AF4".to_string())),
+                ])
+                .unwrap();
+                socket.send_to(&response, from).unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_encode_and_parse_round_trip() {
+        let request = encode_message("public", GET_REQUEST, 300, &[
+            (SYS_NAME, SnmpValue::Null),
+            ("1.3.6.1.4.1.41112.1.6", SnmpValue::ObjectId("2.999.3".to_string())),
+        ])
+        .unwrap();
+        // SEQUENCE, INTEGER 1, OCTET STRING "public"
+        assert_eq!(&request[2..13], &[0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c']);
+
+        let message = parse_message(&request).unwrap();
+        assert_eq!((message.community.as_str(), message.pdu_type, message.request_id), ("public", GET_REQUEST, 300));
+        assert_eq!(message.bindings, vec![
+            (SYS_NAME.to_string(), SnmpValue::Null),
+            ("1.3.6.1.4.1.41112.1.6".to_string(), SnmpValue::ObjectId("2.999.3".to_string())),
+        ]);
+
+        assert!(parse_message(&request[..request.len() - 1]).is_err());
+        assert_eq!(encode_integer(-129), vec![0xFF, 0x7F]);
+        assert_eq!(decode_integer(&[0xFF, 0x7F]).unwrap(), -129);
+    }
+
+    #[test]
+    fn test_query_stub_agent_with_second_community() {
+        let port = stub_agent("secret", 2);
+        let probe = SnmpProbe {
+            port,
+            timeout: Duration::from_millis(300),
+            ..SnmpProbe::new(vec!["public".to_string(), "secret".to_string()])
+        };
+
+        let info = probe.query(IpAddr::from([127, 0, 0, 1])).unwrap();
+        assert_eq!(info.sys_descr.as_deref(), Some("Brother NC-8300w, Firmware Ver.1.14 (14.04.01)"));
+        assert_eq!(info.sys_name.as_deref(), Some("BRN3C2AF4"));
+        assert_eq!(info.enterprise().map(|enterprise| enterprise.vendor), Some("Brother"));
+    }
+
+    #[test]
+    fn test_answers_feed_identity() {
+        let port = stub_agent("public", 1);
+        let probe = SnmpProbe { port, ..SnmpProbe::new(vec![DEFAULT_COMMUNITY.to_string()]) };
+        let mut devices = vec![NetworkDevice::new(
+            IpAddr::from([127, 0, 0, 1]),
+            MacAddress::new("AA:BB:CC:DD:EE:31".to_string()).unwrap(),
+            InterfaceName::new("lo".to_string()),
+        )];
+
+        probe.query_devices(&mut devices);
+        devices[0].build_identity();
+
+        let identity = &devices[0].identity;
+        assert_eq!(identity.device_type, DeviceType::Printer);
+        assert_eq!(identity.manufacturer.as_ref().map(|m| m.as_str()), Some("Brother"));
+        assert_eq!(identity.model.as_ref().map(|m| m.as_str()), Some("Brother NC-8300w"));
+        assert_eq!(identity.friendly_name.as_ref().map(|n| n.as_str()), Some("BRN3C2AF4"));
+    }
+
+    #[test]
+    fn test_parse_community_list() {
+        assert_eq!(parse_community_list("public, private").unwrap(), vec!["public", "private"]);
+        assert!(parse_community_list(" , ").is_err());
+    }
+}
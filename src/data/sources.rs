@@ -0,0 +1,256 @@
+//! Sources of the network state the collector starts from.
+//!
+//! File-backed sources read below a root directory, "/" for the live system,
+//! so the collector can run against fixture trees.
+//!
+//! Interfaces are the exception: live scans use `SystemInterfaces` (getifaddrs),
+//! while fixture trees go through `ProcInterfaces`, which rebuilds them from
+//! fib_trie and the routing table. Fixture tests do not cover the live path.
+
+use crate::data::mdns_discovery::MdnsDiscovery;
+use crate::data::proc_parsers;
+use crate::data::ssdp_discovery::{SsdpDiscovery, UpnpDeviceInfo};
+use crate::domain::{Gateway, InterfaceName, MacAddress, NetworkDevice, NetworkInterface, ServiceInfo};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Network interfaces of this host with their first IPv4 address
+pub trait InterfaceSource {
+    fn interfaces(&self) -> Result<Vec<NetworkInterface>>;
+}
+
+/// Neighbor table: devices with a resolved MAC address
+pub trait NeighborSource {
+    fn neighbors(&self) -> Result<Vec<NetworkDevice>>;
+}
+
+/// Routing table
+pub trait RouteSource {
+    fn default_gateway(&self) -> Result<Option<Gateway>>;
+}
+
+/// Resolver configuration
+pub trait ResolverSource {
+    fn dns_servers(&self) -> Result<Vec<IpAddr>>;
+}
+
+/// mDNS services by device address
+pub trait MdnsSource {
+    fn discover_services(&self, timeout: Duration) -> Result<HashMap<IpAddr, Vec<ServiceInfo>>>;
+}
+
+/// SSDP/UPnP devices by address
+pub trait SsdpSource {
+    fn discover_devices(&self, timeout: Duration) -> Result<HashMap<IpAddr, UpnpDeviceInfo>>;
+}
+
+fn read(root: &Path, path: &str) -> Result<String> {
+    let path = root.join(path);
+    std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Interfaces as reported by the operating system, used for live scans
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemInterfaces;
+
+impl InterfaceSource for SystemInterfaces {
+    fn interfaces(&self) -> Result<Vec<NetworkInterface>> {
+        proc_parsers::get_network_interfaces()
+    }
+}
+
+/// Interfaces from proc/net/fib_trie, proc/net/route and sys/class/net below a root directory
+/// Each local address belongs to the interface routing its subnet. This heuristic is
+/// only used for `--sysroot` trees; it can differ from `SystemInterfaces`, e.g. for
+/// addresses without a subnet route.
+#[derive(Debug, Clone)]
+pub struct ProcInterfaces {
+    root: PathBuf,
+}
+
+impl ProcInterfaces {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Default for ProcInterfaces {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl InterfaceSource for ProcInterfaces {
+    fn interfaces(&self) -> Result<Vec<NetworkInterface>> {
+        let addresses = proc_parsers::parse_local_addresses(&read(&self.root, "proc/net/fib_trie")?);
+        let routes = proc_parsers::parse_routes(&read(&self.root, "proc/net/route")?);
+
+        let mut interfaces: Vec<NetworkInterface> = Vec::new();
+        for address in addresses {
+            // Loopback has no entry in the main routing table
+            let name = match address.is_loopback() {
+                true => InterfaceName::new("lo".to_string()),
+                false => {
+                    let Some(route) = routes.iter()
+                        .filter(|route| !route.is_default() && route.contains(address))
+                        .max_by_key(|route| u32::from(route.mask).count_ones())
+                    else {
+                        continue;
+                    };
+                    route.interface.clone()
+                }
+            };
+            if interfaces.iter().any(|interface| interface.name == name) {
+                continue;
+            }
+
+            let mac = read(&self.root, &format!("sys/class/net/{}/address", name.as_str()))
+                .ok()
+                .and_then(|mac| MacAddress::new(mac.trim().to_string()).ok());
+            interfaces.push(NetworkInterface::new(name, IpAddr::V4(address), mac));
+        }
+        Ok(interfaces)
+    }
+}
+
+/// Neighbor table from proc/net/arp below a root directory
+#[derive(Debug, Clone)]
+pub struct ProcNeighbors {
+    root: PathBuf,
+}
+
+impl ProcNeighbors {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Default for ProcNeighbors {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl NeighborSource for ProcNeighbors {
+    fn neighbors(&self) -> Result<Vec<NetworkDevice>> {
+        Ok(proc_parsers::parse_arp_table(&read(&self.root, "proc/net/arp")?))
+    }
+}
+
+/// Routing table from proc/net/route below a root directory
+#[derive(Debug, Clone)]
+pub struct ProcRoutes {
+    root: PathBuf,
+}
+
+impl ProcRoutes {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Default for ProcRoutes {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl RouteSource for ProcRoutes {
+    fn default_gateway(&self) -> Result<Option<Gateway>> {
+        Ok(proc_parsers::parse_default_gateway(&read(&self.root, "proc/net/route")?))
+    }
+}
+
+/// Resolver configuration from etc/resolv.conf below a root directory
+#[derive(Debug, Clone)]
+pub struct ResolvConf {
+    root: PathBuf,
+}
+
+impl ResolvConf {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl ResolverSource for ResolvConf {
+    fn dns_servers(&self) -> Result<Vec<IpAddr>> {
+        Ok(proc_parsers::parse_dns_servers(&read(&self.root, "etc/resolv.conf")?))
+    }
+}
+
+/// mDNS browsing on the live network, with a fresh daemon for every scan
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiveMdns;
+
+impl MdnsSource for LiveMdns {
+    fn discover_services(&self, timeout: Duration) -> Result<HashMap<IpAddr, Vec<ServiceInfo>>> {
+        MdnsDiscovery::new()?.discover_services(timeout)
+    }
+}
+
+impl SsdpSource for SsdpDiscovery {
+    fn discover_devices(&self, timeout: Duration) -> Result<HashMap<IpAddr, UpnpDeviceInfo>> {
+        SsdpDiscovery::discover_devices(self, timeout)
+    }
+}
+
+/// Fixed services, e.g. none when running against a fixture tree
+impl MdnsSource for HashMap<IpAddr, Vec<ServiceInfo>> {
+    fn discover_services(&self, _timeout: Duration) -> Result<HashMap<IpAddr, Vec<ServiceInfo>>> {
+        Ok(self.clone())
+    }
+}
+
+/// Fixed UPnP devices, e.g. none when running against a fixture tree
+impl SsdpSource for HashMap<IpAddr, UpnpDeviceInfo> {
+    fn discover_devices(&self, _timeout: Duration) -> Result<HashMap<IpAddr, UpnpDeviceInfo>> {
+        Ok(self.clone())
+    }
+}
+
+/// Directory of the fixture tree used in tests
+#[cfg(test)]
+pub fn fixture_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/data/fixtures/sysroot")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_file_sources_read_fixture_tree() {
+        let root = fixture_root();
+
+        let interfaces = ProcInterfaces::new(&root).interfaces().unwrap();
+        let summary: Vec<_> = interfaces.iter()
+            .map(|interface| (interface.name.as_str(), interface.ip.to_string(), interface.mac.as_ref().map(MacAddress::to_string)))
+            .collect();
+        assert_eq!(summary, vec![
+            ("lo", "127.0.0.1".to_string(), Some("00:00:00:00:00:00".to_string())),
+            ("eth0", "192.168.1.20".to_string(), Some("3C:7C:3F:12:34:56".to_string())),
+        ]);
+
+        assert_eq!(ProcNeighbors::new(&root).neighbors().unwrap().len(), 3);
+        assert_eq!(ProcRoutes::new(&root).default_gateway().unwrap().map(|gateway| gateway.0),
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
+        assert_eq!(ResolvConf::new(&root).dns_servers().unwrap(), vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))]);
+    }
+
+    #[test]
+    fn test_missing_file_names_path() {
+        let error = ProcNeighbors::new("/nonexistent").neighbors().unwrap_err();
+        assert!(format!("{:#}", error).contains("/nonexistent/proc/net/arp"));
+    }
+}
@@ -2,10 +2,14 @@
 
 use anyhow::Result;
 use futures::StreamExt;
+use ssdp_client::{SearchTarget, URN};
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::time::Duration;
 
+/// Device type searched for in addition to root devices
+const INTERNET_GATEWAY: URN = URN::device("schemas-upnp-org", "InternetGatewayDevice", 1);
+
 /// UPnP device information discovered via SSDP
 #[derive(Debug, Clone)]
 pub struct UpnpDeviceInfo {
@@ -13,6 +17,8 @@ pub struct UpnpDeviceInfo {
     pub manufacturer: Option<String>,
     pub model_name: Option<String>,
     pub device_type: Option<String>,
+    /// URL of the device description
+    pub location: Option<String>,
 }
 
 impl UpnpDeviceInfo {
@@ -22,6 +28,7 @@ impl UpnpDeviceInfo {
             manufacturer: None,
             model_name: None,
             device_type: None,
+            location: None,
         }
     }
 }
@@ -55,10 +62,11 @@ impl SsdpDiscovery {
     async fn discover_devices_async(&self, timeout: Duration) -> Result<HashMap<IpAddr, UpnpDeviceInfo>> {
         let mut devices: HashMap<IpAddr, UpnpDeviceInfo> = HashMap::new();
 
-        // Search for all UPnP root devices
-        let search_target = ssdp_client::SearchTarget::RootDevice;
-
-        let mut responses = ssdp_client::search(&search_target, timeout, 2, None).await?;
+        // Search for all UPnP root devices, and separately for gateways so they can be told apart
+        // without fetching every description
+        let root_devices = ssdp_client::search(&SearchTarget::RootDevice, timeout, 2, None).await?;
+        let gateways = ssdp_client::search(&INTERNET_GATEWAY.into(), timeout, 2, None).await?;
+        let mut responses = std::pin::pin!(futures::stream::select(root_devices, gateways));
 
         // Collect responses with timeout
         let deadline = tokio::time::Instant::now() + timeout;
@@ -68,14 +76,15 @@ impl SsdpDiscovery {
                 Ok(Some(Ok(response))) => {
                     // Extract IP address from the location URL
                     if let Some(ip) = Self::extract_ip_from_location(response.location()) {
-                        let device_info = UpnpDeviceInfo {
-                            friendly_name: None,
-                            manufacturer: None,
-                            model_name: None,
+                        let device_info = devices.entry(ip).or_insert_with(|| UpnpDeviceInfo {
                             device_type: Some(format!("{:?}", response.search_target())),
-                        };
-
-                        devices.insert(ip, device_info);
+                            location: Some(response.location().to_string()),
+                            ..UpnpDeviceInfo::new()
+                        });
+                        // The gateway answer names the device type, which a root device answer does not
+                        if *response.search_target() == SearchTarget::URN(INTERNET_GATEWAY) {
+                            device_info.device_type = Some(INTERNET_GATEWAY.to_string());
+                        }
                     }
                 }
                 Ok(Some(Err(_))) => {
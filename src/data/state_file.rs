@@ -0,0 +1,76 @@
+//! JSON files in the state directory, replaced atomically.
+//!
+//! Several bar instances (one per monitor) may save the same file at once, so
+//! every write goes to its own temporary file before being renamed into place.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Reads a JSON state file; Ok(None) when it does not exist yet
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json)
+        .map(Some)
+        .with_context(|| format!("Invalid state file {}", path.display()))
+}
+
+/// Writes a JSON state file through a temporary file, creating its directory if needed
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    replace(path, serde_json::to_string_pretty(value)?)
+}
+
+/// Replaces `path` with `contents` through a temporary file that is removed if the rename fails
+pub fn replace(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let tmp_path = temporary_path(path);
+    std::fs::write(&tmp_path, contents)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path).with_context(|| {
+        let _ = std::fs::remove_file(&tmp_path);
+        format!("Failed to move {} into place", path.display())
+    })
+}
+
+/// Temporary file next to `path`, unique per process and write
+fn temporary_path(path: &Path) -> PathBuf {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_concurrent_saves_leave_one_valid_file() {
+        let dir = std::env::temp_dir().join(format!("waybar_lan_state_file_{}", std::process::id()));
+        let path = dir.join("sample.json");
+        assert_eq!(load::<BTreeMap<String, u32>>(&path).unwrap(), None);
+
+        std::thread::scope(|scope| {
+            for writer in 0..8 {
+                let path = &path;
+                scope.spawn(move || save(path, &BTreeMap::from([("writer".to_string(), writer)])).unwrap());
+            }
+        });
+
+        let saved: BTreeMap<String, u32> = load(&path).unwrap().unwrap();
+        assert!(saved["writer"] < 8);
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+//! Persistent record of devices seen across runs.
+
+use crate::data::state_file;
+use crate::domain::{Hostname, InterfaceName, MacAddress, NetworkData};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Devices first seen within this window are reported as new
+pub const NEW_DEVICE_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Last-seen times are only advanced on disk in steps of this size, so that
+/// repeated scans of an unchanged network do not rewrite the store
+pub const LAST_SEEN_RESOLUTION: Duration = Duration::from_secs(60);
+
+/// Last known whereabouts of a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredDevice {
+    pub mac: MacAddress,
+    pub ip: IpAddr,
+    pub interface: InterfaceName,
+    pub name: String,
+    /// Resolved hostname, absent in stores written by older versions
+    #[serde(default)]
+    pub hostname: Option<String>,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+}
+
+/// Devices seen in previous scans, persisted as JSON in the state directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceStore {
+    #[serde(skip)]
+    path: PathBuf,
+    /// Devices present when the store was created are the baseline, never "new"
+    created_at: SystemTime,
+    devices: BTreeMap<String, StoredDevice>,
+}
+
+impl DeviceStore {
+    /// File name of the store inside the state directory
+    pub const FILE_NAME: &'static str = "devices.json";
+
+    /// Loads the store from a state directory, starting empty if it does not exist yet
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(Self::FILE_NAME);
+        let store: Option<Self> = state_file::load(&path)?;
+        Ok(match store {
+            Some(store) => Self { path, ..store },
+            None => Self {
+                path,
+                created_at: SystemTime::now(),
+                devices: BTreeMap::new(),
+            },
+        })
+    }
+
+    /// Writes the store back to disk, creating the state directory if needed
+    pub fn save(&self) -> Result<()> {
+        state_file::save(&self.path, self)
+    }
+
+    /// Records all devices in a snapshot, marks recently first-seen ones as new and
+    /// fills in when each device last answered
+    ///
+    /// Devices that answered a ping, mDNS or SSDP in this scan are seen now. Devices
+    /// known only from a lingering neighbor entry keep the last-seen time from the store.
+    /// Returns whether the store changed and needs saving
+    pub fn record(&mut self, network_data: &mut NetworkData) -> bool {
+        let now = SystemTime::now();
+        let is_baseline = self.devices.is_empty();
+        let mut changed = false;
+
+        for device in &mut network_data.devices {
+            let first_seen = if is_baseline { self.created_at } else { now };
+            let entry = self.devices
+                .entry(device.mac.to_string())
+                .or_insert_with(|| {
+                    changed = true;
+                    StoredDevice {
+                        mac: device.mac.clone(),
+                        ip: device.ip,
+                        interface: device.interface_name.clone(),
+                        name: String::new(),
+                        hostname: None,
+                        first_seen,
+                        last_seen: now,
+                    }
+                });
+
+            let hostname = match &device.hostname {
+                Hostname::Resolved(name) => Some(name.clone()),
+                _ => entry.hostname.clone(),
+            };
+            let updated = StoredDevice {
+                mac: entry.mac.clone(),
+                ip: device.ip,
+                interface: device.interface_name.clone(),
+                name: device.identity.name(),
+                hostname,
+                first_seen: entry.first_seen,
+                last_seen: entry.last_seen,
+            };
+            if updated != *entry {
+                *entry = updated;
+                changed = true;
+            }
+
+            let answered = device.latency.is_some() || !device.services.is_empty() || device.upnp_info.is_some();
+            if !answered {
+                device.last_seen = entry.last_seen;
+            } else if now.duration_since(entry.last_seen).unwrap_or_default() >= LAST_SEEN_RESOLUTION {
+                entry.last_seen = now;
+                changed = true;
+            }
+
+            let age = now.duration_since(entry.first_seen).unwrap_or_default();
+            device.is_new = entry.first_seen > self.created_at && age < NEW_DEVICE_WINDOW;
+        }
+        changed
+    }
+
+    /// Finds a stored device by IP address, MAC address or (partial) name
+    /// Names are matched case-insensitively, exact matches first. A name that matches
+    /// several devices is an error listing them, rather than a guess.
+    pub fn find(&self, query: &str) -> Result<Option<&StoredDevice>> {
+        if let Ok(ip) = query.parse::<IpAddr>() {
+            return Ok(self.devices.values().find(|d| d.ip == ip));
+        }
+        if let Ok(mac) = MacAddress::new(query.to_string()) {
+            return Ok(self.devices.get(&mac.to_string()));
+        }
+
+        let query_lower = query.to_lowercase();
+        let names = |d: &StoredDevice| [d.hostname.as_deref(), Some(d.name.as_str())]
+            .into_iter()
+            .flatten()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        let mut matches: Vec<_> = self.devices.values().filter(|d| names(d).contains(&query_lower)).collect();
+        if matches.is_empty() {
+            matches = self.devices.values().filter(|d| names(d).iter().any(|n| n.contains(&query_lower))).collect();
+        }
+        match matches.as_slice() {
+            [] => Ok(None),
+            [device] => Ok(Some(device)),
+            candidates => {
+                let candidates: Vec<_> = candidates
+                    .iter()
+                    .map(|d| format!("{} ({}, {})", d.hostname.as_deref().unwrap_or(&d.name), d.ip, d.mac))
+                    .collect();
+                bail!("'{}' matches {} devices: {}", query, candidates.len(), candidates.join(", "))
+            }
+        }
+    }
+}
+
+/// Default state directory: $XDG_STATE_HOME/waybar_lan or ~/.local/state/waybar_lan
+pub fn default_state_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("waybar_lan")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ActivityStatus, NetworkDevice};
+    use std::net::Ipv4Addr;
+
+    fn snapshot(macs: &[&str]) -> NetworkData {
+        let devices = macs
+            .iter()
+            .enumerate()
+            .map(|(i, mac)| NetworkDevice::new(
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10 + i as u8)),
+                MacAddress::new(mac.to_string()).unwrap(),
+                InterfaceName::new("eth0".to_string()),
+            ))
+            .collect();
+        NetworkData::new(vec![], devices, None, vec![])
+    }
+
+    fn temp_state_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("waybar_lan_store_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_baseline_devices_are_not_new() {
+        let dir = temp_state_dir("baseline");
+        let mut store = DeviceStore::load(&dir).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:01"]);
+        store.record(&mut data);
+
+        assert!(!data.devices[0].is_new);
+    }
+
+    #[test]
+    fn test_unchanged_network_needs_no_save() {
+        let mut store = DeviceStore::load(&temp_state_dir("unchanged")).unwrap();
+        assert!(store.record(&mut snapshot(&["00:00:00:00:00:01"])));
+        assert!(!store.record(&mut snapshot(&["00:00:00:00:00:01"])));
+
+        let mut moved = snapshot(&["00:00:00:00:00:01"]);
+        moved.devices[0].ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 99));
+        assert!(store.record(&mut moved));
+    }
+
+    #[test]
+    fn test_last_seen_comes_from_store_for_silent_devices() {
+        let dir = temp_state_dir("last_seen");
+        let mut store = DeviceStore::load(&dir).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:01", "00:00:00:00:00:02"]);
+        data.devices[0].latency = Some(Duration::from_millis(2));
+        data.devices[1].latency = Some(Duration::from_millis(3));
+        store.record(&mut data);
+        // Pretend both last answered an hour ago
+        for device in store.devices.values_mut() {
+            device.last_seen -= Duration::from_secs(3600);
+        }
+        store.save().unwrap();
+
+        // Only the first device answers this time; the second is just a neighbor entry
+        let mut store = DeviceStore::load(&dir).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:01", "00:00:00:00:00:02"]);
+        data.devices[0].latency = Some(Duration::from_millis(2));
+        assert!(store.record(&mut data));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(data.devices[0].activity_status(), ActivityStatus::Active);
+        assert_eq!(data.devices[1].activity_status(), ActivityStatus::Stale);
+        assert!(data.devices[0].last_seen > data.devices[1].last_seen);
+    }
+
+    #[test]
+    fn test_new_device_detected_and_persisted() {
+        let dir = temp_state_dir("persist");
+        let mut store = DeviceStore::load(&dir).unwrap();
+        store.record(&mut snapshot(&["00:00:00:00:00:01"]));
+        store.save().unwrap();
+
+        let mut store = DeviceStore::load(&dir).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:01", "00:00:00:00:00:02"]);
+        store.record(&mut data);
+        store.save().unwrap();
+
+        assert!(!data.devices[0].is_new);
+        assert!(data.devices[1].is_new);
+
+        // First-seen times survive a reload
+        let mut reloaded = DeviceStore::load(&dir).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:02"]);
+        reloaded.record(&mut data);
+        assert!(data.devices[0].is_new);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_by_mac_ip_and_hostname() {
+        let mut store = DeviceStore::load(&temp_state_dir("find")).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:01", "00:00:00:00:00:02"]);
+        data.devices[1].hostname = Hostname::Resolved("nas.lan".to_string());
+        store.record(&mut data);
+
+        let find_mac = |query| store.find(query).unwrap().map(|d| d.mac.to_string());
+        assert_eq!(find_mac("00-00-00-00-00-01").as_deref(), Some("00:00:00:00:00:01"));
+        assert_eq!(find_mac("192.168.1.11").as_deref(), Some("00:00:00:00:00:02"));
+        assert_eq!(find_mac("NAS").as_deref(), Some("00:00:00:00:00:02"));
+        assert_eq!(find_mac("printer"), None);
+        assert_eq!(store.find("nas.lan").unwrap().unwrap().interface.as_str(), "eth0");
+    }
+
+    #[test]
+    fn test_ambiguous_name_lists_candidates() {
+        let mut store = DeviceStore::load(&temp_state_dir("ambiguous")).unwrap();
+        let mut data = snapshot(&["00:00:00:00:00:01", "00:00:00:00:00:02", "00:00:00:00:00:03"]);
+        data.devices[0].hostname = Hostname::Resolved("nas-backup.lan".to_string());
+        data.devices[1].hostname = Hostname::Resolved("nas.lan".to_string());
+        data.devices[2].hostname = Hostname::Resolved("nas-media.lan".to_string());
+        store.record(&mut data);
+
+        // An exact name still picks its device
+        assert_eq!(store.find("nas.lan").unwrap().unwrap().mac.to_string(), "00:00:00:00:00:02");
+
+        let error = store.find("nas-").unwrap_err().to_string();
+        assert!(error.starts_with("'nas-' matches 2 devices:"), "{}", error);
+        assert!(error.contains("nas-backup.lan (192.168.1.10, 00:00:00:00:00:01)"), "{}", error);
+        assert!(error.contains("nas-media.lan (192.168.1.12, 00:00:00:00:00:03)"), "{}", error);
+    }
+}
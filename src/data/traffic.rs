@@ -0,0 +1,168 @@
+//! Per-interface throughput from /proc/net/dev byte counters.
+
+use crate::data::state_file;
+use crate::domain::{NetworkData, Throughput};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Samples older than this are not used for rates; the average would hide current load
+pub const MAX_SAMPLE_AGE: Duration = Duration::from_secs(600);
+
+/// Received and transmitted byte counters of one interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Byte counters of all interfaces at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrafficSample {
+    pub taken_at: SystemTime,
+    pub counters: BTreeMap<String, InterfaceCounters>,
+}
+
+impl TrafficSample {
+    /// File name of the previous sample inside the state directory
+    pub const FILE_NAME: &'static str = "traffic.json";
+
+    /// Reads the current counters from proc/net/dev below `root`
+    pub fn read(root: &Path) -> Result<Self> {
+        let path = root.join("proc/net/dev");
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self {
+            taken_at: SystemTime::now(),
+            counters: parse_proc_net_dev(&content),
+        })
+    }
+
+    /// Loads the sample saved by the previous run, if any
+    pub fn load(state_dir: &Path) -> Result<Option<Self>> {
+        state_file::load(&state_dir.join(Self::FILE_NAME))
+    }
+
+    /// Saves the sample for the next run, creating the state directory if needed
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        state_file::save(&state_dir.join(Self::FILE_NAME), self)
+    }
+
+    /// Average rates per interface since an earlier sample
+    /// Interfaces whose counters went backwards (reset or replaced) are left out.
+    pub fn rates_since(&self, previous: &TrafficSample) -> BTreeMap<String, Throughput> {
+        let Ok(elapsed) = self.taken_at.duration_since(previous.taken_at) else {
+            return BTreeMap::new();
+        };
+        if elapsed.is_zero() || elapsed > MAX_SAMPLE_AGE {
+            return BTreeMap::new();
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.counters
+            .iter()
+            .filter_map(|(name, current)| {
+                let before = previous.counters.get(name)?;
+                let rx = current.rx_bytes.checked_sub(before.rx_bytes)?;
+                let tx = current.tx_bytes.checked_sub(before.tx_bytes)?;
+                Some((name.clone(), Throughput {
+                    rx_bytes_per_sec: rx as f64 / seconds,
+                    tx_bytes_per_sec: tx as f64 / seconds,
+                }))
+            })
+            .collect()
+    }
+}
+
+/// Parses byte counters from /proc/net/dev
+///
+/// ```text
+/// Inter-|   Receive                                                |  Transmit
+///  face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets ...
+///   eth0: 1234567    2345    0    0    0     0          0         0   765432    1234 ...
+/// ```
+pub fn parse_proc_net_dev(content: &str) -> BTreeMap<String, InterfaceCounters> {
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, fields) = line.split_once(':')?;
+            let fields: Vec<&str> = fields.split_whitespace().collect();
+            Some((name.trim().to_string(), InterfaceCounters {
+                rx_bytes: fields.first()?.parse().ok()?,
+                tx_bytes: fields.get(8)?.parse().ok()?,
+            }))
+        })
+        .collect()
+}
+
+/// Attaches rates since the previous run's sample and saves the current one
+pub fn record_throughput(root: &Path, state_dir: &Path, network_data: &mut NetworkData) -> Result<()> {
+    let current = TrafficSample::read(root)?;
+    // A corrupt sample is simply replaced; rates resume with the next run
+    let previous = TrafficSample::load(state_dir).unwrap_or_default();
+
+    if let Some(previous) = previous {
+        let mut rates = current.rates_since(&previous);
+        for interface in &mut network_data.interfaces {
+            interface.throughput = rates.remove(interface.name.as_str());
+        }
+    }
+    current.save(state_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:   52000     400    0    0    0     0          0         0    52000     400    0    0    0     0       0          0
+  eth0: 1000000    2345    0    0    0     0          0        12   500000    1234    0    0    0     0       0          0
+";
+
+    #[test]
+    fn test_parse_proc_net_dev() {
+        let counters = parse_proc_net_dev(PROC_NET_DEV);
+        assert_eq!(counters.len(), 2);
+        assert_eq!(counters["eth0"], InterfaceCounters { rx_bytes: 1_000_000, tx_bytes: 500_000 });
+    }
+
+    #[test]
+    fn test_rates_since_previous_sample() {
+        let previous = TrafficSample {
+            taken_at: SystemTime::UNIX_EPOCH,
+            counters: parse_proc_net_dev(PROC_NET_DEV),
+        };
+        let mut current = previous.clone();
+        current.taken_at += Duration::from_secs(10);
+        current.counters.insert("eth0".to_string(), InterfaceCounters { rx_bytes: 3_000_000, tx_bytes: 600_000 });
+        // Counters of a re-created interface start over
+        current.counters.insert("lo".to_string(), InterfaceCounters { rx_bytes: 10, tx_bytes: 10 });
+
+        let rates = current.rates_since(&previous);
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates["eth0"], Throughput { rx_bytes_per_sec: 200_000.0, tx_bytes_per_sec: 10_000.0 });
+
+        current.taken_at += MAX_SAMPLE_AGE;
+        assert!(current.rates_since(&previous).is_empty());
+    }
+
+    #[test]
+    fn test_sample_persisted_between_runs() {
+        let dir = std::env::temp_dir().join(format!("waybar_lan_traffic_{}", std::process::id()));
+        assert_eq!(TrafficSample::load(&dir).unwrap(), None);
+
+        let sample = TrafficSample {
+            taken_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            counters: parse_proc_net_dev(PROC_NET_DEV),
+        };
+        sample.save(&dir).unwrap();
+        assert_eq!(TrafficSample::load(&dir).unwrap(), Some(sample));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
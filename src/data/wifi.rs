@@ -0,0 +1,158 @@
+//! Wireless link details from nl80211 (via `iw`) with a /proc/net/wireless fallback.
+//!
+//! `iw` documents its output as unstable and not meant for scripts, so parsing it
+//! is best effort: fields it stops printing, or prints differently, are left out.
+
+use crate::domain::{InterfaceName, MacAddress, NetworkInterface, WifiLink};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::process::Command;
+
+/// Parses `iw dev <interface> link`; returns None when not connected
+/// Unknown lines are skipped, so changes in iw's output lose fields rather than the link
+///
+/// ```text
+/// Connected to 00:11:22:33:44:55 (on wlp3s0)
+///         SSID: HomeNet
+///         freq: 5180
+///         signal: -52 dBm
+///         tx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2
+/// ```
+pub fn parse_iw_link(output: &str) -> Option<WifiLink> {
+    let mut lines = output.lines();
+    let bssid = lines.next()?.strip_prefix("Connected to ")?.split_whitespace().next()?;
+
+    let mut link = WifiLink {
+        bssid: MacAddress::new(bssid.to_string()).ok(),
+        ..WifiLink::default()
+    };
+    for line in lines {
+        let Some((key, value)) = line.trim().split_once(": ") else {
+            continue;
+        };
+        let number = value.split_whitespace().next().unwrap_or_default();
+        match key {
+            "SSID" => link.ssid = Some(value.to_string()),
+            // Newer iw versions print "freq: 5180.0"
+            "freq" => link.frequency_mhz = number.parse::<f64>().ok().map(|freq| freq as u32),
+            "signal" => link.signal_dbm = number.parse().ok(),
+            "tx bitrate" => link.tx_bitrate_mbps = number.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(link)
+}
+
+/// Parses the signal level of one interface from /proc/net/wireless
+///
+/// ```text
+/// Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+///  face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+/// wlp3s0: 0000   54.  -56.  -256        0      0      0      0     18        0
+/// ```
+pub fn parse_proc_wireless(content: &str, interface: &InterfaceName) -> Option<WifiLink> {
+    let fields = content.lines().skip(2).find_map(|line| {
+        let (name, fields) = line.split_once(':')?;
+        (name.trim() == interface.as_str()).then_some(fields)
+    })?;
+    let level = fields.split_whitespace().nth(2)?.trim_end_matches('.');
+    Some(WifiLink {
+        signal_dbm: level.parse().ok(),
+        ..WifiLink::default()
+    })
+}
+
+/// Reads link details of a wireless interface; Ok(None) when it is not connected
+pub fn read_wifi_link(interface: &InterfaceName) -> Result<Option<WifiLink>> {
+    let iw = Command::new("iw").args(["dev", interface.as_str(), "link"]).output();
+    if let Ok(output) = iw
+        && output.status.success()
+    {
+        return Ok(parse_iw_link(&String::from_utf8_lossy(&output.stdout)));
+    }
+
+    let content = fs::read_to_string("/proc/net/wireless")
+        .context("iw is unavailable and /proc/net/wireless could not be read")?;
+    match parse_proc_wireless(&content, interface) {
+        Some(link) => Ok(Some(link)),
+        None if content.lines().count() > 2 => Ok(None),
+        None => bail!("No wireless statistics for {}", interface),
+    }
+}
+
+/// Adds link details to every wireless interface
+/// A failing interface keeps no details and is named in the returned error; the others are unaffected
+pub fn add_wifi_links(
+    interfaces: Vec<NetworkInterface>,
+    read_link: impl Fn(&InterfaceName) -> Result<Option<WifiLink>>,
+) -> (Vec<NetworkInterface>, Option<String>) {
+    let mut errors = Vec::new();
+    let interfaces = interfaces
+        .into_iter()
+        .map(|interface| {
+            if !interface.name.is_wireless() {
+                return interface;
+            }
+            match read_link(&interface.name) {
+                Ok(link) => interface.with_wifi(link),
+                Err(e) => {
+                    errors.push(format!("{}: {:#}", interface.name, e));
+                    interface
+                }
+            }
+        })
+        .collect();
+    (interfaces, (!errors.is_empty()).then(|| errors.join("; ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iw_link() {
+        let output = "Connected to 00:11:22:33:44:55 (on wlp3s0)\n\
+            \tSSID: Home Net\n\
+            \tfreq: 5180.0\n\
+            \tRX: 1234 bytes (10 packets)\n\
+            \tsignal: -52 dBm\n\
+            \trx bitrate: 780.0 MBit/s VHT-MCS 8 80MHz VHT-NSS 2\n\
+            \ttx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2\n";
+        let link = parse_iw_link(output).unwrap();
+
+        assert_eq!(link.ssid.as_deref(), Some("Home Net"));
+        assert_eq!(link.bssid.as_ref().unwrap().to_string(), "00:11:22:33:44:55");
+        assert_eq!((link.frequency_mhz, link.channel()), (Some(5180), Some(36)));
+        assert_eq!((link.signal_dbm, link.signal_quality()), (Some(-52), Some(96)));
+        assert_eq!(link.tx_bitrate_mbps, Some(866.7));
+
+        assert_eq!(parse_iw_link("Not connected.\n"), None);
+    }
+
+    #[test]
+    fn test_parse_proc_wireless() {
+        let content = "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+            face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n\
+            wlp3s0: 0000   54.  -56.  -256        0      0      0      0     18        0\n";
+        let wlan = InterfaceName::new("wlp3s0".to_string());
+        assert_eq!(parse_proc_wireless(content, &wlan).unwrap().signal_dbm, Some(-56));
+        assert_eq!(parse_proc_wireless(content, &InterfaceName::new("wlan1".to_string())), None);
+    }
+
+    #[test]
+    fn test_failing_interface_keeps_others() {
+        let interface = |name: &str| NetworkInterface::new(InterfaceName::new(name.to_string()),
+            std::net::IpAddr::from([192, 168, 1, 20]), None);
+        let interfaces = vec![interface("eth0"), interface("wlan0"), interface("wlan1")];
+
+        let (interfaces, error) = add_wifi_links(interfaces, |name| match name.as_str() {
+            "wlan0" => bail!("driver hiccup"),
+            _ => Ok(Some(WifiLink { signal_dbm: Some(-60), ..WifiLink::default() })),
+        });
+
+        assert_eq!(error.as_deref(), Some("wlan0: driver hiccup"));
+        assert!(interfaces[0].wifi.is_none());
+        assert!(interfaces[1].wifi.is_none());
+        assert_eq!(interfaces[2].wifi.as_ref().and_then(|wifi| wifi.signal_dbm), Some(-60));
+    }
+}
@@ -0,0 +1,149 @@
+//! Wake-on-LAN magic packets.
+//!
+//! Packets go out as a UDP broadcast and, where raw sockets are permitted,
+//! as an Ethernet frame with the Wake-on-LAN EtherType.
+
+use crate::domain::{InterfaceName, MacAddress};
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// UDP port conventionally used for Wake-on-LAN ("discard")
+pub const WOL_PORT: u16 = 9;
+
+/// EtherType of raw Wake-on-LAN frames
+pub const ETHERTYPE_WOL: u16 = 0x0842;
+
+/// How a magic packet was sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeReport {
+    /// Interface the device was last seen on, if it was known
+    pub interface: Option<InterfaceName>,
+    /// Why the UDP broadcast could not be bound to that interface and went out unbound
+    pub bind_error: Option<String>,
+    /// Whether the raw Ethernet frame went out as well
+    pub raw_ethernet: bool,
+}
+
+/// Builds a magic packet: 6 bytes of 0xFF followed by the MAC repeated 16 times
+pub fn magic_packet(mac: &MacAddress) -> [u8; 102] {
+    let mut packet = [0xFFu8; 102];
+    let octets = mac.octets();
+    for chunk in packet[6..].chunks_mut(6) {
+        chunk.copy_from_slice(&octets);
+    }
+    packet
+}
+
+/// Sends a magic packet as a UDP broadcast, out of `interface` if given
+/// Returns why the socket could not be bound to the interface, in which case the broadcast went out unbound.
+pub fn send_udp(packet: &[u8], interface: Option<&InterfaceName>) -> Result<Option<std::io::Error>> {
+    let (socket, bind_error) = broadcast_socket(interface)?;
+    let target = SocketAddr::from((Ipv4Addr::BROADCAST, WOL_PORT));
+    socket
+        .send_to(packet, target)
+        .with_context(|| format!("Failed to send magic packet to {}", target))?;
+    Ok(bind_error)
+}
+
+/// Broadcast socket bound to `interface` if possible
+/// SO_BINDTODEVICE needs CAP_NET_RAW on kernels before 5.7; without it the socket stays unbound.
+fn broadcast_socket(interface: Option<&InterfaceName>) -> Result<(UdpSocket, Option<std::io::Error>)> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).context("Failed to bind UDP socket")?;
+    socket.set_broadcast(true)?;
+    let bind_error = interface.and_then(|interface| bind_to_device(&socket, interface).err());
+    Ok((socket, bind_error))
+}
+
+fn bind_to_device(socket: &UdpSocket, interface: &InterfaceName) -> std::io::Result<()> {
+    let name = interface.as_str().as_bytes();
+    let result = unsafe {
+        libc::setsockopt(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_BINDTODEVICE,
+            name.as_ptr().cast(), name.len() as libc::socklen_t)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// Sends a magic packet as an Ethernet broadcast frame with EtherType 0x0842
+/// Needs CAP_NET_RAW; fails with a permission error otherwise
+pub fn send_raw(packet: &[u8], interface: &InterfaceName) -> std::io::Result<()> {
+    let name = CString::new(interface.as_str())?;
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // A datagram packet socket lets the kernel build the Ethernet header
+    let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_DGRAM, ETHERTYPE_WOL.to_be().into()) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_protocol = ETHERTYPE_WOL.to_be();
+    address.sll_ifindex = index as i32;
+    address.sll_halen = 6;
+    address.sll_addr[..6].copy_from_slice(&[0xFF; 6]);
+
+    let sent = unsafe {
+        libc::sendto(socket.as_raw_fd(), packet.as_ptr().cast(), packet.len(), 0,
+            (&address as *const libc::sockaddr_ll).cast(),
+            std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+    };
+    match sent {
+        0.. => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// Wakes a device: UDP broadcast, plus a raw Ethernet frame when the interface is known
+/// and raw sockets are permitted
+pub fn wake(mac: &MacAddress, interface: Option<&InterfaceName>) -> Result<WakeReport> {
+    let packet = magic_packet(mac);
+    let bind_error = send_udp(&packet, interface)?;
+    // The raw frame is a bonus for NICs that ignore UDP; lacking CAP_NET_RAW is normal
+    let raw_ethernet = interface.is_some_and(|interface| send_raw(&packet, interface).is_ok());
+    Ok(WakeReport {
+        interface: interface.cloned(),
+        bind_error: bind_error.map(|e| e.to_string()),
+        raw_ethernet,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_magic_packet() {
+        let mac = MacAddress::new("01:23:45:67:89:AB".to_string()).unwrap();
+        let packet = magic_packet(&mac);
+
+        assert_eq!(&packet[..6], &[0xFF; 6]);
+        assert_eq!(&packet[6..12], &[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB]);
+        assert_eq!(&packet[96..], &[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB]);
+    }
+
+    #[test]
+    fn test_unbindable_interface_leaves_socket_unbound() {
+        let interface = InterfaceName::new("nonexistent0".to_string());
+        let (socket, bind_error) = broadcast_socket(Some(&interface)).unwrap();
+        assert!(bind_error.is_some());
+        assert!(socket.broadcast().unwrap());
+
+        let (_, bind_error) = broadcast_socket(None).unwrap();
+        assert!(bind_error.is_none());
+    }
+
+    #[test]
+    fn test_raw_send_on_unknown_interface_fails() {
+        let interface = InterfaceName::new("nonexistent0".to_string());
+        assert!(send_raw(&[0xFF; 102], &interface).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//! Bounded worker pool for per-device queries.
+
+use crate::domain::NetworkDevice;
+use std::sync::Mutex;
+
+/// Runs `f` on every device with at most `workers` threads, one device at a time per thread
+pub fn for_each_device(devices: &mut [NetworkDevice], workers: usize, f: impl Fn(&mut NetworkDevice) + Sync) {
+    let workers = workers.min(devices.len());
+    let next_device = Mutex::new(devices.iter_mut());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let Some(device) = next_device.lock().unwrap().next() else {
+                    break;
+                };
+                f(device);
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Hostname, InterfaceName, MacAddress};
+    use std::net::IpAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_every_device_visited_within_bound() {
+        let mut devices: Vec<_> = (1..=20u8)
+            .map(|i| NetworkDevice::new(
+                IpAddr::from([192, 168, 1, i]),
+                MacAddress::new(format!("AA:BB:CC:DD:EE:{:02X}", i)).unwrap(),
+                InterfaceName::new("eth0".to_string()),
+            ))
+            .collect();
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+
+        for_each_device(&mut devices, 4, |device| {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most_running.fetch_max(now_running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(5));
+            device.hostname = Hostname::resolved("visited".to_string());
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        assert!(devices.iter().all(|d| d.hostname == Hostname::resolved("visited".to_string())));
+        assert!(most_running.load(Ordering::SeqCst) <= 4);
+    }
+}
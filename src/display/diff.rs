@@ -0,0 +1,140 @@
+//! Text and JSON rendering of snapshot differences.
+
+use super::template::DisplayOptions;
+use crate::domain::{DeviceSummary, IconSet, SnapshotDiff};
+use anyhow::Result;
+
+/// Formats a SnapshotDiff for terminals or tools
+pub struct DiffFormatter {
+    icons: IconSet,
+}
+
+impl DiffFormatter {
+    /// Creates a new DiffFormatter instance
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a DiffFormatter using the icons from the display options
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            icons: options.icons.clone(),
+        }
+    }
+
+    /// Formats the diff as human-readable text
+    pub fn format_text(&self, diff: &SnapshotDiff) -> String {
+        if diff.is_empty() {
+            return "No changes".to_string();
+        }
+
+        let mut lines = Vec::new();
+
+        if !diff.added.is_empty() {
+            lines.push(format!("Added ({}):", diff.added.len()));
+            lines.extend(diff.added.iter().map(|d| self.format_summary('+', d)));
+        }
+
+        if !diff.removed.is_empty() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("Removed ({}):", diff.removed.len()));
+            lines.extend(diff.removed.iter().map(|d| self.format_summary('-', d)));
+        }
+
+        if !diff.changed.is_empty() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("Changed ({}):", diff.changed.len()));
+            for device in &diff.changed {
+                lines.push(format!("  ~ {} {} [{}]", self.icons.device(device.device_type), device.name, device.mac));
+                for change in &device.changes {
+                    lines.push(format!("      {}: {} -> {}", change.field, change.old, change.new));
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    /// Formats the diff as pretty-printed JSON
+    pub fn format_json(&self, diff: &SnapshotDiff) -> Result<String> {
+        Ok(serde_json::to_string_pretty(diff)?)
+    }
+
+    fn format_summary(&self, marker: char, device: &DeviceSummary) -> String {
+        format!("  {} {} {} ({}) [{}]", marker, self.icons.device(device.device_type), device.name, device.ip, device.mac)
+    }
+}
+
+impl Default for DiffFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DeviceChange, DeviceType, FieldChange, IconTheme, MacAddress};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn sample_diff() -> SnapshotDiff {
+        let mac = MacAddress::new("00:00:00:00:00:04".to_string()).unwrap();
+        SnapshotDiff {
+            added: vec![DeviceSummary {
+                mac: mac.clone(),
+                ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 40)),
+                device_type: DeviceType::Router,
+                name: "access-point".to_string(),
+            }],
+            removed: vec![],
+            changed: vec![DeviceChange {
+                mac,
+                device_type: DeviceType::NAS,
+                name: "nas".to_string(),
+                changes: vec![FieldChange {
+                    field: "ip".to_string(),
+                    old: "192.168.1.30".to_string(),
+                    new: "192.168.1.31".to_string(),
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_format_text() {
+        let text = DiffFormatter::new().format_text(&sample_diff());
+
+        assert_eq!(text, "Added (1):\n  + 🌐 access-point (192.168.1.40) [00:00:00:00:00:04]\n\n\
+            Changed (1):\n  ~ 🗄 nas [00:00:00:00:00:04]\n      ip: 192.168.1.30 -> 192.168.1.31");
+    }
+
+    #[test]
+    fn test_format_text_with_icon_theme() {
+        let options = DisplayOptions { icons: IconSet::new(IconTheme::Ascii), ..DisplayOptions::default() };
+        let text = DiffFormatter::with_options(&options).format_text(&sample_diff());
+
+        assert!(text.contains("  + [RTR] access-point (192.168.1.40)"));
+        assert!(text.contains("  ~ [NAS] nas [00:00:00:00:00:04]"));
+    }
+
+    #[test]
+    fn test_format_json() {
+        let json = DiffFormatter::new().format_json(&sample_diff()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["added"][0]["mac"], "00:00:00:00:00:04");
+        assert_eq!(value["added"][0]["name"], "access-point");
+        assert_eq!(value["changed"][0]["changes"][0]["field"], "ip");
+        assert!(value["removed"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_format_empty() {
+        let diff = SnapshotDiff { added: vec![], removed: vec![], changed: vec![] };
+        assert_eq!(DiffFormatter::new().format_text(&diff), "No changes");
+    }
+}
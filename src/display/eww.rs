@@ -0,0 +1,127 @@
+//! eww `deflisten` JSON stream output.
+
+use super::formatter::OutputFormatter;
+use super::template::DisplayOptions;
+use super::tree::{AnsiStyle, TreeLayout};
+use super::{error_tooltip, WaybarFormatter};
+use crate::domain::{IconSet, NetworkData};
+use anyhow::Result;
+use serde::Serialize;
+
+/// One eww update, consumed in widgets as e.g. `lan.text` or `lan.devices`
+#[derive(Debug, Clone, Serialize)]
+pub struct EwwOutput {
+    pub text: String,
+    pub tooltip: String,
+    pub class: Vec<String>,
+    pub count: usize,
+    pub devices: Vec<EwwDevice>,
+}
+
+/// Per-device data for building eww widgets
+#[derive(Debug, Clone, Serialize)]
+pub struct EwwDevice {
+    pub name: String,
+    pub ip: String,
+    pub mac: String,
+    pub interface: String,
+    pub device_type: String,
+    pub status: String,
+}
+
+/// Formats network data as single-line JSON objects for eww `deflisten`
+pub struct EwwFormatter {
+    waybar: WaybarFormatter,
+    tree: TreeLayout<AnsiStyle>,
+    icons: IconSet,
+}
+
+impl EwwFormatter {
+    /// Creates a new EwwFormatter instance
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates an EwwFormatter using custom templates
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            waybar: WaybarFormatter::with_options(options),
+            // eww tooltips are plain text
+            tree: TreeLayout::with_options(AnsiStyle { enabled: false }, options),
+            icons: options.icons.clone(),
+        }
+    }
+}
+
+impl OutputFormatter for EwwFormatter {
+    fn render(&self, network_data: &NetworkData) -> Result<String> {
+        let output = self.waybar.format(network_data)?;
+
+        let mut sorted: Vec<_> = network_data.devices.iter().collect();
+        sorted.sort_by_key(|d| (d.interface_name.to_string(), d.ip));
+
+        let devices: Vec<EwwDevice> = sorted
+            .into_iter()
+            .map(|device| EwwDevice {
+                name: device.identity.format_with(&self.icons),
+                ip: device.ip.to_string(),
+                mac: device.mac.to_string(),
+                interface: device.interface_name.to_string(),
+                device_type: device.identity.device_type.to_string(),
+                status: format!("{:?}", device.activity_status()).to_lowercase(),
+            })
+            .collect();
+
+        let eww_output = EwwOutput {
+            text: output.text,
+            tooltip: self.tree.build(network_data).join("\n"),
+            class: output.class.unwrap_or_default(),
+            count: network_data.devices.len(),
+            devices,
+        };
+        Ok(serde_json::to_string(&eww_output)?)
+    }
+
+    fn render_error(&self, error: anyhow::Error) -> Result<String> {
+        let tooltip = error_tooltip(&error).to_plain();
+        let output = self.waybar.error_output(error);
+        let eww_output = EwwOutput {
+            text: output.text,
+            tooltip,
+            class: output.class.unwrap_or_default(),
+            count: 0,
+            devices: Vec::new(),
+        };
+        Ok(serde_json::to_string(&eww_output)?)
+    }
+}
+
+impl Default for EwwFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{InterfaceName, MacAddress, NetworkDevice};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_render_single_line_json() {
+        let device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+            MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        let data = NetworkData::new(vec![], vec![device], None, vec![]);
+        let line = EwwFormatter::new().render(&data).unwrap();
+
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["count"], 1);
+        assert_eq!(json["devices"][0]["ip"], "192.168.1.20");
+        assert_eq!(json["devices"][0]["status"], "active");
+    }
+}
@@ -0,0 +1,52 @@
+//! Output backend abstraction shared by all status bar formatters.
+
+use super::WaybarOutput;
+use crate::domain::NetworkData;
+use anyhow::Result;
+
+/// A status bar output backend
+///
+/// Each call to `render` produces one complete update. In watch mode the
+/// updates are written one after another, after the optional `header`.
+pub trait OutputFormatter {
+    /// Text written once before the first update (e.g. a protocol header)
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// Renders one update from network data
+    fn render(&self, network_data: &NetworkData) -> Result<String>;
+
+    /// Renders one update describing a collection error
+    fn render_error(&self, error: anyhow::Error) -> Result<String>;
+}
+
+/// Foreground color for bars without CSS, derived from the Waybar classes
+pub fn state_color(output: &WaybarOutput) -> Option<&'static str> {
+    let has_class = |name: &str| {
+        output.class.as_ref().is_some_and(|classes| classes.iter().any(|c| c == name))
+    };
+
+    if has_class("error") {
+        Some("#FF0000")
+    } else if has_class("active") {
+        None
+    } else {
+        Some("#888888")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::WaybarFormatter;
+
+    #[test]
+    fn test_state_color() {
+        let empty = WaybarFormatter::new().format(&NetworkData::new(vec![], vec![], None, vec![])).unwrap();
+        assert_eq!(state_color(&empty), Some("#888888"));
+
+        let error = WaybarFormatter::new().error_output(anyhow::anyhow!("down"));
+        assert_eq!(state_color(&error), Some("#FF0000"));
+    }
+}
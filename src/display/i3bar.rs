@@ -0,0 +1,104 @@
+//! i3bar/swaybar JSON protocol output.
+
+use super::formatter::{state_color, OutputFormatter};
+use super::template::DisplayOptions;
+use super::{WaybarFormatter, WaybarOutput};
+use crate::domain::NetworkData;
+use anyhow::Result;
+use serde::Serialize;
+
+/// A single i3bar status block
+#[derive(Debug, Clone, Serialize)]
+pub struct I3barBlock {
+    pub name: String,
+    pub full_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// Formats network data for the i3bar protocol
+///
+/// The header opens the infinite array; every update is one status line
+/// followed by a comma, as the protocol expects.
+pub struct I3barFormatter {
+    options: DisplayOptions,
+    waybar: WaybarFormatter,
+}
+
+impl I3barFormatter {
+    /// Creates a new I3barFormatter instance
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a I3barFormatter using custom templates
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            options: options.clone(),
+            waybar: WaybarFormatter::with_options(options),
+        }
+    }
+
+    fn block(&self, output: &WaybarOutput, short_text: Option<String>) -> I3barBlock {
+        I3barBlock {
+            name: "waybar_lan".to_string(),
+            full_text: output.text.clone(),
+            short_text,
+            color: state_color(output).map(str::to_string),
+        }
+    }
+
+    fn status_line(&self, block: I3barBlock) -> Result<String> {
+        Ok(format!("{},", serde_json::to_string(&[block])?))
+    }
+}
+
+impl OutputFormatter for I3barFormatter {
+    fn header(&self) -> Option<String> {
+        Some("{\"version\":1}\n[".to_string())
+    }
+
+    fn render(&self, network_data: &NetworkData) -> Result<String> {
+        let output = self.waybar.format(network_data)?;
+        self.status_line(self.block(&output, Some(self.options.short_text(network_data))))
+    }
+
+    fn render_error(&self, error: anyhow::Error) -> Result<String> {
+        let output = self.waybar.error_output(error);
+        self.status_line(self.block(&output, Some(self.options.short_error_text())))
+    }
+}
+
+impl Default for I3barFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_opens_infinite_array() {
+        let header = I3barFormatter::new().header().unwrap();
+        let (version, array) = header.split_once('\n').unwrap();
+
+        let version: serde_json::Value = serde_json::from_str(version).unwrap();
+        assert_eq!(version["version"], 1);
+        assert_eq!(array, "[");
+    }
+
+    #[test]
+    fn test_render_status_line() {
+        let data = NetworkData::new(vec![], vec![], None, vec![]);
+        let line = I3barFormatter::new().render(&data).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(line.trim_end_matches(',')).unwrap();
+        assert_eq!(json[0]["name"], "waybar_lan");
+        assert_eq!(json[0]["full_text"], "🖧 No devices");
+        assert_eq!(json[0]["short_text"], "🖧 0");
+    }
+}
@@ -0,0 +1,107 @@
+//! i3blocks line-based output.
+
+use super::formatter::{state_color, OutputFormatter};
+use super::template::DisplayOptions;
+use super::{I3barBlock, WaybarFormatter, WaybarOutput};
+use crate::domain::NetworkData;
+use anyhow::Result;
+
+/// Formats network data as i3blocks full_text, short_text and color lines
+///
+/// Persistent blocks read every line as a separate update, so in that mode
+/// each update is a single JSON line instead (`format=json`).
+pub struct I3blocksFormatter {
+    options: DisplayOptions,
+    waybar: WaybarFormatter,
+    persistent: bool,
+}
+
+impl I3blocksFormatter {
+    /// Creates a new I3blocksFormatter instance
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a I3blocksFormatter using custom templates
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            options: options.clone(),
+            waybar: WaybarFormatter::with_options(options),
+            persistent: false,
+        }
+    }
+
+    /// Writes one JSON line per update for `interval=persist` blocks
+    pub fn with_persistent_output(mut self) -> Self {
+        self.persistent = true;
+        self
+    }
+
+    fn lines(&self, output: &WaybarOutput, short_text: &str) -> Result<String> {
+        if self.persistent {
+            return Ok(serde_json::to_string(&I3barBlock {
+                name: "waybar_lan".to_string(),
+                full_text: output.text.clone(),
+                short_text: Some(short_text.to_string()),
+                color: state_color(output).map(str::to_string),
+            })?);
+        }
+
+        let mut lines = vec![output.text.clone(), short_text.to_string()];
+        if let Some(color) = state_color(output) {
+            lines.push(color.to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+impl OutputFormatter for I3blocksFormatter {
+    fn render(&self, network_data: &NetworkData) -> Result<String> {
+        let output = self.waybar.format(network_data)?;
+        self.lines(&output, &self.options.short_text(network_data))
+    }
+
+    fn render_error(&self, error: anyhow::Error) -> Result<String> {
+        let output = self.waybar.error_output(error);
+        self.lines(&output, &self.options.short_error_text())
+    }
+}
+
+impl Default for I3blocksFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_lines() {
+        let data = NetworkData::new(vec![], vec![], None, vec![]);
+        let output = I3blocksFormatter::new().render(&data).unwrap();
+
+        assert_eq!(output, "🖧 No devices\n🖧 0\n#888888");
+    }
+
+    #[test]
+    fn test_render_error_lines() {
+        let output = I3blocksFormatter::new().render_error(anyhow::anyhow!("down")).unwrap();
+
+        assert_eq!(output.lines().count(), 3);
+        assert!(output.ends_with("#FF0000"));
+    }
+
+    #[test]
+    fn test_persistent_output_is_one_line() {
+        let data = NetworkData::new(vec![], vec![], None, vec![]);
+        let output = I3blocksFormatter::new().with_persistent_output().render(&data).unwrap();
+
+        assert_eq!(output.lines().count(), 1);
+        let block: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(block["full_text"], "🖧 No devices");
+        assert_eq!(block["short_text"], "🖧 0");
+        assert_eq!(block["color"], "#888888");
+    }
+}
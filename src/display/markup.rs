@@ -0,0 +1,206 @@
+//! Structured tooltip text rendered as escaped Pango markup, ANSI or plain text.
+//!
+//! Device names come from the network (UPnP, mDNS, DNS), so dynamic text is
+//! never interpolated into markup directly. Lines are built from pieces and
+//! each backend escapes them when rendering.
+
+use crate::domain::ActivityStatus;
+
+/// Text colors used in tooltips
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Yellow,
+    Grey,
+    Red,
+}
+
+impl Color {
+    /// Color for a device's activity status; Idle uses the default foreground
+    pub fn for_activity(status: ActivityStatus) -> Option<Self> {
+        match status {
+            ActivityStatus::Active => Some(Self::Green),
+            ActivityStatus::Recent => Some(Self::Yellow),
+            ActivityStatus::Idle => None,
+            ActivityStatus::Stale => Some(Self::Grey),
+        }
+    }
+
+    fn hex(&self) -> &'static str {
+        match self {
+            Self::Green => "#00FF00",
+            Self::Yellow => "#FFFF00",
+            Self::Grey => "#888888",
+            Self::Red => "#FF0000",
+        }
+    }
+
+    fn ansi(&self) -> &'static str {
+        match self {
+            Self::Green => "\x1b[92m",
+            Self::Yellow => "\x1b[93m",
+            Self::Grey => "\x1b[90m",
+            Self::Red => "\x1b[91m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Colored(Color, String),
+}
+
+/// A line of text built from plain and colored pieces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Markup {
+    pieces: Vec<Piece>,
+}
+
+impl Markup {
+    /// Creates an empty Markup
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends unstyled text
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.pieces.push(Piece::Text(text.into()));
+        self
+    }
+
+    /// Appends text in a color, or unstyled when there is none
+    pub fn colored(mut self, color: Option<Color>, text: impl Into<String>) -> Self {
+        match color {
+            Some(color) => self.pieces.push(Piece::Colored(color, text.into())),
+            None => self.pieces.push(Piece::Text(text.into())),
+        }
+        self
+    }
+
+    /// Appends all pieces of another Markup
+    pub fn append(mut self, other: Markup) -> Self {
+        self.pieces.extend(other.pieces);
+        self
+    }
+
+    /// Removes trailing whitespace from the end of the line
+    pub fn trim_end(mut self) -> Self {
+        while let Some(Piece::Text(text)) = self.pieces.last_mut() {
+            let trimmed_len = text.trim_end().len();
+            if trimmed_len > 0 {
+                text.truncate(trimmed_len);
+                break;
+            }
+            self.pieces.pop();
+        }
+        self
+    }
+
+    /// True if there is no text at all
+    pub fn is_empty(&self) -> bool {
+        self.pieces.iter().all(|piece| match piece {
+            Piece::Text(text) | Piece::Colored(_, text) => text.is_empty(),
+        })
+    }
+
+    /// Renders Pango markup with all text escaped
+    pub fn to_pango(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => escape_pango(text),
+                Piece::Colored(color, text) => {
+                    format!("<span color='{}'>{}</span>", color.hex(), escape_pango(text))
+                }
+            })
+            .collect()
+    }
+
+    /// Renders text with ANSI colors; control characters in the text are removed
+    pub fn to_ansi(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => strip_control(text),
+                Piece::Colored(color, text) => format!("{}{}\x1b[0m", color.ansi(), strip_control(text)),
+            })
+            .collect()
+    }
+
+    /// Renders plain text; control characters in the text are removed
+    pub fn to_plain(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) | Piece::Colored(_, text) => strip_control(text),
+            })
+            .collect()
+    }
+}
+
+impl From<&str> for Markup {
+    fn from(text: &str) -> Self {
+        Self::new().text(text)
+    }
+}
+
+impl From<String> for Markup {
+    fn from(text: String) -> Self {
+        Self::new().text(text)
+    }
+}
+
+/// Escapes text for use inside Pango markup
+pub fn escape_pango(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            c if c.is_control() && c != '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replaces control characters (including escape sequences and newlines) with spaces
+fn strip_control(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pango_escapes_dynamic_text() {
+        let line = Markup::new()
+            .text("  ├─ ")
+            .colored(Some(Color::Green), "Tom & Jerry's <TV>");
+
+        assert_eq!(
+            line.to_pango(),
+            "  ├─ <span color='#00FF00'>Tom &amp; Jerry&apos;s &lt;TV&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn test_ansi_and_plain_strip_control_characters() {
+        let line = Markup::new().colored(Some(Color::Yellow), "evil\x1b[2Jname\n");
+
+        assert_eq!(line.to_ansi(), "\x1b[93mevil [2Jname \x1b[0m");
+        assert_eq!(line.to_plain(), "evil [2Jname ");
+    }
+
+    #[test]
+    fn test_trim_end() {
+        let line = Markup::new().colored(None, "TV").text(" (10.0.0.2) ").text("  ").trim_end();
+        assert_eq!(line.to_plain(), "TV (10.0.0.2)");
+        assert!(Markup::new().text("   ").trim_end().is_empty());
+    }
+}
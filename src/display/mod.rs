@@ -1,11 +1,38 @@
-//! Display module for formatting network data as Waybar JSON output.
+//! Display module for formatting network data for status bars and terminals.
+pub mod diff;
+pub mod eww;
+pub mod formatter;
+pub mod i3bar;
+pub mod i3blocks;
+pub mod markup;
+pub mod polybar;
+pub mod prometheus;
+pub mod template;
+pub mod terminal;
+pub mod text;
+pub mod tree;
 pub mod waybar;
+pub use diff::*;
+pub use eww::*;
+pub use formatter::*;
+pub use i3bar::*;
+pub use i3blocks::*;
+pub use markup::*;
+pub use polybar::*;
+pub use prometheus::*;
+pub use template::*;
+pub use terminal::*;
+pub use text::*;
 pub use waybar::*;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::NetworkData;
+    use crate::domain::{
+        Gateway, Hostname, IconSet, IconTheme, InterfaceName, MacAddress, NetworkData, NetworkDevice,
+        NetworkInterface,
+    };
+    use std::net::{IpAddr, Ipv4Addr};
 
     #[test]
     fn test_waybar_output_creation() {
@@ -19,9 +46,47 @@ mod tests {
 
     #[test]
     fn test_error_output_formatting() {
-        let error_output = WaybarFormatter::create_error_output(anyhow::anyhow!("Network error"));
+        let error_output = WaybarFormatter::new().error_output(anyhow::anyhow!("Network error"));
 
         assert!(error_output.text.contains("unavailable"));
         assert!(error_output.tooltip.contains("Network error"));
     }
+
+    #[test]
+    fn test_every_surface_uses_icon_theme() {
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let interface = NetworkInterface::new(InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), None);
+        let mut router = NetworkDevice::new(ip, MacAddress::new("00:11:22:33:44:55".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()));
+        router.hostname = Hostname::resolved("router.lan".to_string());
+        router.build_identity();
+        let data = NetworkData::new(vec![interface], vec![router], Some(Gateway::new(ip)), vec![]);
+
+        let options = DisplayOptions { icons: IconSet::new(IconTheme::Ascii), ..DisplayOptions::default() };
+        let formatters: Vec<Box<dyn OutputFormatter>> = vec![
+            Box::new(WaybarFormatter::with_options(&options)),
+            Box::new(I3barFormatter::with_options(&options)),
+            Box::new(I3blocksFormatter::with_options(&options)),
+            Box::new(I3blocksFormatter::with_options(&options).with_persistent_output()),
+            Box::new(PolybarFormatter::with_options(&options)),
+            Box::new(EwwFormatter::with_options(&options)),
+        ];
+        let mut outputs = Vec::new();
+        for formatter in &formatters {
+            outputs.push(formatter.render(&data).unwrap());
+            outputs.push(formatter.render_error(anyhow::anyhow!("down")).unwrap());
+        }
+        for output in &outputs {
+            assert!(output.contains("LAN"), "{}", output);
+        }
+        outputs.push(TerminalFormatter::with_settings(&options, false, None).format(&data));
+        outputs.push(TextFormatter::with_options(&options).format_device(&data.devices[0], &data));
+
+        for output in &outputs {
+            // Emoji live in the supplementary planes; ASCII output has none
+            assert!(output.chars().all(|c| (c as u32) < 0x1F000), "{}", output);
+        }
+        assert!(outputs.iter().any(|output| output.contains("[RTR]")));
+    }
 }
@@ -0,0 +1,70 @@
+//! Polybar format-tag output.
+
+use super::formatter::{state_color, OutputFormatter};
+use super::template::DisplayOptions;
+use super::{WaybarFormatter, WaybarOutput};
+use crate::domain::NetworkData;
+use anyhow::Result;
+
+/// Formats network data as a single polybar line with `%{F}` color tags
+pub struct PolybarFormatter {
+    waybar: WaybarFormatter,
+}
+
+impl PolybarFormatter {
+    /// Creates a new PolybarFormatter instance
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a PolybarFormatter using custom templates
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            waybar: WaybarFormatter::with_options(options),
+        }
+    }
+
+    fn line(&self, output: &WaybarOutput) -> String {
+        // Polybar treats %{...} as a format tag, so escape literal percent signs
+        let text = output.text.replace('%', "%%");
+        match state_color(output) {
+            Some(color) => format!("%{{F{}}}{}%{{F-}}", color, text),
+            None => text,
+        }
+    }
+}
+
+impl OutputFormatter for PolybarFormatter {
+    fn render(&self, network_data: &NetworkData) -> Result<String> {
+        Ok(self.line(&self.waybar.format(network_data)?))
+    }
+
+    fn render_error(&self, error: anyhow::Error) -> Result<String> {
+        Ok(self.line(&self.waybar.error_output(error)))
+    }
+}
+
+impl Default for PolybarFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_with_color_tag() {
+        let data = NetworkData::new(vec![], vec![], None, vec![]);
+        let output = PolybarFormatter::new().render(&data).unwrap();
+
+        assert_eq!(output, "%{F#888888}🖧 No devices%{F-}");
+    }
+
+    #[test]
+    fn test_render_error() {
+        let output = PolybarFormatter::new().render_error(anyhow::anyhow!("down")).unwrap();
+        assert!(output.starts_with("%{F#FF0000}"));
+    }
+}
@@ -0,0 +1,432 @@
+//! Prometheus metrics exposition, served over HTTP or written as a textfile.
+
+use crate::data::state_file;
+use crate::domain::{DiscoverySource, NetworkData};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Absent devices are dropped from the `up` gauge after this long
+pub const DEVICE_EXPIRY: Duration = Duration::from_secs(24 * 3600);
+
+/// Labels identifying a device in the `up` gauge
+#[derive(Debug, Clone)]
+struct DeviceLabels {
+    name: String,
+    device_type: String,
+    last_present: Instant,
+}
+
+/// Accumulates metrics across scans and renders the text exposition format
+///
+/// Devices stay in the `up` gauge with value 0 after they disappear, so
+/// presence over time can be graphed, until they expire.
+pub struct MetricsRegistry {
+    devices_per_interface: BTreeMap<String, usize>,
+    known_devices: BTreeMap<String, DeviceLabels>,
+    present: HashSet<String>,
+    /// Ping round-trip times of present devices, by MAC address
+    device_rtts: BTreeMap<String, f64>,
+    gateway_rtt: Option<f64>,
+    scan_durations: BTreeMap<DiscoverySource, f64>,
+    source_errors: HashMap<DiscoverySource, u64>,
+    failed_scans: u64,
+}
+
+impl MetricsRegistry {
+    /// Creates an empty MetricsRegistry
+    pub fn new() -> Self {
+        Self {
+            devices_per_interface: BTreeMap::new(),
+            known_devices: BTreeMap::new(),
+            present: HashSet::new(),
+            device_rtts: BTreeMap::new(),
+            gateway_rtt: None,
+            scan_durations: BTreeMap::new(),
+            source_errors: HashMap::new(),
+            failed_scans: 0,
+        }
+    }
+
+    /// Records the results of one scan
+    pub fn update(&mut self, network_data: &NetworkData) {
+        self.update_at(network_data, Instant::now());
+    }
+
+    /// Counts a scan that failed before producing a snapshot
+    pub fn record_failed_scan(&mut self) {
+        self.failed_scans += 1;
+    }
+
+    fn update_at(&mut self, network_data: &NetworkData, now: Instant) {
+        self.devices_per_interface = network_data.interfaces
+            .iter()
+            .map(|iface| (iface.name.to_string(), 0))
+            .collect();
+        for (interface, devices) in network_data.devices_by_interface() {
+            self.devices_per_interface.insert(interface.to_string(), devices.len());
+        }
+
+        self.present = network_data.devices.iter().map(|d| d.mac.to_string()).collect();
+        for device in &network_data.devices {
+            let name = device.identity.friendly_name
+                .as_ref()
+                .map(|n| n.as_str().to_string())
+                .unwrap_or_else(|| device.hostname.to_string());
+            self.known_devices.insert(device.mac.to_string(), DeviceLabels {
+                name,
+                device_type: device.identity.device_type.to_string(),
+                last_present: now,
+            });
+        }
+        self.known_devices.retain(|_, labels| now.duration_since(labels.last_present) < DEVICE_EXPIRY);
+
+        self.device_rtts = network_data.devices
+            .iter()
+            .filter_map(|device| Some((device.mac.to_string(), device.latency?.as_secs_f64())))
+            .collect();
+        self.gateway_rtt = network_data.gateway_health
+            .as_ref()
+            .and_then(|health| health.latency)
+            .map(|latency| latency.as_secs_f64());
+
+        if let Some(scan) = &network_data.scan {
+            self.scan_durations = scan.sources
+                .iter()
+                .map(|report| (report.source, report.duration.as_secs_f64()))
+                .collect();
+            for report in &scan.sources {
+                self.source_errors.entry(report.source).or_insert(0);
+            }
+            for report in scan.failed_sources() {
+                *self.source_errors.entry(report.source).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        write_header(&mut out, "waybar_lan_devices", "gauge", "Number of devices seen per interface");
+        for (interface, count) in &self.devices_per_interface {
+            let _ = writeln!(out, "waybar_lan_devices{{interface=\"{}\"}} {}", escape_label(interface), count);
+        }
+
+        write_header(&mut out, "waybar_lan_device_up", "gauge", "Whether the device was present in the last scan");
+        for (mac, labels) in &self.known_devices {
+            let _ = writeln!(
+                out,
+                "waybar_lan_device_up{{mac=\"{}\",name=\"{}\",type=\"{}\"}} {}",
+                escape_label(mac),
+                escape_label(&labels.name),
+                escape_label(&labels.device_type),
+                u8::from(self.present.contains(mac))
+            );
+        }
+
+        write_header(&mut out, "waybar_lan_device_rtt_seconds", "gauge",
+            "Ping round-trip time of the device in the last scan");
+        for (mac, seconds) in &self.device_rtts {
+            let Some(labels) = self.known_devices.get(mac) else {
+                continue;
+            };
+            let _ = writeln!(
+                out,
+                "waybar_lan_device_rtt_seconds{{mac=\"{}\",name=\"{}\",type=\"{}\"}} {:.6}",
+                escape_label(mac),
+                escape_label(&labels.name),
+                escape_label(&labels.device_type),
+                seconds
+            );
+        }
+
+        write_header(&mut out, "waybar_lan_gateway_rtt_seconds", "gauge",
+            "Average round-trip time to the default gateway in the last scan");
+        if let Some(seconds) = self.gateway_rtt {
+            let _ = writeln!(out, "waybar_lan_gateway_rtt_seconds {:.6}", seconds);
+        }
+
+        write_header(&mut out, "waybar_lan_scan_duration_seconds", "gauge",
+            "Duration of the last scan per discovery source");
+        for (source, seconds) in &self.scan_durations {
+            let _ = writeln!(out, "waybar_lan_scan_duration_seconds{{source=\"{}\"}} {:.6}", source, seconds);
+        }
+
+        write_header(&mut out, "waybar_lan_discovery_errors_total", "counter",
+            "Number of failed scans per discovery source");
+        let mut errors: Vec<_> = self.source_errors.iter().collect();
+        errors.sort();
+        for (source, count) in errors {
+            let _ = writeln!(out, "waybar_lan_discovery_errors_total{{source=\"{}\"}} {}", source, count);
+        }
+
+        write_header(&mut out, "waybar_lan_scan_failures_total", "counter",
+            "Number of scans that failed without producing a snapshot");
+        let _ = writeln!(out, "waybar_lan_scan_failures_total {}", self.failed_scans);
+
+        out
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escapes a label value per the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes metrics for the node_exporter textfile collector
+/// Writes to a temporary file first so the collector never reads a partial file;
+/// several bar instances may write the same textfile, so each write gets its own
+pub fn write_textfile(path: &Path, metrics: &str) -> Result<()> {
+    state_file::replace(path, metrics)
+}
+
+/// Serves the latest rendered metrics on `/metrics`
+pub struct MetricsServer {
+    metrics: Arc<Mutex<String>>,
+    local_addr: SocketAddr,
+}
+
+impl MetricsServer {
+    /// Binds the listener and serves requests on a background thread
+    pub fn start(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to bind metrics listener on {}", addr))?;
+        let local_addr = listener.local_addr()?;
+        let metrics = Arc::new(Mutex::new(String::new()));
+
+        let shared = Arc::clone(&metrics);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A misbehaving client must not stop the server
+                let _ = handle_request(stream, &shared);
+            }
+        });
+
+        Ok(Self { metrics, local_addr })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Replaces the metrics served to subsequent requests
+    pub fn publish(&self, metrics: String) {
+        if let Ok(mut current) = self.metrics.lock() {
+            *current = metrics;
+        }
+    }
+}
+
+fn handle_request(stream: TcpStream, metrics: &Mutex<String>) -> Result<()> {
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Drain headers so the client is not reset while still sending
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = metrics.lock().map(|m| m.clone()).unwrap_or_default();
+            ("200 OK", body)
+        }
+        _ => ("404 Not Found", "Not Found\n".to_string()),
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        GatewayHealth, InterfaceName, MacAddress, NetworkDevice, NetworkInterface, ScanMetadata, SourceReport,
+    };
+    use std::io::Read;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::SystemTime;
+
+    fn sample_data(with_device: bool) -> NetworkData {
+        let interface = NetworkInterface::new(
+            InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            None,
+        );
+        let devices = if with_device {
+            let mut device = NetworkDevice::new(
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+                MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap(),
+                InterfaceName::new("eth0".to_string()),
+            );
+            device.hostname = crate::domain::Hostname::resolved("tv \"living\"".to_string());
+            device.latency = Some(Duration::from_micros(2500));
+            device.build_identity();
+            vec![device]
+        } else {
+            vec![]
+        };
+        let scan = ScanMetadata {
+            started_at: SystemTime::now(),
+            duration: Duration::from_millis(1500),
+            sources: vec![
+                SourceReport { source: DiscoverySource::Arp, duration: Duration::from_millis(2), error: None },
+                SourceReport {
+                    source: DiscoverySource::Mdns,
+                    duration: Duration::from_millis(1500),
+                    error: Some("no multicast".to_string()),
+                },
+            ],
+        };
+        NetworkData::new(vec![interface], devices, None, vec![]).with_scan(scan)
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let mut registry = MetricsRegistry::new();
+        registry.update(&sample_data(true));
+        let text = registry.render();
+
+        assert!(text.contains("# TYPE waybar_lan_devices gauge"));
+        assert!(text.contains("waybar_lan_devices{interface=\"eth0\"} 1"));
+        assert!(text.contains(
+            "waybar_lan_device_up{mac=\"AA:BB:CC:DD:EE:FF\",name=\"tv \\\"living\\\"\",type=\"Device\"} 1"));
+        assert!(text.contains("waybar_lan_scan_duration_seconds{source=\"mdns\"} 1.500000"));
+        assert!(text.contains("waybar_lan_discovery_errors_total{source=\"arp\"} 0"));
+        assert!(text.contains("waybar_lan_discovery_errors_total{source=\"mdns\"} 1"));
+    }
+
+    #[test]
+    fn test_device_rtt_only_for_present_devices() {
+        let mut registry = MetricsRegistry::new();
+        registry.update(&sample_data(true));
+        assert!(registry.render().contains(
+            "waybar_lan_device_rtt_seconds{mac=\"AA:BB:CC:DD:EE:FF\",name=\"tv \\\"living\\\"\",type=\"Device\"} 0.002500"));
+
+        registry.update(&sample_data(false));
+        assert!(!registry.render().contains("waybar_lan_device_rtt_seconds{"));
+    }
+
+    #[test]
+    fn test_gateway_rtt() {
+        let mut registry = MetricsRegistry::new();
+        registry.update(&sample_data(false));
+        assert!(!registry.render().contains("\nwaybar_lan_gateway_rtt_seconds "));
+
+        let health = GatewayHealth {
+            probes_sent: 3,
+            replies: 3,
+            latency: Some(Duration::from_millis(4)),
+            upstream: None,
+        };
+        registry.update(&sample_data(false).with_gateway_health(Some(health)));
+        assert!(registry.render().contains("waybar_lan_gateway_rtt_seconds 0.004000"));
+    }
+
+    #[test]
+    fn test_device_goes_down_and_errors_accumulate() {
+        let mut registry = MetricsRegistry::new();
+        registry.update(&sample_data(true));
+        registry.update(&sample_data(false));
+        let text = registry.render();
+
+        assert!(text.contains("waybar_lan_devices{interface=\"eth0\"} 0"));
+        assert!(text.contains("mac=\"AA:BB:CC:DD:EE:FF\""));
+        assert!(text.contains("type=\"Device\"} 0"));
+        assert!(text.contains("waybar_lan_discovery_errors_total{source=\"mdns\"} 2"));
+    }
+
+    #[test]
+    fn test_absent_devices_expire() {
+        let mut registry = MetricsRegistry::new();
+        let start = Instant::now();
+        registry.update_at(&sample_data(true), start);
+        registry.update_at(&sample_data(false), start + Duration::from_secs(3600));
+        assert!(registry.render().contains("mac=\"AA:BB:CC:DD:EE:FF\""));
+
+        registry.update_at(&sample_data(false), start + DEVICE_EXPIRY);
+        assert!(!registry.render().contains("mac=\"AA:BB:CC:DD:EE:FF\""));
+    }
+
+    #[test]
+    fn test_failed_scans_counted() {
+        let mut registry = MetricsRegistry::new();
+        assert!(registry.render().contains("waybar_lan_scan_failures_total 0"));
+
+        registry.record_failed_scan();
+        registry.record_failed_scan();
+        assert!(registry.render().contains("waybar_lan_scan_failures_total 2"));
+    }
+
+    #[test]
+    fn test_write_textfile() {
+        let dir = std::env::temp_dir().join(format!("waybar_lan_textfile_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("waybar_lan.prom");
+
+        write_textfile(&path, "waybar_lan_devices{interface=\"eth0\"} 3\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "waybar_lan_devices{interface=\"eth0\"} 3\n");
+
+        // A failed rename leaves no temporary file behind
+        let blocked = dir.join("blocked.prom");
+        std::fs::create_dir_all(blocked.join("occupied")).unwrap();
+        assert!(write_textfile(&blocked, "waybar_lan_devices{interface=\"eth0\"} 3\n").is_err());
+
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_metrics_server() {
+        let server = MetricsServer::start("127.0.0.1:0".parse().unwrap()).unwrap();
+        server.publish("waybar_lan_devices{interface=\"eth0\"} 3\n".to_string());
+
+        let fetch = |path: &str| {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = fetch("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("waybar_lan_devices{interface=\"eth0\"} 3\n"));
+
+        assert!(fetch("/").starts_with("HTTP/1.1 404"));
+    }
+}
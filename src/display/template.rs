@@ -0,0 +1,350 @@
+//! User templates for the bar text and tooltip device lines.
+
+use super::markup::Markup;
+use super::tree::{GroupBy, SortKey};
+use crate::domain::{ActivityStatus, DeviceType, IconSet, NetworkData, NetworkDevice};
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+/// Placeholders available in the bar text template
+pub const TEXT_PLACEHOLDERS: &[&str] = &[
+    "icon", "count", "devices", "active", "new", "gateway_latency", "gateway_loss", "upstream", "connectivity",
+    "rx", "tx", "by_type",
+];
+
+/// Placeholders available in the tooltip device line template
+pub const DEVICE_PLACEHOLDERS: &[&str] = &[
+    "icon", "name", "ip", "mac", "latency", "type", "hostname", "interface",
+];
+
+/// Default bar text, e.g. "🖧 3 devices"
+pub const DEFAULT_TEXT_TEMPLATE: &str = "{icon} {devices}";
+
+/// Default tooltip device line, e.g. "💻 Dell XPS (192.168.1.20)"
+pub const DEFAULT_DEVICE_TEMPLATE: &str = "{icon} {name} ({ip})";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A parsed template with `{placeholder}` fields; `{{` and `}}` are literal braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses a template, rejecting placeholders not in `allowed`
+    pub fn parse(source: &str, allowed: &[&str]) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("Unclosed '{{' in template '{}'", source),
+                        }
+                    }
+                    if !allowed.contains(&name.as_str()) {
+                        bail!("Unknown placeholder {{{}}} in template '{}' (expected one of: {})",
+                            name, source, allowed.join(", "));
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Placeholder(name));
+                }
+                '}' => bail!("Unmatched '}}' in template '{}' (use '}}}}' for a literal brace)", source),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Renders the template, looking up each placeholder with `value`
+    pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.clone(),
+                Segment::Placeholder(name) => value(name),
+            })
+            .collect()
+    }
+
+    /// Renders the template as markup; literals are escaped like any other text
+    pub fn render_markup(&self, value: impl Fn(&str) -> Markup) -> Markup {
+        self.segments.iter().fold(Markup::new(), |markup, segment| match segment {
+            Segment::Literal(text) => markup.text(text.clone()),
+            Segment::Placeholder(name) => markup.append(value(name)),
+        })
+    }
+}
+
+/// Templates and settings used by the bar formatters and the tooltip tree
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    pub text_template: Template,
+    pub device_template: Template,
+    pub icons: IconSet,
+    /// Tooltip grouping and order of devices within a group
+    pub group_by: GroupBy,
+    pub sort_by: SortKey,
+    /// Tooltip length limit; excess devices are summarized as "+N more"
+    pub max_lines: Option<usize>,
+    /// Leave Stale devices out of the tooltip
+    pub hide_stale: bool,
+    /// Leave devices without a resolved hostname or friendly name out of the tooltip
+    pub hide_unnamed: bool,
+    /// Devices (IP, MAC or name) whose absence is reported as `critical-missing`
+    pub critical_devices: Vec<String>,
+}
+
+impl DisplayOptions {
+    /// Creates DisplayOptions with the default templates
+    pub fn new() -> Self {
+        Self {
+            text_template: parse_text_template(DEFAULT_TEXT_TEMPLATE)
+                .expect("default text template is valid"),
+            device_template: parse_device_template(DEFAULT_DEVICE_TEMPLATE)
+                .expect("default device template is valid"),
+            icons: IconSet::default(),
+            group_by: GroupBy::default(),
+            sort_by: SortKey::default(),
+            max_lines: None,
+            hide_stale: false,
+            hide_unnamed: false,
+            critical_devices: Vec::new(),
+        }
+    }
+
+    /// Renders the bar text for a snapshot
+    pub fn bar_text(&self, network_data: &NetworkData) -> String {
+        let devices = &network_data.devices;
+        self.text_template.render(|name| match name {
+            "icon" => self.icons.bar().to_string(),
+            "count" => devices.len().to_string(),
+            "devices" => match devices.len() {
+                0 => "No devices".to_string(),
+                1 => "1 device".to_string(),
+                count => format!("{} devices", count),
+            },
+            "active" => devices
+                .iter()
+                .filter(|d| d.activity_status() == ActivityStatus::Active)
+                .count()
+                .to_string(),
+            "new" => devices.iter().filter(|d| d.is_new).count().to_string(),
+            "gateway_latency" => network_data
+                .gateway_health
+                .as_ref()
+                .and_then(|health| health.latency)
+                .or_else(|| network_data.gateway_device().and_then(|gateway| gateway.latency))
+                .map(format_latency)
+                .unwrap_or_else(|| "--".to_string()),
+            "gateway_loss" => network_data
+                .gateway_health
+                .as_ref()
+                .filter(|health| health.was_probed())
+                .map(|health| format!("{:.0}%", health.loss_percent()))
+                .unwrap_or_else(|| "--".to_string()),
+            "upstream" => match network_data.gateway_health.as_ref().and_then(|h| h.upstream.as_ref()) {
+                Some(upstream) if upstream.reachable => "up".to_string(),
+                Some(_) => "down".to_string(),
+                None => "--".to_string(),
+            },
+            "connectivity" => network_data
+                .connectivity
+                .as_ref()
+                .map_or_else(|| "--".to_string(), |check| check.status.to_string()),
+            "rx" => network_data
+                .total_throughput()
+                .map_or_else(|| "--".to_string(), |rates| format_rate(rates.rx_bytes_per_sec)),
+            "tx" => network_data
+                .total_throughput()
+                .map_or_else(|| "--".to_string(), |rates| format_rate(rates.tx_bytes_per_sec)),
+            "by_type" => self.by_type(devices),
+            _ => String::new(),
+        })
+    }
+
+    /// Compact bar text for narrow bars
+    pub fn short_text(&self, network_data: &NetworkData) -> String {
+        format!("{} {}", self.icons.bar(), network_data.devices.len())
+    }
+
+    /// Bar text shown when collection failed
+    pub fn error_text(&self) -> String {
+        format!("{} -- Network unavailable", self.icons.bar())
+    }
+
+    /// Compact bar text shown when collection failed
+    pub fn short_error_text(&self) -> String {
+        format!("{} --", self.icons.bar())
+    }
+
+    /// Device counts per type, e.g. "💻 2 📞 1"
+    fn by_type(&self, devices: &[NetworkDevice]) -> String {
+        DeviceType::ALL
+            .iter()
+            .filter_map(|device_type| {
+                let count = devices.iter().filter(|d| d.identity.device_type == *device_type).count();
+                (count > 0).then(|| format!("{} {}", self.icons.device(*device_type), count))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a bar text template
+pub fn parse_text_template(source: &str) -> Result<Template> {
+    Template::parse(source, TEXT_PLACEHOLDERS)
+}
+
+/// Parses a tooltip device line template
+pub fn parse_device_template(source: &str) -> Result<Template> {
+    Template::parse(source, DEVICE_PLACEHOLDERS)
+}
+
+/// Value of a device line placeholder, unstyled
+pub fn device_value(device: &NetworkDevice, name: &str, icons: &IconSet) -> String {
+    match name {
+        "icon" => icons.device(device.identity.device_type).to_string(),
+        "name" => device.identity.name(),
+        "ip" => device.ip.to_string(),
+        "mac" => device.mac.to_string(),
+        "latency" => device.latency.map(format_latency).unwrap_or_default(),
+        "type" => device.identity.device_type.to_string(),
+        "hostname" => device.hostname.to_string(),
+        "interface" => device.interface_name.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Formats a round-trip time in milliseconds, e.g. "1.4 ms"
+pub fn format_latency(latency: Duration) -> String {
+    format!("{:.1} ms", latency.as_secs_f64() * 1000.0)
+}
+
+/// Formats a transfer rate with decimal units, e.g. "1.2 MB/s"
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    match bytes_per_sec {
+        rate if rate >= 1e9 => format!("{:.1} GB/s", rate / 1e9),
+        rate if rate >= 1e6 => format!("{:.1} MB/s", rate / 1e6),
+        rate if rate >= 1e3 => format!("{:.0} kB/s", rate / 1e3),
+        rate => format!("{:.0} B/s", rate),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Gateway, InterfaceName, MacAddress, NetworkInterface, Throughput};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn sample_data() -> NetworkData {
+        let gateway_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let mut router = NetworkDevice::new(
+            gateway_ip,
+            MacAddress::new("00:11:22:33:44:55".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        router.identity.device_type = DeviceType::Router;
+        router.latency = Some(Duration::from_micros(1420));
+
+        let mut laptop = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+            MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        laptop.is_new = true;
+
+        NetworkData::new(vec![], vec![router, laptop], Some(Gateway::new(gateway_ip)), vec![])
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_templates() {
+        assert!(parse_text_template("{count} devices").is_ok());
+        assert!(parse_text_template("{{literal}}").is_ok());
+
+        let unknown = parse_text_template("{cuont}").unwrap_err().to_string();
+        assert!(unknown.contains("Unknown placeholder {cuont}"));
+        assert!(parse_text_template("{count").is_err());
+        assert!(parse_text_template("count}").is_err());
+        assert!(parse_device_template("{count}").is_err());
+    }
+
+    #[test]
+    fn test_default_bar_text() {
+        let options = DisplayOptions::new();
+        assert_eq!(options.bar_text(&NetworkData::new(vec![], vec![], None, vec![])), "🖧 No devices");
+        assert_eq!(options.bar_text(&sample_data()), "🖧 2 devices");
+    }
+
+    #[test]
+    fn test_custom_bar_text() {
+        let options = DisplayOptions {
+            text_template: parse_text_template(
+                "{count} ({active} up, {new} new) gw {gateway_latency} {{{by_type}}}").unwrap(),
+            ..DisplayOptions::new()
+        };
+        assert_eq!(options.bar_text(&sample_data()), "2 (2 up, 1 new) gw 1.4 ms {🌐 1 🖥 1}");
+    }
+
+    #[test]
+    fn test_throughput_placeholders() {
+        let options = DisplayOptions {
+            text_template: parse_text_template("↓{rx} ↑{tx}").unwrap(),
+            ..DisplayOptions::new()
+        };
+        let mut data = sample_data();
+        assert_eq!(options.bar_text(&data), "↓-- ↑--");
+
+        let interface = |name: &str, rx_bytes_per_sec, tx_bytes_per_sec| {
+            let mut interface = NetworkInterface::new(
+                InterfaceName::new(name.to_string()), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), None);
+            interface.throughput = Some(Throughput { rx_bytes_per_sec, tx_bytes_per_sec });
+            interface
+        };
+        data.interfaces = vec![interface("eth0", 1_200_000.0, 800.0), interface("wlan0", 300_000.0, 34_600.0)];
+        assert_eq!(options.bar_text(&data), "↓1.5 MB/s ↑35 kB/s");
+    }
+
+    #[test]
+    fn test_device_values() {
+        let data = sample_data();
+        let template = parse_device_template("{name} {ip} {latency}").unwrap();
+
+        let router = template.render(|name| device_value(&data.devices[0], name, &IconSet::default()));
+        assert_eq!(router, "Router 192.168.1.1 1.4 ms");
+
+        let laptop = template.render(|name| device_value(&data.devices[1], name, &IconSet::default()));
+        assert_eq!(laptop, "Device 192.168.1.20 ");
+    }
+}
@@ -0,0 +1,165 @@
+//! ANSI terminal rendering of the tooltip tree.
+
+use super::template::DisplayOptions;
+use super::tree::{AnsiStyle, TreeLayout};
+use crate::domain::NetworkData;
+use std::io::IsTerminal;
+use unicode_width::UnicodeWidthChar;
+
+/// Formats the tooltip tree for a terminal
+pub struct TerminalFormatter {
+    tree: TreeLayout<AnsiStyle>,
+    width: Option<usize>,
+}
+
+impl TerminalFormatter {
+    /// Creates a TerminalFormatter configured for the current stdout
+    /// Colors are used only on a TTY and when NO_COLOR is unset
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a TerminalFormatter for the current stdout using custom templates
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        let is_tty = std::io::stdout().is_terminal();
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let width = if is_tty { terminal_width() } else { None };
+
+        Self::with_settings(options, is_tty && !no_color, width)
+    }
+
+    /// Creates a TerminalFormatter with explicit color and width settings
+    pub fn with_settings(options: &DisplayOptions, color: bool, width: Option<usize>) -> Self {
+        Self {
+            tree: TreeLayout::with_options(AnsiStyle { enabled: color }, options),
+            width,
+        }
+    }
+
+    /// Formats the tree, truncating lines that exceed the terminal width
+    pub fn format(&self, network_data: &NetworkData) -> String {
+        self.tree
+            .build(network_data)
+            .into_iter()
+            .map(|line| match self.width {
+                Some(width) => truncate_visible(&line, width),
+                None => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for TerminalFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Detects terminal width from COLUMNS, falling back to the TTY window size
+fn terminal_width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(columns);
+    }
+
+    // SAFETY: TIOCGWINSZ only writes into the provided winsize struct
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+/// Truncates a line to `width` visible columns, ignoring ANSI escape sequences
+/// Appends an ellipsis and a color reset when text is cut
+fn truncate_visible(line: &str, width: usize) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut visible = 0;
+    let mut chars = line.chars().peekable();
+    let mut has_escape = false;
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Copy the whole escape sequence up to its final 'm'
+            has_escape = true;
+            result.push(c);
+            for next in chars.by_ref() {
+                result.push(next);
+                if next == 'm' {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let w = c.width().unwrap_or(0);
+        let remaining_is_wider = visible + w > width
+            || (visible + w == width && chars.peek().is_some_and(|n| *n != '\x1b'));
+        if remaining_is_wider {
+            result.push('…');
+            if has_escape {
+                result.push_str("\x1b[0m");
+            }
+            return result;
+        }
+
+        result.push(c);
+        visible += w;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{InterfaceName, MacAddress, NetworkDevice, NetworkInterface};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn sample_data() -> NetworkData {
+        let interface = NetworkInterface::new(
+            InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            None,
+        );
+        let device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+            MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        NetworkData::new(vec![interface], vec![device], None, vec![])
+    }
+
+    #[test]
+    fn test_format_with_color() {
+        let formatter = TerminalFormatter::with_settings(&DisplayOptions::default(), true, None);
+        let output = formatter.format(&sample_data());
+
+        assert!(output.starts_with("🔌 eth0: 192.168.1.10"));
+        assert!(output.contains("\x1b[92m")); // Freshly seen device is Active
+        assert!(!output.contains("<span"));
+    }
+
+    #[test]
+    fn test_format_without_color() {
+        let formatter = TerminalFormatter::with_settings(&DisplayOptions::default(), false, None);
+        let output = formatter.format(&sample_data());
+
+        assert!(!output.contains('\x1b'));
+        assert!(output.contains("(192.168.1.20)"));
+    }
+
+    #[test]
+    fn test_truncate_visible() {
+        assert_eq!(truncate_visible("short", 10), "short");
+        assert_eq!(truncate_visible("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_visible("much longer line", 8), "much lo…");
+
+        // Escape sequences do not count towards the width
+        let colored = "\x1b[92mgreen text\x1b[0m";
+        assert_eq!(truncate_visible(colored, 20), colored);
+        assert_eq!(truncate_visible(colored, 6), "\x1b[92mgreen…\x1b[0m");
+
+        // Accented Latin letters are one column, emoji two
+        assert_eq!(truncate_visible("Café Müller", 11), "Café Müller");
+        assert_eq!(truncate_visible("📺 TV set", 5), "📺 T…");
+    }
+}
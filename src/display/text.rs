@@ -0,0 +1,247 @@
+//! Plain-text terminal output for the CLI subcommands.
+
+use super::template::DisplayOptions;
+use crate::domain::{Hostname, IconSet, NetworkData, NetworkDevice};
+use unicode_width::UnicodeWidthStr;
+
+/// Formats network data as human-readable terminal text
+pub struct TextFormatter {
+    icons: IconSet,
+}
+
+impl TextFormatter {
+    /// Creates a new TextFormatter instance
+    pub fn new() -> Self {
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a TextFormatter using the icons from the display options
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            icons: options.icons.clone(),
+        }
+    }
+
+    /// Formats all devices as an aligned table, one device per line
+    pub fn format_list(&self, network_data: &NetworkData) -> String {
+        let header = ["IP", "MAC", "INTERFACE", "TYPE", "NAME", "STATUS"].map(String::from);
+
+        let mut devices: Vec<&NetworkDevice> = network_data.devices.iter().collect();
+        devices.sort_by_key(|d| (d.interface_name.to_string(), d.ip));
+
+        let rows: Vec<[String; 6]> = devices
+            .iter()
+            .map(|device| {
+                [
+                    device.ip.to_string(),
+                    device.mac.to_string(),
+                    device.interface_name.to_string(),
+                    device.identity.device_type.to_string(),
+                    self.device_name(device),
+                    format!("{:?}", device.activity_status()),
+                ]
+            })
+            .collect();
+
+        let widths: Vec<usize> = (0..header.len())
+            .map(|col| {
+                std::iter::once(&header)
+                    .chain(rows.iter())
+                    .map(|row| row[col].width())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        std::iter::once(&header)
+            .chain(rows.iter())
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Formats everything known about a single device
+    pub fn format_device(&self, device: &NetworkDevice, network_data: &NetworkData) -> String {
+        let identity = &device.identity;
+        let mut lines = vec![
+            identity.format_with(&self.icons),
+            format!("  IP:           {}", device.ip),
+            format!("  MAC:          {}", device.mac),
+            format!("  Interface:    {}", device.interface_name),
+            format!("  Hostname:     {}", device.hostname),
+            format!("  Status:       {:?}", device.activity_status()),
+            format!("  Type:         {}", identity.device_type),
+        ];
+
+        if let Some(mfr) = &identity.manufacturer {
+            lines.push(format!("  Manufacturer: {}", mfr.as_str()));
+        }
+        if let Some(model) = &identity.model {
+            lines.push(format!("  Model:        {}", model.as_str()));
+        }
+        if let Some(name) = &identity.friendly_name {
+            lines.push(format!("  Name:         {}", name.as_str()));
+        }
+        if network_data.gateway.is_some_and(|gw| gw.0 == device.ip) {
+            lines.push("  Role:         Gateway".to_string());
+        }
+
+        lines.push(String::new());
+        lines.push("Services:".to_string());
+        if device.services.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+        for service in &device.services {
+            lines.push(format!(
+                "  {} {} port {} ({})",
+                service.friendly_type(),
+                service.service_type,
+                service.port,
+                service.instance_name
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("UPnP:".to_string());
+        match &device.upnp_info {
+            Some(upnp) => {
+                let fields = [
+                    ("Friendly name", upnp.friendly_name.as_ref().map(|v| v.as_str())),
+                    ("Manufacturer", upnp.manufacturer.as_ref().map(|v| v.as_str())),
+                    ("Model", upnp.model_name.as_ref().map(|v| v.as_str())),
+                    ("Device type", upnp.device_type.as_ref().map(|v| v.as_str())),
+                ];
+                for (label, value) in fields {
+                    if let Some(value) = value {
+                        lines.push(format!("  {}: {}", label, value));
+                    }
+                }
+            }
+            None => lines.push("  (none)".to_string()),
+        }
+
+        if let Some(igd) = &device.igd {
+            lines.push(String::new());
+            lines.push("Internet gateway:".to_string());
+            if let Some(external_ip) = igd.external_ip {
+                lines.push(format!("  External IP: {}", external_ip));
+            }
+            if let Some(status) = &igd.connection_status {
+                lines.push(format!("  Status: {}", status));
+            }
+            if let Some(uptime) = igd.uptime {
+                lines.push(format!("  Uptime: {}", humantime::format_duration(uptime)));
+            }
+            for mapping in &igd.port_mappings {
+                lines.push(format!(
+                    "  Port mapping: {} {} -> {}:{} \"{}\"{}",
+                    mapping.protocol,
+                    mapping.external_port,
+                    mapping.internal_client,
+                    mapping.internal_port,
+                    mapping.description,
+                    if mapping.enabled { "" } else { " (disabled)" }
+                ));
+            }
+        }
+
+        lines.push(String::new());
+        lines.push("Evidence:".to_string());
+        if identity.evidence.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+        for evidence in &identity.evidence {
+            lines.push(format!("  {}", evidence));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Best available name for a device in a single table cell
+    fn device_name(&self, device: &NetworkDevice) -> String {
+        if let Some(name) = &device.identity.friendly_name {
+            return name.as_str().to_string();
+        }
+        match &device.hostname {
+            Hostname::Resolved(name) => name.clone(),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+impl Default for TextFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Gateway, IconTheme, InterfaceName, MacAddress, NetworkDevice};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn sample_data() -> NetworkData {
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let mac = MacAddress::new("00:11:22:33:44:55".to_string()).unwrap();
+        let mut router = NetworkDevice::new(ip, mac, InterfaceName::new("eth0".to_string()));
+        router.hostname = Hostname::resolved("router.lan".to_string());
+        router.build_identity();
+
+        NetworkData::new(vec![], vec![router], Some(Gateway::new(ip)), vec![])
+    }
+
+    #[test]
+    fn test_format_list() {
+        let data = sample_data();
+        let table = TextFormatter::new().format_list(&data);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("IP"));
+        assert!(lines[1].contains("192.168.1.1"));
+        assert!(lines[1].contains("00:11:22:33:44:55"));
+        assert!(lines[1].contains("router.lan"));
+        // Columns line up
+        assert_eq!(lines[0].find("MAC"), lines[1].find("00:11"));
+    }
+
+    #[test]
+    fn test_format_device() {
+        let data = sample_data();
+        let output = TextFormatter::new().format_device(&data.devices[0], &data);
+
+        assert!(output.contains("Role:         Gateway"));
+        assert!(output.contains("Type:         Router"));
+        assert!(output.contains("Hostname: device type Router"));
+        assert!(output.starts_with("🌐 "));
+    }
+
+    #[test]
+    fn test_format_device_with_icon_theme() {
+        let data = sample_data();
+        let options = DisplayOptions { icons: IconSet::new(IconTheme::Ascii), ..DisplayOptions::default() };
+        let output = TextFormatter::with_options(&options).format_device(&data.devices[0], &data);
+
+        assert!(output.starts_with("[RTR] "));
+    }
+
+    #[test]
+    fn test_format_list_aligns_wide_names() {
+        let mut data = sample_data();
+        data.devices[0].hostname = Hostname::resolved("Café 📺".to_string());
+        let table = TextFormatter::new().format_list(&data);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines[0].find("STATUS").map(|i| lines[0][..i].width()),
+            lines[1].rfind("Active").map(|i| lines[1][..i].width()));
+    }
+}
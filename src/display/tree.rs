@@ -0,0 +1,850 @@
+//! Tooltip tree layout shared by the Waybar and terminal backends.
+
+use super::markup::{Color, Markup};
+use super::template::{device_value, format_latency, format_rate, DisplayOptions};
+use crate::domain::{
+    ActivityStatus, ConnectivityStatus, DeviceTraffic, DeviceType, Hostname, NetworkData, NetworkDevice,
+    NetworkInterface, PrinterState, WifiLink,
+};
+use anyhow::{bail, Result};
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+/// Number of devices listed under "Top talkers"
+pub const TOP_TALKERS: usize = 3;
+
+/// Rendering of tree lines, which differs between output backends
+pub trait TreeStyle {
+    /// Renders one line, escaping all text for the backend
+    fn render(&self, line: &Markup) -> String;
+}
+
+/// Pango markup styling for Waybar tooltips
+pub struct PangoStyle;
+
+impl TreeStyle for PangoStyle {
+    fn render(&self, line: &Markup) -> String {
+        line.to_pango()
+    }
+}
+
+/// ANSI escape styling for terminals; plain text when disabled
+pub struct AnsiStyle {
+    pub enabled: bool,
+}
+
+impl TreeStyle for AnsiStyle {
+    fn render(&self, line: &Markup) -> String {
+        if self.enabled { line.to_ansi() } else { line.to_plain() }
+    }
+}
+
+/// How devices are grouped in the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    #[default]
+    Interface,
+    DeviceType,
+    Status,
+}
+
+impl GroupBy {
+    /// Stable identifiers accepted on the command line
+    pub const IDS: [&'static str; 3] = ["interface", "type", "status"];
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "interface" => Ok(Self::Interface),
+            "type" => Ok(Self::DeviceType),
+            "status" => Ok(Self::Status),
+            _ => bail!("Unknown grouping '{}' (expected one of: {})", s, Self::IDS.join(", ")),
+        }
+    }
+}
+
+/// Order of devices within a group; ties are broken by IP address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Ip,
+    Name,
+    /// Most recently seen first
+    LastSeen,
+    /// Fastest first; devices without a measurement last
+    Latency,
+}
+
+impl SortKey {
+    /// Stable identifiers accepted on the command line
+    pub const IDS: [&'static str; 4] = ["ip", "name", "last-seen", "latency"];
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ip" => Ok(Self::Ip),
+            "name" => Ok(Self::Name),
+            "last-seen" => Ok(Self::LastSeen),
+            "latency" => Ok(Self::Latency),
+            _ => bail!("Unknown sort key '{}' (expected one of: {})", s, Self::IDS.join(", ")),
+        }
+    }
+}
+
+/// Lays out devices as a tree, grouped by interface, type or status
+pub struct TreeLayout<S> {
+    style: S,
+    options: DisplayOptions,
+}
+
+impl<S: TreeStyle> TreeLayout<S> {
+    /// Creates a TreeLayout with the given styling and display options
+    pub fn with_options(style: S, options: &DisplayOptions) -> Self {
+        Self {
+            style,
+            options: options.clone(),
+        }
+    }
+
+    /// Builds the full tree, one block per group
+    /// Truncated to `max_lines` with a summary of the devices left out
+    pub fn build(&self, network_data: &NetworkData) -> Vec<String> {
+        if network_data.interfaces.is_empty() {
+            return vec!["No network interfaces found".to_string()];
+        }
+
+        let visible: Vec<&NetworkDevice> = network_data.devices
+            .iter()
+            .filter(|device| self.is_visible(device))
+            .collect();
+
+        let mut lines = self.format_connectivity(network_data);
+        lines.extend(self.format_gateway_health(network_data));
+        lines.extend(self.format_top_talkers(network_data));
+        let mut device_lines = Vec::new(); // Index of each device's first line
+
+        for (header, devices) in self.groups(network_data, &visible) {
+            lines.push(header);
+
+            if devices.is_empty() {
+                lines.push(Markup::from("  └─ No devices"));
+            }
+            let sorted_devices = self.sort_devices(&devices);
+            for (i, device) in sorted_devices.iter().enumerate() {
+                device_lines.push(lines.len());
+                let is_last = i == sorted_devices.len() - 1;
+                lines.extend(self.format_device_entry(device, is_last, network_data));
+            }
+
+            lines.push(Markup::new()); // Empty line between groups
+        }
+
+        if lines.is_empty() {
+            lines.push(Markup::from("No devices"));
+        }
+        if let Some(max_lines) = self.options.max_lines
+            && lines.len() > max_lines
+        {
+            let kept = max_lines.saturating_sub(1);
+            let hidden = device_lines.iter().filter(|&&start| start >= kept).count();
+            lines.truncate(kept);
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+            lines.push(Markup::from(match hidden {
+                0 => "…".to_string(),
+                1 => "… +1 more device".to_string(),
+                n => format!("… +{} more devices", n),
+            }));
+        }
+
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines.iter().map(|line| self.style.render(line)).collect()
+    }
+
+    /// Connectivity check result with the captive portal URL, followed by an empty line
+    fn format_connectivity(&self, network_data: &NetworkData) -> Vec<Markup> {
+        let Some(check) = &network_data.connectivity else {
+            return Vec::new();
+        };
+
+        let prefix = Markup::from("Internet: ");
+        let line = match (check.status, &check.portal_url) {
+            (ConnectivityStatus::Online, _) => prefix.text("online"),
+            (ConnectivityStatus::Captive, Some(portal_url)) => prefix
+                .colored(Some(Color::Yellow), "captive portal")
+                .text(format!(" ({})", portal_url)),
+            (ConnectivityStatus::Captive, None) => prefix.colored(Some(Color::Yellow), "captive portal"),
+            (ConnectivityStatus::Offline, _) => prefix.colored(Some(Color::Red), "offline"),
+        };
+        vec![line, Markup::new()]
+    }
+
+    /// Gateway latency, loss and upstream reachability, followed by an empty line
+    fn format_gateway_health(&self, network_data: &NetworkData) -> Vec<Markup> {
+        let (Some(gateway), Some(health)) = (network_data.gateway, &network_data.gateway_health) else {
+            return Vec::new();
+        };
+
+        let mut lines = vec![match health.latency {
+            _ if !health.was_probed() => Markup::from(format!("Gateway {}: ", gateway))
+                .colored(Some(Color::Yellow), "not probed"),
+            Some(latency) if health.is_responding() => Markup::from(format!(
+                "Gateway {}: {}, {:.0}% loss", gateway, format_latency(latency), health.loss_percent()
+            )),
+            _ => Markup::from(format!("Gateway {}: ", gateway))
+                .colored(Some(Color::Red), format!("no reply ({:.0}% loss)", health.loss_percent())),
+        }];
+
+        if let Some(upstream) = &health.upstream {
+            let prefix = Markup::from(format!("  └─ Upstream {}: ", upstream.target));
+            lines.push(match (upstream.reachable, upstream.latency) {
+                (true, Some(latency)) => prefix.text(format!("reachable ({})", format_latency(latency))),
+                (true, None) => prefix.text("reachable"),
+                (false, _) => prefix.colored(Some(Color::Red), "unreachable"),
+            });
+        }
+
+        lines.push(Markup::new());
+        lines
+    }
+
+    /// Devices using the most bandwidth, followed by an empty line
+    /// Ranked by current rate, or by total bytes before rates are available
+    fn format_top_talkers(&self, network_data: &NetworkData) -> Vec<Markup> {
+        let mut talkers: Vec<(&NetworkDevice, &DeviceTraffic)> = network_data.devices
+            .iter()
+            .filter_map(|device| Some((device, device.traffic.as_ref()?)))
+            .collect();
+        if talkers.is_empty() {
+            return Vec::new();
+        }
+        let load = |traffic: &DeviceTraffic| match traffic.rates {
+            Some(rates) => rates.rx_bytes_per_sec + rates.tx_bytes_per_sec,
+            None => (traffic.rx_bytes + traffic.tx_bytes) as f64,
+        };
+        talkers.sort_by(|a, b| load(b.1).total_cmp(&load(a.1)));
+        talkers.truncate(TOP_TALKERS);
+
+        let mut lines = vec![Markup::from("Top talkers")];
+        for (i, (device, traffic)) in talkers.iter().enumerate() {
+            let branch = if i == talkers.len() - 1 { "  └─ " } else { "  ├─ " };
+            let usage = match traffic.rates {
+                Some(rates) => format!("↓ {} ↑ {}", format_rate(rates.rx_bytes_per_sec), format_rate(rates.tx_bytes_per_sec)),
+                None => format!("↓ {} ↑ {}", format_bytes(traffic.rx_bytes), format_bytes(traffic.tx_bytes)),
+            };
+            let icon = self.options.icons.device(device.identity.device_type);
+            lines.push(Markup::from(branch)
+                .text(format!("{} {} ({}): {}", icon.trim_end(), device.identity.name(), device.ip, usage)));
+        }
+        lines.push(Markup::new());
+        lines
+    }
+
+    /// Devices shown after applying the hide filters
+    fn is_visible(&self, device: &NetworkDevice) -> bool {
+        let is_stale = device.activity_status() == ActivityStatus::Stale;
+        let is_unnamed = !matches!(device.hostname, Hostname::Resolved(_))
+            && device.identity.friendly_name.is_none();
+        let hidden = (self.options.hide_stale && is_stale) || (self.options.hide_unnamed && is_unnamed);
+        !hidden
+    }
+
+    /// Group headers with their devices, in display order
+    fn groups<'a>(&self, network_data: &NetworkData, devices: &[&'a NetworkDevice])
+        -> Vec<(Markup, Vec<&'a NetworkDevice>)> {
+        let icons = &self.options.icons;
+        let select = |keep: &dyn Fn(&NetworkDevice) -> bool| -> Vec<&'a NetworkDevice> {
+            devices.iter().copied().filter(|device| keep(device)).collect()
+        };
+
+        match self.options.group_by {
+            GroupBy::Interface => network_data.interfaces
+                .iter()
+                .map(|interface| (
+                    self.format_interface_header(interface),
+                    select(&|device| device.interface_name == interface.name),
+                ))
+                .collect(),
+            GroupBy::DeviceType => DeviceType::ALL
+                .iter()
+                .map(|device_type| (device_type, select(&|device| device.identity.device_type == *device_type)))
+                .filter(|(_, group)| !group.is_empty())
+                .map(|(device_type, group)| (
+                    Markup::from(format!("{} {} ({})", icons.device(*device_type), device_type, group.len())),
+                    group,
+                ))
+                .collect(),
+            GroupBy::Status => ActivityStatus::ALL
+                .iter()
+                .map(|status| (status, select(&|device| device.activity_status() == *status)))
+                .filter(|(_, group)| !group.is_empty())
+                .map(|(status, group)| (
+                    Markup::new().colored(Color::for_activity(*status), format!("{:?} ({})", status, group.len())),
+                    group,
+                ))
+                .collect(),
+        }
+    }
+
+    /// Format interface header line
+    /// Wireless interfaces add SSID, BSSID, channel, signal and bitrate;
+    /// measured interfaces add their receive and transmit rates
+    pub fn format_interface_header(&self, interface: &NetworkInterface) -> Markup {
+        let icon = self.options.icons.interface(&interface.name);
+        let header = Markup::from(if let Some(mac) = &interface.mac {
+            format!("{} {}: {} ({})", icon, interface.name, interface.ip, mac)
+        } else {
+            format!("{} {}: {}", icon, interface.name, interface.ip)
+        });
+        let wifi = interface.wifi.as_ref().map(format_wifi_link).unwrap_or_default();
+        let throughput = interface.throughput.map(|rates| {
+            format!("↓ {} ↑ {}", format_rate(rates.rx_bytes_per_sec), format_rate(rates.tx_bytes_per_sec))
+        });
+
+        wifi.into_iter()
+            .chain(throughput)
+            .fold(header, |header, detail| header.text(" · ").text(detail))
+    }
+
+    /// Sort devices by the configured key, then by IP address
+    fn sort_devices<'a>(&self, devices: &[&'a NetworkDevice]) -> Vec<&'a NetworkDevice> {
+        let mut sorted = devices.to_vec();
+        match self.options.sort_by {
+            SortKey::Ip => sorted.sort_by_key(|d| d.ip),
+            SortKey::Name => sorted.sort_by_key(|d| (d.identity.name().to_lowercase(), d.ip)),
+            SortKey::LastSeen => sorted.sort_by_key(|d| (Reverse(d.last_seen), d.ip)),
+            SortKey::Latency => sorted.sort_by_key(|d| (d.latency.is_none(), d.latency, d.ip)),
+        }
+        sorted
+    }
+
+    /// Format a single device entry with its services and gateway info
+    pub fn format_device_entry(&self, device: &NetworkDevice, is_last: bool,
+        network_data: &NetworkData) -> Vec<Markup> {
+        let mut lines = Vec::new();
+        let prefix = if is_last { "  └─ " } else { "  ├─ " };
+
+        // Main device line; the name is colored by activity status
+        let line = self.options.device_template.render_markup(|name| match name {
+            "name" => Markup::new().colored(Color::for_activity(device.activity_status()), device.identity.name()),
+            _ => Markup::from(device_value(device, name, &self.options.icons)),
+        });
+        lines.push(Markup::from(prefix).append(line).trim_end());
+
+        // Services
+        if let Some(services_line) = self.format_services(device, is_last) {
+            lines.push(Markup::from(services_line));
+        }
+
+        // Printer state and supplies
+        lines.extend(self.format_printer(device, is_last));
+
+        // WAN status and port mappings of UPnP gateways
+        lines.extend(self.format_igd(device, is_last, network_data));
+
+        // Gateway/DNS info
+        lines.extend(self.format_gateway_info(device, is_last, network_data).into_iter().map(Markup::from));
+
+        lines
+    }
+
+    /// Format services list for a device
+    fn format_services(&self, device: &NetworkDevice, is_last: bool) -> Option<String> {
+        if device.services.is_empty() {
+            return None;
+        }
+
+        let service_prefix = if is_last { "      " } else { "  │   " };
+        let mut unique_services: Vec<String> = device.services
+            .iter()
+            .map(|s| s.friendly_type().to_string())
+            .collect();
+        unique_services.sort();
+        unique_services.dedup();
+
+        if unique_services.is_empty() {
+            None
+        } else {
+            Some(format!("{}  Services: {}", service_prefix, unique_services.join(", ")))
+        }
+    }
+
+    /// Printer model, state and reasons, then supply levels with low ones in red
+    fn format_printer(&self, device: &NetworkDevice, is_last: bool) -> Vec<Markup> {
+        let Some(printer) = &device.printer else {
+            return Vec::new();
+        };
+        let printer_prefix = if is_last { "      " } else { "  │   " };
+
+        let mut status = Markup::from(format!("{}  Printer: ", printer_prefix));
+        let mut separator = "";
+        if let Some(model) = &printer.model {
+            status = status.text(model.clone());
+            separator = ", ";
+        }
+        if let Some(state) = printer.state {
+            let color = (state == PrinterState::Stopped).then_some(Color::Red);
+            status = status.text(separator).colored(color, state.as_str());
+            separator = ", ";
+        }
+        if !printer.state_reasons.is_empty() {
+            status = status.text(separator).colored(Some(Color::Yellow), printer.state_reasons.join(", "));
+        }
+        let mut lines = vec![status];
+
+        if !printer.markers.is_empty() {
+            let mut supplies = Markup::from(format!("{}  Supplies: ", printer_prefix));
+            for (index, marker) in printer.markers.iter().enumerate() {
+                if index > 0 {
+                    supplies = supplies.text(", ");
+                }
+                let text = match marker.level {
+                    Some(level) => format!("{} {}%", marker.name, level),
+                    None => marker.name.clone(),
+                };
+                supplies = supplies.colored(marker.is_low().then_some(Color::Red), text);
+            }
+            lines.push(supplies);
+        }
+        lines
+    }
+
+    /// External address, status and uptime, then one line per port mapping
+    fn format_igd(&self, device: &NetworkDevice, is_last: bool, network_data: &NetworkData) -> Vec<Markup> {
+        let Some(igd) = &device.igd else {
+            return Vec::new();
+        };
+        let igd_prefix = if is_last { "      " } else { "  │   " };
+
+        let mut wan = Vec::new();
+        if let Some(external_ip) = igd.external_ip {
+            wan.push(external_ip.to_string());
+        }
+        if let Some(status) = &igd.connection_status {
+            wan.push(status.clone());
+        }
+        if let Some(uptime) = igd.uptime {
+            wan.push(format!("up {}", format_uptime(uptime)));
+        }
+        let mut lines = Vec::new();
+        if !wan.is_empty() {
+            lines.push(Markup::from(format!("{}  WAN: {}", igd_prefix, wan.join(", "))));
+        }
+
+        for mapping in &igd.port_mappings {
+            let client = match network_data.devices.iter().find(|d| Some(d.ip) == mapping.internal_ip()) {
+                Some(client) => format!("{} ({})", client.identity.name(), client.ip),
+                None => mapping.internal_client.clone(),
+            };
+            let mut line = format!("{}  Forward {} {} → {}:{}", igd_prefix, mapping.protocol,
+                mapping.external_port, client, mapping.internal_port);
+            if !mapping.description.is_empty() {
+                line.push_str(&format!(" \"{}\"", mapping.description));
+            }
+            if !mapping.enabled {
+                line.push_str(" (disabled)");
+            }
+            lines.push(Markup::from(line));
+        }
+        lines
+    }
+
+    /// Format gateway and DNS information for a device
+    fn format_gateway_info(&self, device: &NetworkDevice, is_last: bool,
+        network_data: &NetworkData) -> Vec<String> {
+        use std::net::IpAddr;
+
+        let Some(gateway) = network_data.gateway else { return Vec::new() };
+        if device.ip != gateway.0 {
+            return Vec::new();
+        }
+
+        let mut lines = Vec::new();
+        let info_prefix = if is_last { "      " } else { "  │   " };
+
+        // Gateway label
+        let dns_matches_gateway = network_data.dns_servers.iter().any(|dns| dns == &gateway.0);
+        if dns_matches_gateway {
+            lines.push(format!("{}  Gateway (also DNS)", info_prefix));
+        } else {
+            lines.push(format!("{}  Gateway", info_prefix));
+        }
+
+        // Additional DNS servers
+        let other_dns: Vec<&IpAddr> = network_data.dns_servers
+            .iter()
+            .filter(|dns| *dns != &gateway.0)
+            .collect();
+
+        if !other_dns.is_empty() {
+            let dns_list: Vec<String> = other_dns
+                .iter()
+                .map(|dns| self.format_dns_entry(dns))
+                .collect();
+            lines.push(format!("{}  DNS: {}", info_prefix, dns_list.join(", ")));
+        }
+
+        // Public address over NAT-PMP or PCP
+        if let Some(nat) = &network_data.gateway_nat {
+            lines.push(format!("{}  Public IP: {} ({}, up {})",
+                info_prefix, nat.external_ip, nat.protocol.as_str(), format_uptime(nat.epoch)));
+        }
+
+        lines
+    }
+
+    /// Format a single DNS entry with local/external label
+    fn format_dns_entry(&self, dns: &std::net::IpAddr) -> String {
+        use std::net::IpAddr;
+
+        let is_local = match dns {
+            IpAddr::V4(ipv4) => {
+                let octets = ipv4.octets();
+                octets[0] == 192 && octets[1] == 168
+                    || octets[0] == 10
+                    || (octets[0] == 172 && (16..=31).contains(&octets[1]))
+            }
+            IpAddr::V6(_) => false,
+        };
+
+        if is_local {
+            format!("{} (local)", dns)
+        } else {
+            format!("{} (external)", dns)
+        }
+    }
+}
+
+/// Formats a byte count with decimal units, e.g. "4.2 MB"
+fn format_bytes(bytes: u64) -> String {
+    match bytes as f64 {
+        size if size >= 1e9 => format!("{:.1} GB", size / 1e9),
+        size if size >= 1e6 => format!("{:.1} MB", size / 1e6),
+        size if size >= 1e3 => format!("{:.0} kB", size / 1e3),
+        size => format!("{:.0} B", size),
+    }
+}
+
+/// Formats an uptime with its two largest units, e.g. "3d 4h" or "12m"
+fn format_uptime(uptime: std::time::Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// Link details in display order, e.g. ["Home (00:11:22:33:44:55)", "ch 36, 5180 MHz", "-52 dBm", "866.7 Mbit/s"]
+fn format_wifi_link(wifi: &WifiLink) -> Vec<String> {
+    let network = match (&wifi.ssid, &wifi.bssid) {
+        (Some(ssid), Some(bssid)) => Some(format!("{} ({})", ssid, bssid)),
+        (Some(ssid), None) => Some(ssid.clone()),
+        (None, Some(bssid)) => Some(bssid.to_string()),
+        (None, None) => None,
+    };
+    let frequency = match (wifi.channel(), wifi.frequency_mhz) {
+        (Some(channel), Some(freq)) => Some(format!("ch {}, {} MHz", channel, freq)),
+        (None, Some(freq)) => Some(format!("{} MHz", freq)),
+        _ => None,
+    };
+
+    [
+        network,
+        frequency,
+        wifi.signal_dbm.map(|dbm| format!("{} dBm", dbm)),
+        wifi.tx_bitrate_mbps.map(|rate| format!("{:.1} Mbit/s", rate)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        FriendlyName, Gateway, GatewayHealth, GatewayNat, IgdStatus, InterfaceName, MacAddress, MarkerLevel, PortMapping,
+        NatProtocol, PrinterStatus, Throughput, UpstreamCheck,
+    };
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, SystemTime};
+
+    fn busy_network() -> NetworkData {
+        let interface = NetworkInterface::new(
+            InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            None,
+        );
+        let devices = (1..=5u8)
+            .map(|i| {
+                let mut device = NetworkDevice::new(
+                    IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20 + i)),
+                    MacAddress::new(format!("00:00:00:00:00:0{}", i)).unwrap(),
+                    InterfaceName::new("eth0".to_string()),
+                );
+                device.hostname = Hostname::resolved(format!("host{}", i));
+                device.identity.device_type = if i % 2 == 0 { DeviceType::Computer } else { DeviceType::Printer };
+                device.latency = Some(Duration::from_millis(10 - u64::from(i)));
+                device
+            })
+            .collect();
+        NetworkData::new(vec![interface], devices, None, vec![])
+    }
+
+    fn layout(options: DisplayOptions) -> TreeLayout<AnsiStyle> {
+        TreeLayout::with_options(AnsiStyle { enabled: false }, &options)
+    }
+
+    #[test]
+    fn test_ansi_style() {
+        let line = Markup::new().colored(Color::for_activity(ActivityStatus::Active), "TV");
+        assert_eq!(AnsiStyle { enabled: true }.render(&line), "\x1b[92mTV\x1b[0m");
+        assert_eq!(AnsiStyle { enabled: false }.render(&line), "TV");
+    }
+
+    #[test]
+    fn test_pango_escapes_untrusted_names() {
+        let mut data = busy_network();
+        data.devices.truncate(1);
+        data.devices[0].identity.friendly_name = Some(FriendlyName::new("Tom & Jerry's <TV>".to_string()));
+
+        let lines = TreeLayout::with_options(PangoStyle, &DisplayOptions::default()).build(&data);
+        assert!(lines[1].contains("Tom &amp; Jerry&apos;s &lt;TV&gt;"));
+        assert!(!lines[1].contains("<TV>"));
+    }
+
+    #[test]
+    fn test_build_without_devices() {
+        let interface = NetworkInterface::new(
+            InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            Some(MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap()),
+        );
+        let data = NetworkData::new(vec![interface], vec![], None, vec![]);
+        let lines = TreeLayout::with_options(PangoStyle, &DisplayOptions::default()).build(&data);
+
+        assert_eq!(lines, vec![
+            "🔌 eth0: 192.168.1.10 (AA:BB:CC:DD:EE:FF)".to_string(),
+            "  └─ No devices".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_group_by_type_and_sort_by_latency() {
+        let options = DisplayOptions {
+            group_by: GroupBy::DeviceType,
+            sort_by: SortKey::Latency,
+            ..DisplayOptions::default()
+        };
+        let lines = layout(options).build(&busy_network());
+
+        assert_eq!(lines, vec![
+            "🖨 Printer (3)",
+            "  ├─ 🖨 Printer (192.168.1.25)",
+            "  ├─ 🖨 Printer (192.168.1.23)",
+            "  └─ 🖨 Printer (192.168.1.21)",
+            "",
+            "💻 Computer (2)",
+            "  ├─ 💻 Computer (192.168.1.24)",
+            "  └─ 💻 Computer (192.168.1.22)",
+        ]);
+    }
+
+    #[test]
+    fn test_max_lines_summarizes_hidden_devices() {
+        let options = DisplayOptions { max_lines: Some(4), ..DisplayOptions::default() };
+        let lines = layout(options).build(&busy_network());
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[3], "… +3 more devices");
+    }
+
+    #[test]
+    fn test_hide_stale_and_unnamed() {
+        let mut data = busy_network();
+        data.devices[0].last_seen = SystemTime::now() - Duration::from_secs(3600);
+        data.devices[1].hostname = Hostname::Unknown;
+
+        let options = DisplayOptions { hide_stale: true, hide_unnamed: true, ..DisplayOptions::default() };
+        let lines = layout(options).build(&data);
+
+        assert_eq!(lines.len(), 4); // Header and three devices
+        assert!(!lines.iter().any(|line| line.contains("192.168.1.21") || line.contains("192.168.1.22")));
+    }
+
+    #[test]
+    fn test_gateway_health_section() {
+        let mut data = busy_network();
+        data.gateway = Some(Gateway::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
+        data.gateway_health = Some(GatewayHealth {
+            probes_sent: 3,
+            replies: 0,
+            latency: None,
+            upstream: Some(UpstreamCheck {
+                target: "1.1.1.1:53".to_string(),
+                reachable: false,
+                latency: None,
+                error: Some("timed out".to_string()),
+            }),
+        });
+
+        let lines = layout(DisplayOptions::default()).build(&data);
+        assert_eq!(lines[0], "Gateway 192.168.1.1: no reply (100% loss)");
+        assert_eq!(lines[1], "  └─ Upstream 1.1.1.1:53: unreachable");
+        assert_eq!(lines[2], "");
+        assert!(lines[3].contains("eth0"));
+    }
+
+    #[test]
+    fn test_interface_header_with_throughput() {
+        let mut interface = NetworkInterface::new(
+            InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            None,
+        );
+        interface.throughput = Some(Throughput { rx_bytes_per_sec: 1_250_000.0, tx_bytes_per_sec: 512.0 });
+
+        let header = layout(DisplayOptions::default()).format_interface_header(&interface);
+        assert_eq!(header.to_plain(), "🔌 eth0: 192.168.1.10 · ↓ 1.2 MB/s ↑ 512 B/s");
+    }
+
+    #[test]
+    fn test_wireless_interface_header() {
+        let wifi = WifiLink {
+            ssid: Some("Cafe <Guest>".to_string()),
+            bssid: Some(MacAddress::new("00:11:22:33:44:55".to_string()).unwrap()),
+            frequency_mhz: Some(2437),
+            signal_dbm: Some(-67),
+            tx_bitrate_mbps: Some(72.2),
+        };
+        let interface = NetworkInterface::new(
+            InterfaceName::new("wlan0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 10)),
+            None,
+        )
+        .with_wifi(Some(wifi));
+
+        let header = TreeLayout::with_options(PangoStyle, &DisplayOptions::default())
+            .format_interface_header(&interface);
+        assert_eq!(
+            header.to_pango(),
+            "📶 wlan0: 10.0.0.10 · Cafe &lt;Guest&gt; (00:11:22:33:44:55) · ch 6, 2437 MHz · -67 dBm · 72.2 Mbit/s"
+        );
+    }
+
+    #[test]
+    fn test_top_talkers() {
+        let mut data = busy_network();
+        for (i, device) in data.devices.iter_mut().enumerate() {
+            device.traffic = Some(DeviceTraffic {
+                rx_bytes: 1_000 * i as u64,
+                tx_bytes: 100,
+                packets: 10,
+                rates: None,
+            });
+        }
+        data.devices[1].traffic.as_mut().unwrap().rates =
+            Some(Throughput { rx_bytes_per_sec: 2_500_000.0, tx_bytes_per_sec: 40_000.0 });
+
+        let lines = layout(DisplayOptions::default()).build(&data);
+        assert_eq!(&lines[..5], [
+            "Top talkers",
+            "  ├─ 💻 Computer (192.168.1.22): ↓ 2.5 MB/s ↑ 40 kB/s",
+            "  ├─ 🖨 Printer (192.168.1.25): ↓ 4 kB ↑ 100 B",
+            "  └─ 💻 Computer (192.168.1.24): ↓ 3 kB ↑ 100 B",
+            "",
+        ]);
+    }
+
+    #[test]
+    fn test_printer_status_under_device() {
+        let mut data = busy_network();
+        data.devices.truncate(1);
+        data.devices[0].printer = Some(PrinterStatus {
+            model: Some("Brother HL-L2350DW".to_string()),
+            state: Some(PrinterState::Stopped),
+            state_reasons: vec!["media-jam-error".to_string()],
+            markers: vec![
+                MarkerLevel { name: "Black Toner".to_string(), level: Some(8) },
+                MarkerLevel { name: "Drum".to_string(), level: None },
+            ],
+        });
+
+        let lines = layout(DisplayOptions::default()).build(&data);
+        let printer = lines.iter().position(|line| line.contains("Printer: ")).unwrap();
+        assert_eq!(&lines[printer..printer + 2], [
+            "        Printer: Brother HL-L2350DW, stopped, media-jam-error",
+            "        Supplies: Black Toner 8%, Drum",
+        ]);
+    }
+
+    #[test]
+    fn test_gateway_wan_status_and_port_mappings() {
+        let mut data = busy_network();
+        data.devices.truncate(2);
+        let mapping = PortMapping {
+            protocol: "TCP".to_string(),
+            external_port: 51413,
+            internal_client: "192.168.1.22".to_string(),
+            internal_port: 51413,
+            description: "Transmission".to_string(),
+            enabled: true,
+            remote_host: None,
+            lease_duration: Duration::ZERO,
+        };
+        data.devices[0].igd = Some(IgdStatus {
+            external_ip: Some("203.0.113.5".parse().unwrap()),
+            connection_status: Some("Connected".to_string()),
+            uptime: Some(Duration::from_secs(273_600)),
+            port_mappings: vec![
+                mapping.clone(),
+                PortMapping { internal_client: "192.168.1.99".to_string(), enabled: false, ..mapping },
+            ],
+        });
+
+        let lines = layout(DisplayOptions::default()).build(&data);
+        let wan = lines.iter().position(|line| line.contains("WAN: ")).unwrap();
+        assert_eq!(&lines[wan..wan + 3], [
+            "  │     WAN: 203.0.113.5, Connected, up 3d 4h",
+            "  │     Forward TCP 51413 → Computer (192.168.1.22):51413 \"Transmission\"",
+            "  │     Forward TCP 51413 → 192.168.1.99:51413 \"Transmission\" (disabled)",
+        ]);
+        assert_eq!(format_uptime(Duration::from_secs(4_000)), "1h 6m");
+    }
+
+    #[test]
+    fn test_gateway_public_address() {
+        let mut data = busy_network();
+        data.devices.truncate(1);
+        data.gateway = Some(Gateway::new(data.devices[0].ip));
+        data.dns_servers = vec![data.devices[0].ip];
+        data.gateway_nat = Some(GatewayNat {
+            protocol: NatProtocol::NatPmp,
+            external_ip: "203.0.113.5".parse().unwrap(),
+            epoch: Duration::from_secs(90_000),
+        });
+
+        let lines = layout(DisplayOptions::default()).build(&data);
+        let gateway = lines.iter().position(|line| line.contains("Gateway (also DNS)")).unwrap();
+        assert_eq!(lines[gateway + 1], "        Public IP: 203.0.113.5 (NAT-PMP, up 1d 1h)");
+
+        data.gateway_nat = Some(GatewayNat {
+            protocol: NatProtocol::Pcp,
+            external_ip: "203.0.113.7".parse().unwrap(),
+            epoch: Duration::from_secs(600),
+        });
+        let lines = layout(DisplayOptions::default()).build(&data);
+        assert_eq!(lines[gateway + 1], "        Public IP: 203.0.113.7 (PCP, up 10m)");
+    }
+}
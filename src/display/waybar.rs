@@ -1,13 +1,36 @@
 //! Waybar JSON output formatting for network data.
 
-use crate::domain::NetworkData;
+use super::formatter::OutputFormatter;
+use super::markup::{escape_pango, Color, Markup};
+use super::template::DisplayOptions;
+use super::tree::{PangoStyle, TreeLayout};
+use crate::domain::{ConnectivityStatus, DeviceType, NetworkData};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 /// Waybar output format
+///
+/// `class` always contains `network`, plus `active` when devices were found
+/// and every applicable state below. `alt` is the most severe state, or
+/// `network` when none applies, for use with `format-icons`:
+///
+/// | State | Meaning |
+/// |-------|---------|
+/// | `offline` | No network interface is up, or the connectivity check got no answer |
+/// | `captive-portal` | The connectivity check was redirected or answered by a portal |
+/// | `critical-missing` | A device passed with `--critical` was not found |
+/// | `gateway-unreachable` | The gateway stopped answering pings, or is missing from the neighbor table |
+/// | `degraded` | A discovery source failed during the scan |
+/// | `printer-attention` | A printer is low on ink or toner, jammed, out of paper or open |
+/// | `new-device` | A device was first seen within the last hour |
+/// | `unknown-devices` | A device could not be classified |
+///
+/// Errors use `error` for both.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WaybarOutput {
+    /// Plain text; escaped for Pango when rendered for Waybar
     pub text: String,
+    /// Pango markup
     pub tooltip: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alt: Option<String>,
@@ -17,206 +40,138 @@ pub struct WaybarOutput {
     pub percentage: Option<u8>,
 }
 
+/// Notable network states exposed as Waybar classes, ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BarState {
+    Offline,
+    CaptivePortal,
+    CriticalMissing,
+    GatewayUnreachable,
+    Degraded,
+    PrinterAttention,
+    NewDevice,
+    UnknownDevices,
+}
+
+impl BarState {
+    /// CSS class and `alt` value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Offline => "offline",
+            Self::CaptivePortal => "captive-portal",
+            Self::CriticalMissing => "critical-missing",
+            Self::GatewayUnreachable => "gateway-unreachable",
+            Self::Degraded => "degraded",
+            Self::PrinterAttention => "printer-attention",
+            Self::NewDevice => "new-device",
+            Self::UnknownDevices => "unknown-devices",
+        }
+    }
+
+    /// All states that apply to a snapshot, most severe first
+    pub fn detect(network_data: &NetworkData, options: &DisplayOptions) -> Vec<Self> {
+        let devices = &network_data.devices;
+        let connectivity = network_data.connectivity.as_ref().map(|check| check.status);
+        let checks = [
+            (
+                Self::Offline,
+                network_data.interfaces.is_empty() || connectivity == Some(ConnectivityStatus::Offline),
+            ),
+            (Self::CaptivePortal, connectivity == Some(ConnectivityStatus::Captive)),
+            (
+                Self::CriticalMissing,
+                options.critical_devices.iter().any(|query| network_data.find_device_exact(query).is_none()),
+            ),
+            (
+                Self::GatewayUnreachable,
+                network_data.gateway.is_some()
+                    && (network_data.gateway_device().is_none()
+                        || network_data.gateway_health.as_ref().is_some_and(|health| health.was_probed() && !health.is_responding())),
+            ),
+            (
+                Self::Degraded,
+                network_data.scan.as_ref().is_some_and(|scan| scan.failed_sources().next().is_some()),
+            ),
+            (
+                Self::PrinterAttention,
+                devices.iter().any(|d| d.printer.as_ref().is_some_and(|printer| printer.needs_attention())),
+            ),
+            (Self::NewDevice, devices.iter().any(|d| d.is_new)),
+            (
+                Self::UnknownDevices,
+                devices.iter().any(|d| d.identity.device_type == DeviceType::Unknown),
+            ),
+        ];
+
+        checks.into_iter().filter(|(_, applies)| *applies).map(|(state, _)| state).collect()
+    }
+}
+
 /// Formats network data as Waybar JSON
-pub struct WaybarFormatter;
+pub struct WaybarFormatter {
+    options: DisplayOptions,
+    tree: TreeLayout<PangoStyle>,
+}
 
 impl WaybarFormatter {
     /// Creates a new WaybarFormatter instance
     pub fn new() -> Self {
-        Self
+        Self::with_options(&DisplayOptions::default())
+    }
+
+    /// Creates a WaybarFormatter using custom templates
+    pub fn with_options(options: &DisplayOptions) -> Self {
+        Self {
+            options: options.clone(),
+            tree: TreeLayout::with_options(PangoStyle, options),
+        }
     }
 
     /// Formats network data for Waybar display
     pub fn format(&self, network_data: &NetworkData) -> Result<WaybarOutput> {
         let device_count = network_data.devices.len();
 
-        // Main text: device count
-        let text = if device_count == 0 {
-            "🖧 No devices".to_string()
-        } else if device_count == 1 {
-            "🖧 1 device".to_string()
-        } else {
-            format!("🖧 {} devices", device_count)
-        };
+        // Main text from the user template, device count by default
+        let text = self.options.bar_text(network_data);
 
         // Build tooltip with tree structure
         let tooltip = self.build_tooltip(network_data);
 
         // CSS classes based on state
-        let classes = if device_count > 0 {
-            vec!["network".to_string(), "active".to_string()]
-        } else {
-            vec!["network".to_string()]
-        };
+        let states = BarState::detect(network_data, &self.options);
+        let mut classes = vec!["network".to_string()];
+        if device_count > 0 {
+            classes.push("active".to_string());
+        }
+        classes.extend(states.iter().map(|state| state.as_str().to_string()));
+
+        let alt = states.first().map_or("network", |state| state.as_str());
+
+        // Signal quality of the first connected wireless interface
+        let percentage = network_data
+            .interfaces
+            .iter()
+            .find_map(|interface| interface.wifi.as_ref().and_then(|wifi| wifi.signal_quality()));
 
         Ok(WaybarOutput {
             text,
             tooltip,
-            alt: Some("network".to_string()),
+            alt: Some(alt.to_string()),
             class: Some(classes),
-            percentage: None,
+            percentage,
         })
     }
 
     /// Builds the tooltip with tree structure
     fn build_tooltip(&self, network_data: &NetworkData) -> String {
-        if network_data.interfaces.is_empty() {
-            return "No network interfaces found".to_string();
-        }
-
-        let devices_by_interface = network_data.devices_by_interface();
-        let mut lines = Vec::new();
-
-        for interface in &network_data.interfaces {
-            lines.push(self.format_interface_header(interface));
-
-            if let Some(devices) = devices_by_interface.get(&interface.name) {
-                let sorted_devices = self.sort_devices(devices);
-                lines.extend(self.format_devices(&sorted_devices, network_data));
-            } else {
-                lines.push("  └─ No devices".to_string());
-            }
-
-            lines.push(String::new()); // Empty line between interfaces
-        }
-
-        lines.join("\n").trim_end().to_string()
-    }
-
-    /// Format interface header line
-    fn format_interface_header(&self, interface: &crate::domain::NetworkInterface) -> String {
-        if let Some(mac) = &interface.mac {
-            format!("{}: {} ({})", interface.name, interface.ip, mac)
-        } else {
-            format!("{}: {}", interface.name, interface.ip)
-        }
-    }
-
-    /// Sort devices by IP address
-    fn sort_devices<'a>(&self, devices: &[&'a crate::domain::NetworkDevice])
-        -> Vec<&'a crate::domain::NetworkDevice> {
-        let mut sorted = devices.to_vec();
-        sorted.sort_by_key(|d| d.ip);
-        sorted
-    }
-
-    /// Format all devices for an interface
-    fn format_devices(&self, devices: &[&crate::domain::NetworkDevice], network_data: &NetworkData)
-        -> Vec<String> {
-        let device_count = devices.len();
-        devices.iter().enumerate().flat_map(|(i, device)| {
-            let is_last = i == device_count - 1;
-            self.format_device_entry(device, is_last, network_data)
-        }).collect()
-    }
-
-    /// Format a single device entry with its services and gateway info
-    fn format_device_entry(&self, device: &crate::domain::NetworkDevice, is_last: bool,
-        network_data: &NetworkData) -> Vec<String> {
-        let mut lines = Vec::new();
-        let prefix = if is_last { "  └─ " } else { "  ├─ " };
-
-        // Main device line
-        let display_name = device.identity.format();
-        let colored_name = device.activity_status().colorize(&display_name);
-        lines.push(format!("{}{} ({})", prefix, colored_name, device.ip));
-
-        // Services
-        if let Some(services_line) = self.format_services(device, is_last) {
-            lines.push(services_line);
-        }
-
-        // Gateway/DNS info
-        lines.extend(self.format_gateway_info(device, is_last, network_data));
-
-        lines
-    }
-
-    /// Format services list for a device
-    fn format_services(&self, device: &crate::domain::NetworkDevice, is_last: bool) -> Option<String> {
-        if device.services.is_empty() {
-            return None;
-        }
-
-        let service_prefix = if is_last { "      " } else { "  │   " };
-        let mut unique_services: Vec<String> = device.services
-            .iter()
-            .map(|s| s.friendly_type().to_string())
-            .collect();
-        unique_services.sort();
-        unique_services.dedup();
-
-        if unique_services.is_empty() {
-            None
-        } else {
-            Some(format!("{}  Services: {}", service_prefix, unique_services.join(", ")))
-        }
-    }
-
-    /// Format gateway and DNS information for a device
-    fn format_gateway_info(&self, device: &crate::domain::NetworkDevice, is_last: bool,
-        network_data: &NetworkData) -> Vec<String> {
-        use std::net::IpAddr;
-
-        let Some(gateway) = network_data.gateway else { return Vec::new() };
-        if device.ip != gateway.0 {
-            return Vec::new();
-        }
-
-        let mut lines = Vec::new();
-        let info_prefix = if is_last { "      " } else { "  │   " };
-
-        // Gateway label
-        let dns_matches_gateway = network_data.dns_servers.iter().any(|dns| dns == &gateway.0);
-        if dns_matches_gateway {
-            lines.push(format!("{}  Gateway (also DNS)", info_prefix));
-        } else {
-            lines.push(format!("{}  Gateway", info_prefix));
-        }
-
-        // Additional DNS servers
-        let other_dns: Vec<&IpAddr> = network_data.dns_servers
-            .iter()
-            .filter(|dns| *dns != &gateway.0)
-            .collect();
-
-        if !other_dns.is_empty() {
-            let dns_list: Vec<String> = other_dns
-                .iter()
-                .map(|dns| self.format_dns_entry(dns))
-                .collect();
-            lines.push(format!("{}  DNS: {}", info_prefix, dns_list.join(", ")));
-        }
-
-        lines
-    }
-
-    /// Format a single DNS entry with local/external label
-    fn format_dns_entry(&self, dns: &std::net::IpAddr) -> String {
-        use std::net::IpAddr;
-
-        let is_local = match dns {
-            IpAddr::V4(ipv4) => {
-                let octets = ipv4.octets();
-                octets[0] == 192 && octets[1] == 168
-                    || octets[0] == 10
-                    || (octets[0] == 172 && (16..=31).contains(&octets[1]))
-            }
-            IpAddr::V6(_) => false,
-        };
-
-        if is_local {
-            format!("{} (local)", dns)
-        } else {
-            format!("{} (external)", dns)
-        }
+        self.tree.build(network_data).join("\n")
     }
 
     /// Creates error output for Waybar
-    pub fn create_error_output(error: anyhow::Error) -> WaybarOutput {
+    pub fn error_output(&self, error: anyhow::Error) -> WaybarOutput {
         WaybarOutput {
-            text: "🖧 -- Network unavailable".to_string(),
-            tooltip: format!("Unable to fetch network data\n\nError: {}", error),
+            text: self.options.error_text(),
+            tooltip: error_tooltip(&error).to_pango(),
             alt: Some("error".to_string()),
             class: Some(vec!["error".to_string()]),
             percentage: None,
@@ -224,6 +179,25 @@ impl WaybarFormatter {
     }
 }
 
+/// Tooltip explaining a collection error
+pub fn error_tooltip(error: &anyhow::Error) -> Markup {
+    Markup::new()
+        .text("Unable to fetch network data\n\n")
+        .colored(Some(Color::Red), format!("Error: {}", error))
+}
+
+impl OutputFormatter for WaybarFormatter {
+    fn render(&self, network_data: &NetworkData) -> Result<String> {
+        let mut output = self.format(network_data)?;
+        output.text = escape_pango(&output.text);
+        Ok(serde_json::to_string(&output)?)
+    }
+
+    fn render_error(&self, error: anyhow::Error) -> Result<String> {
+        Ok(serde_json::to_string(&self.error_output(error))?)
+    }
+}
+
 impl Default for WaybarFormatter {
     fn default() -> Self {
         Self::new()
@@ -244,7 +218,7 @@ mod tests {
     #[test]
     fn test_error_output() {
         let error = anyhow::anyhow!("Test error");
-        let output = WaybarFormatter::create_error_output(error);
+        let output = WaybarFormatter::new().error_output(error);
 
         assert!(output.text.contains("unavailable"));
         assert!(output.tooltip.contains("Test error"));
@@ -288,4 +262,176 @@ mod tests {
         assert!(output.tooltip.contains("192.168.1.1"));
         assert!(output.tooltip.contains("Gateway"));
     }
+
+    #[test]
+    fn test_offline_state() {
+        let output = WaybarFormatter::new().format(&NetworkData::new(vec![], vec![], None, vec![])).unwrap();
+
+        assert_eq!(output.alt, Some("offline".to_string()));
+        assert_eq!(output.class, Some(vec!["network".to_string(), "offline".to_string()]));
+    }
+
+    #[test]
+    fn test_states_ordered_by_severity() {
+        let interface_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+        let interface = NetworkInterface::new(crate::domain::InterfaceName::new("eth0".to_string()), interface_ip, None);
+        let mut device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)),
+            MacAddress::new("11:22:33:44:55:66".to_string()).unwrap(),
+            crate::domain::InterfaceName::new("eth0".to_string()),
+        );
+        device.is_new = true;
+        let gateway = Gateway::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let data = NetworkData::new(vec![interface], vec![device], Some(gateway), vec![]);
+
+        let options = DisplayOptions {
+            critical_devices: vec!["192.168.1.50".to_string(), "nas".to_string()],
+            ..DisplayOptions::default()
+        };
+        let output = WaybarFormatter::with_options(&options).format(&data).unwrap();
+
+        assert_eq!(output.alt, Some("critical-missing".to_string()));
+        assert_eq!(output.class, Some(vec![
+            "network".to_string(),
+            "active".to_string(),
+            "critical-missing".to_string(),
+            "gateway-unreachable".to_string(),
+            "new-device".to_string(),
+            "unknown-devices".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_critical_device_needs_exact_match() {
+        let interface = NetworkInterface::new(crate::domain::InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), None);
+        let mut device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)),
+            MacAddress::new("11:22:33:44:55:66".to_string()).unwrap(),
+            crate::domain::InterfaceName::new("eth0".to_string()),
+        );
+        device.hostname = crate::domain::Hostname::resolved("nas-backup.lan".to_string());
+        let data = NetworkData::new(vec![interface], vec![device], None, vec![]);
+
+        let critical = |query: &str| {
+            let options = DisplayOptions { critical_devices: vec![query.to_string()], ..DisplayOptions::default() };
+            BarState::detect(&data, &options).contains(&BarState::CriticalMissing)
+        };
+        assert!(critical("nas"));
+        assert!(!critical("NAS-Backup.lan"));
+        assert!(!critical("11:22:33:44:55:66"));
+    }
+
+    #[test]
+    fn test_render_escapes_markup() {
+        let options = DisplayOptions {
+            icons: crate::domain::IconSet::default().with_override(DeviceType::Unknown, "<&>".to_string()),
+            text_template: crate::display::parse_text_template("{by_type}").unwrap(),
+            ..DisplayOptions::default()
+        };
+        let device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)),
+            MacAddress::new("11:22:33:44:55:66".to_string()).unwrap(),
+            crate::domain::InterfaceName::new("eth0".to_string()),
+        );
+        let data = NetworkData::new(vec![], vec![device], None, vec![]);
+        let json: serde_json::Value =
+            serde_json::from_str(&WaybarFormatter::with_options(&options).render(&data).unwrap()).unwrap();
+        assert_eq!(json["text"], "&lt;&amp;&gt; 1");
+
+        let error = WaybarFormatter::new().error_output(anyhow::anyhow!("bad <reply>"));
+        assert!(error.tooltip.contains("Error: bad &lt;reply&gt;"));
+    }
+
+    #[test]
+    fn test_gateway_not_responding() {
+        let gateway_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let interface = NetworkInterface::new(
+            crate::domain::InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            None,
+        );
+        let mut router = NetworkDevice::new(
+            gateway_ip,
+            MacAddress::new("00:11:22:33:44:55".to_string()).unwrap(),
+            crate::domain::InterfaceName::new("eth0".to_string()),
+        );
+        router.identity.device_type = DeviceType::Router;
+        let health = crate::domain::GatewayHealth { probes_sent: 3, replies: 0, latency: None, upstream: None };
+        let data = NetworkData::new(vec![interface], vec![router], Some(Gateway::new(gateway_ip)), vec![])
+            .with_gateway_health(Some(health));
+
+        let output = WaybarFormatter::new().format(&data).unwrap();
+        assert_eq!(output.alt, Some("gateway-unreachable".to_string()));
+    }
+
+    #[test]
+    fn test_captive_portal() {
+        let interface = NetworkInterface::new(
+            crate::domain::InterfaceName::new("wlan0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 10)),
+            None,
+        );
+        let check = crate::domain::ConnectivityCheck {
+            url: "http://connectivitycheck.gstatic.com/generate_204".to_string(),
+            status: ConnectivityStatus::Captive,
+            portal_url: Some("http://portal.example/login?a=1&b=2".to_string()),
+            detail: None,
+        };
+        let options = DisplayOptions {
+            text_template: crate::display::parse_text_template("{icon} {connectivity}").unwrap(),
+            ..DisplayOptions::default()
+        };
+        let data = NetworkData::new(vec![interface], vec![], None, vec![]).with_connectivity(Some(check));
+
+        let output = WaybarFormatter::with_options(&options).format(&data).unwrap();
+        assert_eq!(output.percentage, None);
+        assert_eq!(output.text, "🖧 captive");
+        assert_eq!(output.alt, Some("captive-portal".to_string()));
+        assert!(output.tooltip.contains("captive portal</span> (http://portal.example/login?a=1&amp;b=2)"));
+    }
+
+    #[test]
+    fn test_wifi_signal_percentage() {
+        let wifi = crate::domain::WifiLink { signal_dbm: Some(-60), ..Default::default() };
+        let interface = NetworkInterface::new(
+            crate::domain::InterfaceName::new("wlan0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 10)),
+            None,
+        )
+        .with_wifi(Some(wifi));
+        let data = NetworkData::new(vec![interface], vec![], None, vec![]);
+
+        assert_eq!(WaybarFormatter::new().format(&data).unwrap().percentage, Some(80));
+    }
+
+    #[test]
+    fn test_printer_attention() {
+        let interface = NetworkInterface::new(
+            crate::domain::InterfaceName::new("eth0".to_string()),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            None,
+        );
+        let mut device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 30)),
+            MacAddress::new("11:22:33:44:55:30".to_string()).unwrap(),
+            crate::domain::InterfaceName::new("eth0".to_string()),
+        );
+        device.identity.device_type = DeviceType::Printer;
+        let mut printer = crate::domain::PrinterStatus {
+            model: None,
+            state: Some(crate::domain::PrinterState::Idle),
+            state_reasons: vec![],
+            markers: vec![crate::domain::MarkerLevel { name: "Black".to_string(), level: Some(40) }],
+        };
+        device.printer = Some(printer.clone());
+        let mut data = NetworkData::new(vec![interface], vec![device], None, vec![]);
+        assert_eq!(WaybarFormatter::new().format(&data).unwrap().alt, Some("network".to_string()));
+
+        printer.state_reasons = vec!["media-jam-error".to_string()];
+        data.devices[0].printer = Some(printer);
+        let output = WaybarFormatter::new().format(&data).unwrap();
+        assert_eq!(output.alt, Some("printer-attention".to_string()));
+        assert!(output.class.unwrap().contains(&"printer-attention".to_string()));
+    }
 }
@@ -0,0 +1,186 @@
+//! Comparison of two network snapshots, matching devices by MAC address.
+
+use super::types::{DeviceType, MacAddress, NetworkDevice, NetworkSnapshot};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+/// Short description of a device that appeared or disappeared
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceSummary {
+    pub mac: MacAddress,
+    pub ip: IpAddr,
+    pub device_type: DeviceType,
+    /// Display name without icon
+    pub name: String,
+}
+
+impl DeviceSummary {
+    fn from_device(device: &NetworkDevice) -> Self {
+        Self {
+            mac: device.mac.clone(),
+            ip: device.ip,
+            device_type: device.identity.device_type,
+            name: device.identity.name(),
+        }
+    }
+}
+
+/// A single field that differs between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// All field changes for a device present in both snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceChange {
+    pub mac: MacAddress,
+    pub device_type: DeviceType,
+    /// Display name without icon
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Differences between an old and a new snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<DeviceSummary>,
+    pub removed: Vec<DeviceSummary>,
+    pub changed: Vec<DeviceChange>,
+}
+
+impl SnapshotDiff {
+    /// Compares two snapshots; results are ordered by MAC address
+    pub fn between(old: &NetworkSnapshot, new: &NetworkSnapshot) -> Self {
+        let by_mac = |snapshot: &NetworkSnapshot| -> BTreeMap<String, NetworkDevice> {
+            snapshot.devices.iter().map(|d| (d.mac.to_string(), d.clone())).collect()
+        };
+        let old_devices = by_mac(old);
+        let new_devices = by_mac(new);
+
+        let added = new_devices
+            .iter()
+            .filter(|(mac, _)| !old_devices.contains_key(*mac))
+            .map(|(_, device)| DeviceSummary::from_device(device))
+            .collect();
+        let removed = old_devices
+            .iter()
+            .filter(|(mac, _)| !new_devices.contains_key(*mac))
+            .map(|(_, device)| DeviceSummary::from_device(device))
+            .collect();
+        let changed = old_devices
+            .iter()
+            .filter_map(|(mac, old_device)| {
+                let new_device = new_devices.get(mac)?;
+                let changes = compare_devices(old_device, new_device);
+                (!changes.is_empty()).then(|| DeviceChange {
+                    mac: new_device.mac.clone(),
+                    device_type: new_device.identity.device_type,
+                    name: new_device.identity.name(),
+                    changes,
+                })
+            })
+            .collect();
+
+        Self { added, removed, changed }
+    }
+
+    /// True if the snapshots contain the same devices with the same fields
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Field-by-field comparison of the same device in two snapshots
+fn compare_devices(old: &NetworkDevice, new: &NetworkDevice) -> Vec<FieldChange> {
+    let optional = |value: Option<&str>| value.unwrap_or("-").to_string();
+    let services = |device: &NetworkDevice| {
+        let mut types: Vec<&str> = device.services.iter().map(|s| s.service_type.as_str()).collect();
+        types.sort();
+        types.dedup();
+        if types.is_empty() { "-".to_string() } else { types.join(", ") }
+    };
+
+    let fields = [
+        ("ip", old.ip.to_string(), new.ip.to_string()),
+        ("interface", old.interface_name.to_string(), new.interface_name.to_string()),
+        ("hostname", old.hostname.to_string(), new.hostname.to_string()),
+        ("services", services(old), services(new)),
+        ("device_type", old.identity.device_type.to_string(), new.identity.device_type.to_string()),
+        (
+            "manufacturer",
+            optional(old.identity.manufacturer.as_ref().map(|m| m.as_str())),
+            optional(new.identity.manufacturer.as_ref().map(|m| m.as_str())),
+        ),
+        (
+            "model",
+            optional(old.identity.model.as_ref().map(|m| m.as_str())),
+            optional(new.identity.model.as_ref().map(|m| m.as_str())),
+        ),
+        (
+            "friendly_name",
+            optional(old.identity.friendly_name.as_ref().map(|n| n.as_str())),
+            optional(new.identity.friendly_name.as_ref().map(|n| n.as_str())),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| FieldChange { field: field.to_string(), old, new })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Hostname, InterfaceName};
+    use std::net::Ipv4Addr;
+
+    fn device(mac: &str, last_octet: u8, hostname: &str) -> NetworkDevice {
+        let mut device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, last_octet)),
+            MacAddress::new(mac.to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        device.hostname = Hostname::resolved(hostname.to_string());
+        device.build_identity();
+        device
+    }
+
+    #[test]
+    fn test_added_removed_changed() {
+        let old = NetworkSnapshot::new(vec![], vec![
+            device("00:00:00:00:00:01", 10, "desktop"),
+            device("00:00:00:00:00:02", 20, "old-phone"),
+            device("00:00:00:00:00:03", 30, "nas"),
+        ], None, vec![]);
+        let new = NetworkSnapshot::new(vec![], vec![
+            device("00:00:00:00:00:01", 10, "desktop"),
+            device("00:00:00:00:00:03", 31, "nas"),
+            device("00:00:00:00:00:04", 40, "access-point"),
+        ], None, vec![]);
+
+        let diff = SnapshotDiff::between(&old, &new);
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].mac.to_string(), "00:00:00:00:00:04");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].mac.to_string(), "00:00:00:00:00:02");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].changes, vec![FieldChange {
+            field: "ip".to_string(),
+            old: "192.168.1.30".to_string(),
+            new: "192.168.1.31".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_identical_snapshots() {
+        let snapshot = NetworkSnapshot::new(vec![], vec![device("00:00:00:00:00:01", 10, "desktop")], None, vec![]);
+        assert!(SnapshotDiff::between(&snapshot, &snapshot).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Icon sets for device types, interfaces and the bar.
+
+use super::types::{DeviceType, InterfaceName};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Built-in icon themes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconTheme {
+    /// Color emoji (requires an emoji font)
+    #[default]
+    Emoji,
+    /// Nerd Font Material Design glyphs
+    NerdFont,
+    /// Plain ASCII labels
+    Ascii,
+}
+
+impl IconTheme {
+    /// Stable identifiers accepted on the command line
+    pub const IDS: [&'static str; 3] = ["emoji", "nerd-font", "ascii"];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Emoji => "emoji",
+            Self::NerdFont => "nerd-font",
+            Self::Ascii => "ascii",
+        }
+    }
+}
+
+impl FromStr for IconTheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [Self::Emoji, Self::NerdFont, Self::Ascii]
+            .into_iter()
+            .find(|theme| theme.id() == s)
+            .ok_or_else(|| anyhow!("Unknown icon set '{}' (expected one of: {})", s, Self::IDS.join(", ")))
+    }
+}
+
+/// Icons for a theme, with optional per-device-type overrides
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IconSet {
+    theme: IconTheme,
+    overrides: HashMap<DeviceType, String>,
+}
+
+impl IconSet {
+    /// Creates an IconSet for a built-in theme
+    pub fn new(theme: IconTheme) -> Self {
+        Self {
+            theme,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Replaces the icon for one device type
+    pub fn with_override(mut self, device_type: DeviceType, icon: String) -> Self {
+        self.overrides.insert(device_type, icon);
+        self
+    }
+
+    /// Icon for a device type
+    pub fn device(&self, device_type: DeviceType) -> &str {
+        if let Some(icon) = self.overrides.get(&device_type) {
+            return icon;
+        }
+        match self.theme {
+            IconTheme::Emoji => device_type.as_emoji(),
+            IconTheme::NerdFont => match device_type {
+                DeviceType::Television => "\u{f0502}",
+                DeviceType::Printer => "\u{f042a}",
+                DeviceType::Router => "\u{f0469}",
+                DeviceType::Computer => "\u{f0322}",
+                DeviceType::NAS => "\u{f08f3}",
+                DeviceType::MobileDevice => "\u{f011c}",
+                DeviceType::Tablet => "\u{f04f6}",
+                DeviceType::Speaker => "\u{f04c3}",
+                DeviceType::StreamingDevice => "\u{f0118}",
+                DeviceType::SmartHome => "\u{f07d0}",
+                DeviceType::Unknown => "\u{f0fb0}",
+            },
+            IconTheme::Ascii => match device_type {
+                DeviceType::Television => "[TV]",
+                DeviceType::Printer => "[PRN]",
+                DeviceType::Router => "[RTR]",
+                DeviceType::Computer => "[PC]",
+                DeviceType::NAS => "[NAS]",
+                DeviceType::MobileDevice => "[PHN]",
+                DeviceType::Tablet => "[TAB]",
+                DeviceType::Speaker => "[SPK]",
+                DeviceType::StreamingDevice => "[STR]",
+                DeviceType::SmartHome => "[HOM]",
+                DeviceType::Unknown => "[?]",
+            },
+        }
+    }
+
+    /// Icon shown in the bar text
+    pub fn bar(&self) -> &'static str {
+        match self.theme {
+            IconTheme::Emoji => "🖧",
+            IconTheme::NerdFont => "\u{f0317}",
+            IconTheme::Ascii => "LAN",
+        }
+    }
+
+    /// Icon for an interface header, wired or wireless
+    pub fn interface(&self, name: &InterfaceName) -> &'static str {
+        match (self.theme, name.is_wireless()) {
+            (IconTheme::Emoji, false) => "🔌",
+            (IconTheme::Emoji, true) => "📶",
+            (IconTheme::NerdFont, false) => "\u{f0200}",
+            (IconTheme::NerdFont, true) => "\u{f05a9}",
+            (IconTheme::Ascii, false) => "[eth]",
+            (IconTheme::Ascii, true) => "[wifi]",
+        }
+    }
+}
+
+/// Parses a `TYPE=ICON` override, e.g. `nas=🗃`
+pub fn parse_icon_override(value: &str) -> Result<(DeviceType, String)> {
+    let Some((id, icon)) = value.split_once('=') else {
+        bail!("Expected TYPE=ICON, got '{}'", value);
+    };
+    let device_type = DeviceType::from_id(id).ok_or_else(|| {
+        let ids: Vec<&str> = DeviceType::ALL.iter().map(|t| t.id()).collect();
+        anyhow!("Unknown device type '{}' (expected one of: {})", id, ids.join(", "))
+    })?;
+    Ok((device_type, icon.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_themes_and_overrides() {
+        let ascii = IconSet::new("ascii".parse().unwrap());
+        assert_eq!(ascii.device(DeviceType::Router), "[RTR]");
+        assert_eq!(ascii.bar(), "LAN");
+        assert_eq!(ascii.interface(&InterfaceName::new("wlp3s0".to_string())), "[wifi]");
+
+        let custom = IconSet::default().with_override(DeviceType::NAS, "🗃".to_string());
+        assert_eq!(custom.device(DeviceType::NAS), "🗃");
+        assert_eq!(custom.device(DeviceType::Router), "🌐");
+
+        assert!("wingdings".parse::<IconTheme>().is_err());
+    }
+
+    #[test]
+    fn test_parse_icon_override() {
+        assert_eq!(parse_icon_override("smart_home=H").unwrap(), (DeviceType::SmartHome, "H".to_string()));
+        assert!(parse_icon_override("toaster=T").is_err());
+        assert!(parse_icon_override("nas").is_err());
+    }
+}
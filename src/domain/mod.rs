@@ -1,7 +1,11 @@
 //! Domain value objects for network data with type-level safety and validation.
 
+pub mod diff;
+pub mod icons;
 pub mod types;
 
+pub use diff::*;
+pub use icons::*;
 pub use types::*;
 
 #[cfg(test)]
@@ -9,6 +13,5 @@ mod tests {
     #[test]
     fn test_placeholder() {
         // Placeholder test to ensure module compiles
-        assert!(true);
     }
 }
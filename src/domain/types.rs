@@ -5,6 +5,7 @@
 //! - Validation happens at construction time
 //! - Invalid states are unrepresentable
 
+use super::icons::IconSet;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -42,6 +43,21 @@ impl MacAddress {
 
         Ok(Self(normalized))
     }
+
+    /// Raw address bytes
+    pub fn octets(&self) -> [u8; 6] {
+        let mut octets = [0u8; 6];
+        for (octet, part) in octets.iter_mut().zip(self.0.split(':')) {
+            // Validated as hex in `new`
+            *octet = u8::from_str_radix(part, 16).unwrap_or(0);
+        }
+        octets
+    }
+
+    /// Lowercase hex digits without separators, e.g. "aabbccddeeff"
+    pub fn to_compact(&self) -> String {
+        self.0.replace(':', "").to_lowercase()
+    }
 }
 
 impl fmt::Display for MacAddress {
@@ -154,6 +170,15 @@ impl InterfaceName {
     pub fn new(value: String) -> Self {
         Self(value)
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Wireless interfaces follow the kernel's `wl` naming (wlan0, wlp3s0)
+    pub fn is_wireless(&self) -> bool {
+        self.0.starts_with("wl")
+    }
 }
 
 impl fmt::Display for InterfaceName {
@@ -232,6 +257,8 @@ pub enum ActivityStatus {
 }
 
 impl ActivityStatus {
+    pub const ALL: [ActivityStatus; 4] = [Self::Active, Self::Recent, Self::Idle, Self::Stale];
+
     /// Calculate activity status from last seen time
     pub fn from_last_seen(last_seen: SystemTime) -> Self {
         let elapsed = SystemTime::now()
@@ -248,22 +275,6 @@ impl ActivityStatus {
             Self::Stale
         }
     }
-
-    /// Get Pango markup for coloring text
-    pub fn pango_color(&self) -> (&'static str, &'static str) {
-        match self {
-            Self::Active => ("<span color='#00FF00'>", "</span>"),   // Green
-            Self::Recent => ("<span color='#FFFF00'>", "</span>"),   // Yellow
-            Self::Idle => ("", ""),                                   // White (default)
-            Self::Stale => ("<span color='#888888'>", "</span>"),    // Grey
-        }
-    }
-
-    /// Wrap text with color markup based on activity status
-    pub fn colorize(&self, text: &str) -> String {
-        let (start, end) = self.pango_color();
-        format!("{}{}{}", start, text, end)
-    }
 }
 
 impl Hostname {
@@ -287,7 +298,7 @@ impl fmt::Display for Hostname {
 }
 
 /// Device type classification
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DeviceType {
     Television,
     Printer,
@@ -303,6 +314,42 @@ pub enum DeviceType {
 }
 
 impl DeviceType {
+    pub const ALL: [DeviceType; 11] = [
+        Self::Television,
+        Self::Printer,
+        Self::Router,
+        Self::Computer,
+        Self::NAS,
+        Self::MobileDevice,
+        Self::Tablet,
+        Self::Speaker,
+        Self::StreamingDevice,
+        Self::SmartHome,
+        Self::Unknown,
+    ];
+
+    /// Stable lowercase identifier for configuration and export files
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Television => "television",
+            Self::Printer => "printer",
+            Self::Router => "router",
+            Self::Computer => "computer",
+            Self::NAS => "nas",
+            Self::MobileDevice => "mobile_device",
+            Self::Tablet => "tablet",
+            Self::Speaker => "speaker",
+            Self::StreamingDevice => "streaming_device",
+            Self::SmartHome => "smart_home",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Parses a stable identifier produced by `id`
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.id() == id)
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Television => "Television",
@@ -322,16 +369,16 @@ impl DeviceType {
     pub fn as_emoji(&self) -> &'static str {
         match self {
             Self::Television => "📺",
-            Self::Printer => "🖨 ",      // Extra space for alignment
+            Self::Printer => "🖨",
             Self::Router => "🌐",
             Self::Computer => "💻",
             Self::NAS => "🗄",
-            Self::MobileDevice => "📞",  // Telephone receiver for phones
-            Self::Tablet => "📋",        // Clipboard for tablets
+            Self::MobileDevice => "📞", // Telephone receiver for phones
+            Self::Tablet => "📋",       // Clipboard for tablets
             Self::Speaker => "🔊",
             Self::StreamingDevice => "📺",
             Self::SmartHome => "🏠",
-            Self::Unknown => "🖥 ",      // Extra space for alignment
+            Self::Unknown => "🖥",
         }
     }
 }
@@ -342,6 +389,98 @@ impl fmt::Display for DeviceType {
     }
 }
 
+/// What a service told about itself when connected to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BannerKind {
+    /// `<title>` of the HTTP root page
+    HttpTitle,
+    /// HTTP `Server` header
+    HttpServer,
+    /// SSH identification string, e.g. "OpenSSH_9.6p1 Debian-3"
+    SshVersion,
+}
+
+impl BannerKind {
+    pub const ALL: [BannerKind; 3] = [Self::HttpTitle, Self::HttpServer, Self::SshVersion];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HttpTitle => "title",
+            Self::HttpServer => "server",
+            Self::SshVersion => "ssh",
+        }
+    }
+}
+
+/// A banner grabbed from one of a device's services
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceBanner {
+    pub port: u16,
+    pub kind: BannerKind,
+    pub text: String,
+}
+
+impl fmt::Display for ServiceBanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "port {} {} \"{}\"", self.port, self.kind.as_str(), self.text)
+    }
+}
+
+/// Source of a piece of identity evidence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvidenceSource {
+    Upnp,
+    Mdns,
+    Ipp,
+    Snmp,
+    Banner,
+    Vendor,
+    Hostname,
+}
+
+impl EvidenceSource {
+    pub const ALL: [EvidenceSource; 7] = [
+        Self::Upnp, Self::Mdns, Self::Ipp, Self::Snmp, Self::Banner, Self::Vendor, Self::Hostname,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Upnp => "UPnP",
+            Self::Mdns => "mDNS",
+            Self::Ipp => "IPP",
+            Self::Snmp => "SNMP",
+            Self::Banner => "Banner",
+            Self::Vendor => "Vendor",
+            Self::Hostname => "Hostname",
+        }
+    }
+}
+
+impl fmt::Display for EvidenceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A single fact that contributed to a device's identity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub source: EvidenceSource,
+    pub detail: String,
+}
+
+impl Evidence {
+    pub fn new(source: EvidenceSource, detail: String) -> Self {
+        Self { source, detail }
+    }
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.detail)
+    }
+}
+
 /// Structured device identity with classification and naming
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DeviceIdentity {
@@ -353,6 +492,9 @@ pub struct DeviceIdentity {
     pub model: Option<ModelName>,
     /// User-friendly name or network hostname
     pub friendly_name: Option<FriendlyName>,
+    /// Which sources decided each of the fields above
+    #[serde(default)]
+    pub evidence: Vec<Evidence>,
 }
 
 impl DeviceIdentity {
@@ -362,24 +504,29 @@ impl DeviceIdentity {
             manufacturer: None,
             model: None,
             friendly_name: None,
+            evidence: Vec::new(),
         }
     }
 
-    /// Format device name with emoji and available information
-    /// Format: {Emoji} {Manufacturer} {Model} or {Emoji} {FriendlyName} or just {Emoji}
-    pub fn format(&self) -> String {
-        let emoji = self.device_type.as_emoji();
+    /// Format device name with the icon from the given icon set
+    /// Format: {Icon} {Manufacturer} {Model} or {Icon} {FriendlyName} or {Icon} {DeviceType}
+    pub fn format_with(&self, icons: &IconSet) -> String {
+        format!("{} {}", icons.device(self.device_type), self.name())
+    }
 
+    /// Device name without icon
+    /// Format: {Manufacturer} {Model} or {FriendlyName} or {DeviceType}
+    pub fn name(&self) -> String {
         match (&self.manufacturer, &self.model) {
-            (Some(mfr), Some(model)) => format!("{} {} {}", emoji, mfr.as_str(), model.as_str()),
-            (Some(mfr), None) => format!("{} {}", emoji, mfr.as_str()),
-            (None, Some(model)) => format!("{} {}", emoji, model.as_str()),
+            (Some(mfr), Some(model)) => format!("{} {}", mfr.as_str(), model.as_str()),
+            (Some(mfr), None) => mfr.as_str().to_string(),
+            (None, Some(model)) => model.as_str().to_string(),
             (None, None) => {
                 if let Some(name) = &self.friendly_name {
-                    format!("{} {}", emoji, name.as_str())
+                    name.as_str().to_string()
                 } else {
                     // Add device type name as fallback
-                    format!("{} {}", emoji, self.device_type.as_str())
+                    self.device_type.as_str().to_string()
                 }
             }
         }
@@ -399,6 +546,9 @@ pub struct UpnpInfo {
     pub manufacturer: Option<ManufacturerName>,
     pub model_name: Option<ModelName>,
     pub device_type: Option<DeviceTypeName>,
+    /// URL of the device description, from the SSDP LOCATION header
+    #[serde(default)]
+    pub location: Option<String>,
 }
 
 impl UpnpInfo {
@@ -408,6 +558,7 @@ impl UpnpInfo {
             manufacturer: None,
             model_name: None,
             device_type: None,
+            location: None,
         }
     }
 }
@@ -429,6 +580,27 @@ pub struct NetworkDevice {
     pub upnp_info: Option<UpnpInfo>,
     pub last_seen: SystemTime,
     pub identity: DeviceIdentity,
+    /// Average ping round-trip time, if the device answered
+    #[serde(default)]
+    pub latency: Option<Duration>,
+    /// First seen recently according to the device store
+    #[serde(default)]
+    pub is_new: bool,
+    /// Traffic through this host's connection tracking, when enabled
+    #[serde(default)]
+    pub traffic: Option<DeviceTraffic>,
+    /// HTTP titles, Server headers and SSH versions, when banner grabbing is enabled
+    #[serde(default)]
+    pub banners: Vec<ServiceBanner>,
+    /// State and supply levels of printers that answer IPP
+    #[serde(default)]
+    pub printer: Option<PrinterStatus>,
+    /// System group of devices that answer SNMP
+    #[serde(default)]
+    pub snmp: Option<SnmpInfo>,
+    /// WAN status and port mappings of UPnP Internet gateways
+    #[serde(default)]
+    pub igd: Option<IgdStatus>,
 }
 
 impl NetworkDevice {
@@ -442,6 +614,13 @@ impl NetworkDevice {
             upnp_info: None,
             last_seen: SystemTime::now(),
             identity: DeviceIdentity::new(),
+            latency: None,
+            is_new: false,
+            traffic: None,
+            banners: Vec::new(),
+            printer: None,
+            snmp: None,
+            igd: None,
         }
     }
 
@@ -457,22 +636,53 @@ impl NetworkDevice {
 
     /// Build DeviceIdentity from collected information
     /// Uses priority-based inference for device type, manufacturer, model, and friendly name
+    /// Records the source of every inferred field as evidence
     pub fn build_identity(&mut self) {
+        let device_type = self.infer_device_type();
+        let manufacturer = self.extract_manufacturer();
+        let model = self.extract_model();
+        let friendly_name = self.extract_friendly_name();
+
+        let mut evidence = Vec::new();
+        if let Some((device_type, source)) = device_type {
+            evidence.push(Evidence::new(source, format!("device type {}", device_type)));
+        }
+        if let Some((mfr, source)) = &manufacturer {
+            evidence.push(Evidence::new(*source, format!("manufacturer {}", mfr.as_str())));
+        }
+        if let Some((model, source)) = &model {
+            evidence.push(Evidence::new(*source, format!("model {}", model.as_str())));
+        }
+        if let Some((name, source)) = &friendly_name {
+            evidence.push(Evidence::new(*source, format!("name {}", name.as_str())));
+        }
+        evidence.extend(self.banners.iter().map(|banner| Evidence::new(EvidenceSource::Banner, banner.to_string())));
+
         self.identity = DeviceIdentity {
-            device_type: self.infer_device_type(),
-            manufacturer: self.extract_manufacturer(),
-            model: self.extract_model(),
-            friendly_name: self.extract_friendly_name(),
+            device_type: device_type.map(|(t, _)| t).unwrap_or(DeviceType::Unknown),
+            manufacturer: manufacturer.map(|(m, _)| m),
+            model: model.map(|(m, _)| m),
+            friendly_name: friendly_name.map(|(n, _)| n),
+            evidence,
         };
     }
 
     /// Infer device type from available information
-    fn infer_device_type(&self) -> DeviceType {
-        self.infer_from_upnp()
-            .or_else(|| self.infer_from_services())
-            .or_else(|| self.infer_from_manufacturer_and_model())
-            .or_else(|| self.infer_from_hostname())
-            .unwrap_or(DeviceType::Unknown)
+    fn infer_device_type(&self) -> Option<(DeviceType, EvidenceSource)> {
+        self.infer_from_upnp().map(|t| (t, EvidenceSource::Upnp))
+            .or_else(|| self.infer_from_services().map(|t| (t, EvidenceSource::Mdns)))
+            .or_else(|| self.infer_from_banners().map(|t| (t, EvidenceSource::Banner)))
+            .or_else(|| self.infer_from_snmp().map(|t| (t, EvidenceSource::Snmp)))
+            .or_else(|| self.infer_from_manufacturer_and_model().map(|t| (t, EvidenceSource::Vendor)))
+            .or_else(|| self.infer_from_hostname().map(|t| (t, EvidenceSource::Hostname)))
+    }
+
+    /// True when the UPnP device type is an InternetGatewayDevice
+    pub fn is_internet_gateway(&self) -> bool {
+        self.upnp_info
+            .as_ref()
+            .and_then(|upnp| upnp.device_type.as_ref())
+            .is_some_and(|device_type| device_type.as_str().to_lowercase().contains("internetgatewaydevice"))
     }
 
     /// Infer device type from UPnP device type URN
@@ -488,7 +698,7 @@ impl NetworkDevice {
             }
             return Some(DeviceType::Speaker);
         }
-        if device_type_lower.contains("internetgatewaydevice") {
+        if self.is_internet_gateway() {
             return Some(DeviceType::Router);
         }
         if device_type_lower.contains("mediaserver") {
@@ -497,9 +707,9 @@ impl NetworkDevice {
         None
     }
 
-    /// Infer device type from mDNS service types
+    /// Infer device type from mDNS service types and probed ports
     fn infer_from_services(&self) -> Option<DeviceType> {
-        if self.has_service("_printer") || self.has_service("_ipp") {
+        if self.has_service("_printer") || self.has_service("_ipp") || self.has_service("_pdl-datastream") {
             return Some(DeviceType::Printer);
         }
         if self.has_service("_googlecast")
@@ -515,9 +725,33 @@ impl NetworkDevice {
         if self.has_service("_homekit") {
             return Some(DeviceType::SmartHome);
         }
+        if self.has_service("_rdp") {
+            return Some(DeviceType::Computer);
+        }
         None
     }
 
+    /// Infer device type from HTTP titles, Server headers and SSH versions
+    fn infer_from_banners(&self) -> Option<DeviceType> {
+        let rules: [(&[&str], DeviceType); 5] = [
+            (&["diskstation", "synology", "qnap", "truenas", "unraid", "openmediavault"], DeviceType::NAS),
+            (&["octoprint", "cups", "printer", "laserjet"], DeviceType::Printer),
+            (&["openwrt", "luci", "dd-wrt", "pfsense", "opnsense", "routeros", "fritz!box", "dropbear"], DeviceType::Router),
+            (&["home assistant", "homebridge", "philips hue", "shelly", "tasmota"], DeviceType::SmartHome),
+            (&["pi-hole", "debian", "ubuntu", "raspbian", "fedora", "freebsd", "openssh_for_windows"], DeviceType::Computer),
+        ];
+        let texts: Vec<String> = self.banners.iter().map(|banner| banner.text.to_lowercase()).collect();
+        rules
+            .iter()
+            .find(|(keywords, _)| keywords.iter().any(|keyword| texts.iter().any(|text| text.contains(keyword))))
+            .map(|(_, device_type)| *device_type)
+    }
+
+    /// Infer device type from the SNMP enterprise number
+    fn infer_from_snmp(&self) -> Option<DeviceType> {
+        self.snmp.as_ref()?.enterprise()?.device_type
+    }
+
     /// Infer device type from manufacturer and model with service heuristics
     fn infer_from_manufacturer_and_model(&self) -> Option<DeviceType> {
         let manufacturer_from_upnp = self.upnp_info.as_ref()
@@ -606,16 +840,21 @@ impl NetworkDevice {
     }
 
     /// Extract manufacturer from available sources
-    fn extract_manufacturer(&self) -> Option<ManufacturerName> {
+    fn extract_manufacturer(&self) -> Option<(ManufacturerName, EvidenceSource)> {
         // Priority 1: UPnP manufacturer field
         if let Some(upnp) = &self.upnp_info
             && let Some(mfr) = &upnp.manufacturer
             && !mfr.as_str().is_empty()
         {
-            return Some(mfr.clone());
+            return Some((mfr.clone(), EvidenceSource::Upnp));
+        }
+
+        // Priority 2: SNMP sysObjectID enterprise number
+        if let Some(enterprise) = self.snmp.as_ref().and_then(SnmpInfo::enterprise) {
+            return Some((ManufacturerName::new(enterprise.vendor.to_string()), EvidenceSource::Snmp));
         }
 
-        // Priority 2: Extract from friendly name
+        // Priority 3: Extract from friendly name
         if let Some(upnp) = &self.upnp_info
             && let Some(friendly) = &upnp.friendly_name
         {
@@ -628,13 +867,13 @@ impl NetworkDevice {
                                           "Canon", "Epson", "Apple", "Google", "Amazon"];
                 for mfr in &known_manufacturers {
                     if first.eq_ignore_ascii_case(mfr) {
-                        return Some(ManufacturerName::new(mfr.to_string()));
+                        return Some((ManufacturerName::new(mfr.to_string()), EvidenceSource::Upnp));
                     }
                 }
             }
         }
 
-        // Priority 3: Extract from hostname
+        // Priority 4: Extract from hostname
         if let Hostname::Resolved(hostname) = &self.hostname {
             let hostname_lower = hostname.to_lowercase();
             let known_manufacturers = ["samsung", "lg", "sony", "brother", "hp",
@@ -645,7 +884,7 @@ impl NetworkDevice {
                     let capitalized = format!("{}{}",
                         &mfr[0..1].to_uppercase(),
                         &mfr[1..]);
-                    return Some(ManufacturerName::new(capitalized));
+                    return Some((ManufacturerName::new(capitalized), EvidenceSource::Hostname));
                 }
             }
         }
@@ -654,33 +893,52 @@ impl NetworkDevice {
     }
 
     /// Extract model from available sources
-    fn extract_model(&self) -> Option<ModelName> {
+    fn extract_model(&self) -> Option<(ModelName, EvidenceSource)> {
         // Priority 1: UPnP model name
         if let Some(upnp) = &self.upnp_info
             && let Some(model) = &upnp.model_name
             && !model.as_str().is_empty()
         {
-            return Some(model.clone());
+            return Some((model.clone(), EvidenceSource::Upnp));
+        }
+
+        // Priority 2: IPP printer-make-and-model
+        if let Some(model) = self.printer.as_ref().and_then(|printer| printer.model.as_ref())
+            && !model.is_empty()
+        {
+            return Some((ModelName::new(model.clone()), EvidenceSource::Ipp));
+        }
+
+        // Priority 3: first part of the SNMP sysDescr
+        if let Some(model) = self.snmp.as_ref().and_then(SnmpInfo::model) {
+            return Some((ModelName::new(model), EvidenceSource::Snmp));
         }
 
         None
     }
 
     /// Extract friendly name from available sources
-    fn extract_friendly_name(&self) -> Option<FriendlyName> {
+    fn extract_friendly_name(&self) -> Option<(FriendlyName, EvidenceSource)> {
         // Priority 1: UPnP friendly name (but only if it's descriptive)
         if let Some(upnp) = &self.upnp_info
             && let Some(friendly) = &upnp.friendly_name
             && !friendly.as_str().is_empty() && !friendly.as_str().contains("uuid")
         {
-            return Some(friendly.clone());
+            return Some((friendly.clone(), EvidenceSource::Upnp));
         }
 
-        // Priority 2: DNS hostname (if available and descriptive)
+        // Priority 2: SNMP sysName, set by whoever administers the device
+        if let Some(sys_name) = self.snmp.as_ref().and_then(|snmp| snmp.sys_name.as_ref())
+            && !sys_name.is_empty()
+        {
+            return Some((FriendlyName::new(sys_name.clone()), EvidenceSource::Snmp));
+        }
+
+        // Priority 3: DNS hostname (if available and descriptive)
         if let Hostname::Resolved(hostname) = &self.hostname
             && !hostname.is_empty() && !hostname.starts_with('_')
         {
-            return Some(FriendlyName::new(hostname.clone()));
+            return Some((FriendlyName::new(hostname.clone()), EvidenceSource::Hostname));
         }
 
         None
@@ -694,17 +952,251 @@ impl NetworkDevice {
     }
 }
 
+/// Link details of a wireless interface; fields the driver does not report are None
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WifiLink {
+    pub ssid: Option<String>,
+    pub bssid: Option<MacAddress>,
+    pub frequency_mhz: Option<u32>,
+    pub signal_dbm: Option<i32>,
+    pub tx_bitrate_mbps: Option<f64>,
+}
+
+impl WifiLink {
+    /// Channel number for a 2.4, 5 or 6 GHz frequency
+    pub fn channel(&self) -> Option<u32> {
+        match self.frequency_mhz? {
+            2484 => Some(14),
+            freq @ 2412..=2472 => Some((freq - 2407) / 5),
+            freq @ 5955..=7115 => Some((freq - 5950) / 5),
+            freq @ 5000..=5900 => Some((freq - 5000) / 5),
+            _ => None,
+        }
+    }
+
+    /// Signal quality in percent: -100 dBm or weaker is 0, -50 dBm or stronger is 100
+    pub fn signal_quality(&self) -> Option<u8> {
+        self.signal_dbm.map(|dbm| (2 * (dbm + 100)).clamp(0, 100) as u8)
+    }
+}
+
+/// Average receive and transmit rates of an interface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Throughput {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+/// Bytes and packets a device exchanged through tracked connections
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceTraffic {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub packets: u64,
+    /// Rates since the previous scan, absent on the first one
+    pub rates: Option<Throughput>,
+}
+
+/// Printer state from IPP `printer-state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrinterState {
+    Idle,
+    Processing,
+    Stopped,
+}
+
+impl PrinterState {
+    pub const ALL: [PrinterState; 3] = [Self::Idle, Self::Processing, Self::Stopped];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Processing => "processing",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+/// Level of one ink or toner supply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarkerLevel {
+    pub name: String,
+    /// Percent remaining, None when the printer cannot tell
+    pub level: Option<u8>,
+}
+
+/// Status reported by a printer over IPP
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrinterStatus {
+    pub model: Option<String>,
+    pub state: Option<PrinterState>,
+    /// IPP keywords such as "media-jam" or "toner-low-report"; "none" is left out
+    pub state_reasons: Vec<String>,
+    pub markers: Vec<MarkerLevel>,
+}
+
+impl PrinterStatus {
+    /// Supplies at or below this percentage count as low
+    pub const LOW_MARKER_LEVEL: u8 = 10;
+
+    /// State reasons, without severity suffix, that need someone at the printer
+    const ATTENTION_REASONS: [&'static str; 9] = [
+        "media-jam", "media-empty", "media-needed", "door-open", "cover-open",
+        "toner-low", "toner-empty", "marker-supply-low", "marker-supply-empty",
+    ];
+
+    /// True when a supply is low or the printer reports a jam, empty tray or open door
+    pub fn needs_attention(&self) -> bool {
+        let reason_applies = self.state_reasons.iter().any(|reason| {
+            let keyword = reason
+                .trim_end_matches("-report")
+                .trim_end_matches("-warning")
+                .trim_end_matches("-error");
+            Self::ATTENTION_REASONS.contains(&keyword)
+        });
+        reason_applies || self.markers.iter().any(MarkerLevel::is_low)
+    }
+}
+
+impl MarkerLevel {
+    pub fn is_low(&self) -> bool {
+        self.level.is_some_and(|level| level <= PrinterStatus::LOW_MARKER_LEVEL)
+    }
+}
+
+/// A port forwarding opened on the router through UPnP
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortMapping {
+    /// "TCP" or "UDP"
+    pub protocol: String,
+    pub external_port: u16,
+    /// LAN address the port is forwarded to, as reported by the router
+    pub internal_client: String,
+    pub internal_port: u16,
+    /// Free text set by the application that opened the port
+    pub description: String,
+    pub enabled: bool,
+    /// Remote address the mapping is limited to; None for any
+    pub remote_host: Option<String>,
+    /// Remaining lease; zero means permanent
+    pub lease_duration: Duration,
+}
+
+impl PortMapping {
+    pub fn internal_ip(&self) -> Option<IpAddr> {
+        self.internal_client.parse().ok()
+    }
+}
+
+/// WAN connection of a UPnP Internet gateway device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgdStatus {
+    pub external_ip: Option<IpAddr>,
+    /// "Connected", "Disconnected", ... as reported by the router
+    pub connection_status: Option<String>,
+    pub uptime: Option<Duration>,
+    pub port_mappings: Vec<PortMapping>,
+}
+
+/// Organization registered for an SNMP enterprise number (1.3.6.1.4.1.N)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Enterprise {
+    pub number: u32,
+    pub vendor: &'static str,
+    /// Set when nearly everything the vendor sells with SNMP is one kind of device
+    pub device_type: Option<DeviceType>,
+}
+
+impl Enterprise {
+    const KNOWN: [Enterprise; 26] = [
+        Self::new(9, "Cisco", Some(DeviceType::Router)),
+        Self::new(11, "HP", None),
+        Self::new(171, "D-Link", Some(DeviceType::Router)),
+        Self::new(253, "Xerox", Some(DeviceType::Printer)),
+        Self::new(311, "Microsoft", Some(DeviceType::Computer)),
+        Self::new(367, "Ricoh", Some(DeviceType::Printer)),
+        Self::new(641, "Lexmark", Some(DeviceType::Printer)),
+        Self::new(674, "Dell", None),
+        Self::new(1248, "Epson", Some(DeviceType::Printer)),
+        Self::new(1347, "Kyocera", Some(DeviceType::Printer)),
+        Self::new(1602, "Canon", Some(DeviceType::Printer)),
+        Self::new(2011, "Huawei", Some(DeviceType::Router)),
+        Self::new(2435, "Brother", Some(DeviceType::Printer)),
+        Self::new(2636, "Juniper", Some(DeviceType::Router)),
+        Self::new(4526, "Netgear", Some(DeviceType::Router)),
+        Self::new(6574, "Synology", Some(DeviceType::NAS)),
+        Self::new(8072, "Net-SNMP", None),
+        Self::new(11863, "TP-Link", Some(DeviceType::Router)),
+        Self::new(12356, "Fortinet", Some(DeviceType::Router)),
+        Self::new(14823, "Aruba", Some(DeviceType::Router)),
+        Self::new(14988, "MikroTik", Some(DeviceType::Router)),
+        Self::new(18334, "Konica Minolta", Some(DeviceType::Printer)),
+        Self::new(24681, "QNAP", Some(DeviceType::NAS)),
+        Self::new(25053, "Ruckus", Some(DeviceType::Router)),
+        Self::new(30065, "Arista", Some(DeviceType::Router)),
+        Self::new(41112, "Ubiquiti", Some(DeviceType::Router)),
+    ];
+
+    const fn new(number: u32, vendor: &'static str, device_type: Option<DeviceType>) -> Self {
+        Self { number, vendor, device_type }
+    }
+
+    /// Looks up a known enterprise number
+    pub fn lookup(number: u32) -> Option<Self> {
+        Self::KNOWN.iter().find(|enterprise| enterprise.number == number).copied()
+    }
+}
+
+/// SNMP system group (sysDescr, sysObjectID, sysName) of a device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnmpInfo {
+    pub sys_descr: Option<String>,
+    /// Dotted OID, e.g. "1.3.6.1.4.1.2435.2.3.9.1"
+    pub sys_object_id: Option<String>,
+    pub sys_name: Option<String>,
+}
+
+impl SnmpInfo {
+    /// Longest model taken from sysDescr
+    const MAX_MODEL_CHARS: usize = 60;
+
+    /// Enterprise from a sysObjectID below 1.3.6.1.4.1, if it is a known one
+    pub fn enterprise(&self) -> Option<Enterprise> {
+        let number = self.sys_object_id.as_ref()?.strip_prefix("1.3.6.1.4.1.")?.split('.').next()?;
+        Enterprise::lookup(number.parse().ok()?)
+    }
+
+    /// sysDescr up to the first comma or semicolon, where firmware details usually start
+    pub fn model(&self) -> Option<String> {
+        let descr = self.sys_descr.as_ref()?;
+        let model: String = descr.split([',', ';']).next()?.trim().chars().take(Self::MAX_MODEL_CHARS).collect();
+        (!model.is_empty()).then_some(model)
+    }
+}
+
 /// Network interface on this machine
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkInterface {
     pub name: InterfaceName,
     pub ip: IpAddr,
     pub mac: Option<MacAddress>,
+    /// Link details, present for connected wireless interfaces
+    #[serde(default)]
+    pub wifi: Option<WifiLink>,
+    /// Rates since the previous scan, absent on the first one
+    #[serde(default)]
+    pub throughput: Option<Throughput>,
 }
 
 impl NetworkInterface {
     pub fn new(name: InterfaceName, ip: IpAddr, mac: Option<MacAddress>) -> Self {
-        Self { name, ip, mac }
+        Self { name, ip, mac, wifi: None, throughput: None }
+    }
+
+    /// Attaches wireless link details to the interface
+    pub fn with_wifi(mut self, wifi: Option<WifiLink>) -> Self {
+        self.wifi = wifi;
+        self
     }
 }
 
@@ -724,6 +1216,216 @@ impl fmt::Display for Gateway {
     }
 }
 
+/// Discovery step that contributes to a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DiscoverySource {
+    Interfaces,
+    PingSweep,
+    Arp,
+    Mdns,
+    Ssdp,
+    ReverseDns,
+    Route,
+    ResolvConf,
+    GatewayHealth,
+    Connectivity,
+    Wifi,
+    PortProbe,
+    Banners,
+    Ipp,
+    Snmp,
+    Igd,
+    NatPmp,
+}
+
+impl DiscoverySource {
+    pub const ALL: [DiscoverySource; 17] = [
+        Self::Interfaces,
+        Self::PingSweep,
+        Self::Arp,
+        Self::Mdns,
+        Self::Ssdp,
+        Self::ReverseDns,
+        Self::Route,
+        Self::ResolvConf,
+        Self::GatewayHealth,
+        Self::Connectivity,
+        Self::Wifi,
+        Self::PortProbe,
+        Self::Banners,
+        Self::Ipp,
+        Self::Snmp,
+        Self::Igd,
+        Self::NatPmp,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Interfaces => "interfaces",
+            Self::PingSweep => "ping_sweep",
+            Self::Arp => "arp",
+            Self::Mdns => "mdns",
+            Self::Ssdp => "ssdp",
+            Self::ReverseDns => "reverse_dns",
+            Self::Route => "route",
+            Self::ResolvConf => "resolv_conf",
+            Self::GatewayHealth => "gateway_health",
+            Self::Connectivity => "connectivity",
+            Self::Wifi => "wifi",
+            Self::PortProbe => "port_probe",
+            Self::Banners => "banners",
+            Self::Ipp => "ipp",
+            Self::Snmp => "snmp",
+            Self::Igd => "igd",
+            Self::NatPmp => "nat_pmp",
+        }
+    }
+}
+
+impl fmt::Display for DiscoverySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Duration and outcome of one discovery source during a scan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceReport {
+    pub source: DiscoverySource,
+    pub duration: Duration,
+    /// Error message if the source failed; the scan may still have succeeded
+    pub error: Option<String>,
+}
+
+/// How and when a snapshot was collected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanMetadata {
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub sources: Vec<SourceReport>,
+}
+
+impl ScanMetadata {
+    /// Sources that reported an error during the scan
+    pub fn failed_sources(&self) -> impl Iterator<Item = &SourceReport> {
+        self.sources.iter().filter(|report| report.error.is_some())
+    }
+}
+
+/// Result of checking whether an upstream host accepts connections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamCheck {
+    /// Host or DNS name with port, as configured
+    pub target: String,
+    pub reachable: bool,
+    /// Time to establish the connection
+    pub latency: Option<Duration>,
+    pub error: Option<String>,
+}
+
+/// Protocol the gateway answered for external address discovery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NatProtocol {
+    NatPmp,
+    Pcp,
+}
+
+impl NatProtocol {
+    pub const ALL: [NatProtocol; 2] = [Self::NatPmp, Self::Pcp];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NatPmp => "NAT-PMP",
+            Self::Pcp => "PCP",
+        }
+    }
+
+    /// Stable identifier used in exports
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::NatPmp => "nat_pmp",
+            Self::Pcp => "pcp",
+        }
+    }
+}
+
+/// External address and mapping epoch reported by the gateway over NAT-PMP or PCP
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayNat {
+    pub protocol: NatProtocol,
+    pub external_ip: IpAddr,
+    /// Time since the gateway's mapping state started, usually its uptime
+    pub epoch: Duration,
+}
+
+/// Gateway round-trip time, packet loss and upstream reachability
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayHealth {
+    pub probes_sent: u32,
+    pub replies: u32,
+    /// Average round-trip time of the replies
+    pub latency: Option<Duration>,
+    pub upstream: Option<UpstreamCheck>,
+}
+
+impl GatewayHealth {
+    /// Percentage of probes without a reply
+    pub fn loss_percent(&self) -> f64 {
+        if self.probes_sent == 0 {
+            return 0.0;
+        }
+        f64::from(self.probes_sent - self.replies.min(self.probes_sent)) * 100.0 / f64::from(self.probes_sent)
+    }
+
+    /// The gateway answered at least one probe
+    pub fn is_responding(&self) -> bool {
+        self.replies > 0
+    }
+
+    /// Probes went out; false when ping could not be run or its output not understood
+    pub fn was_probed(&self) -> bool {
+        self.probes_sent > 0
+    }
+}
+
+/// Internet access as seen through a connectivity check URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectivityStatus {
+    Online,
+    /// Requests are redirected or answered by a captive portal
+    Captive,
+    Offline,
+}
+
+impl ConnectivityStatus {
+    pub const ALL: [ConnectivityStatus; 3] = [Self::Online, Self::Captive, Self::Offline];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Online => "online",
+            Self::Captive => "captive",
+            Self::Offline => "offline",
+        }
+    }
+}
+
+impl fmt::Display for ConnectivityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Result of a "generate_204"-style connectivity check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectivityCheck {
+    pub url: String,
+    pub status: ConnectivityStatus,
+    /// Where a captive portal redirected the request, if it said
+    pub portal_url: Option<String>,
+    /// Why the check was not classified as online
+    pub detail: Option<String>,
+}
+
 /// Complete network snapshot at a point in time
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkSnapshot {
@@ -731,6 +1433,18 @@ pub struct NetworkSnapshot {
     pub devices: Vec<NetworkDevice>,
     pub gateway: Option<Gateway>,
     pub dns_servers: Vec<IpAddr>,
+    /// Scan timing, absent for snapshots not produced by the collector
+    #[serde(default)]
+    pub scan: Option<ScanMetadata>,
+    /// Gateway probe results, absent if the gateway could not be probed
+    #[serde(default)]
+    pub gateway_health: Option<GatewayHealth>,
+    /// Connectivity check result, absent unless a check URL is configured
+    #[serde(default)]
+    pub connectivity: Option<ConnectivityCheck>,
+    /// NAT-PMP or PCP answer from the gateway, absent unless queried
+    #[serde(default)]
+    pub gateway_nat: Option<GatewayNat>,
 }
 
 impl NetworkSnapshot {
@@ -745,9 +1459,49 @@ impl NetworkSnapshot {
             devices,
             gateway,
             dns_servers,
+            scan: None,
+            gateway_health: None,
+            connectivity: None,
+            gateway_nat: None,
         }
     }
 
+    /// Attaches scan metadata to the snapshot
+    pub fn with_scan(mut self, scan: ScanMetadata) -> Self {
+        self.scan = Some(scan);
+        self
+    }
+
+    /// Attaches a connectivity check result to the snapshot
+    pub fn with_connectivity(mut self, connectivity: Option<ConnectivityCheck>) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    /// Attaches the gateway's NAT-PMP or PCP answer to the snapshot
+    pub fn with_gateway_nat(mut self, nat: Option<GatewayNat>) -> Self {
+        self.gateway_nat = nat;
+        self
+    }
+
+    /// Attaches gateway probe results to the snapshot
+    pub fn with_gateway_health(mut self, health: Option<GatewayHealth>) -> Self {
+        self.gateway_health = health;
+        self
+    }
+
+    /// Summed rates of all non-loopback interfaces, if any were measured
+    pub fn total_throughput(&self) -> Option<Throughput> {
+        self.interfaces
+            .iter()
+            .filter(|interface| !interface.ip.is_loopback())
+            .filter_map(|interface| interface.throughput)
+            .reduce(|total, rates| Throughput {
+                rx_bytes_per_sec: total.rx_bytes_per_sec + rates.rx_bytes_per_sec,
+                tx_bytes_per_sec: total.tx_bytes_per_sec + rates.tx_bytes_per_sec,
+            })
+    }
+
     /// Groups devices by their interface name
     pub fn devices_by_interface(&self) -> std::collections::HashMap<InterfaceName, Vec<&NetworkDevice>> {
         self.devices.iter().fold(std::collections::HashMap::new(), |mut map, device| {
@@ -757,6 +1511,45 @@ impl NetworkSnapshot {
             map
         })
     }
+
+    /// The device acting as default gateway, if it was discovered
+    pub fn gateway_device(&self) -> Option<&NetworkDevice> {
+        let gateway = self.gateway?;
+        self.devices.iter().find(|d| d.ip == gateway.0)
+    }
+
+    /// Finds a device by IP address, MAC address or (partial) name
+    /// Names are matched case-insensitively against hostname and identity
+    pub fn find_device(&self, query: &str) -> Option<&NetworkDevice> {
+        // Prefer an exact name match over a substring match
+        self.find_device_exact(query).or_else(|| {
+            let query_lower = query.to_lowercase();
+            self.devices.iter().find(|d| device_names(d).iter().flatten().any(|n| n.contains(&query_lower)))
+        })
+    }
+
+    /// Finds a device by IP address, MAC address or full name, never by substring
+    pub fn find_device_exact(&self, query: &str) -> Option<&NetworkDevice> {
+        if let Ok(ip) = query.parse::<IpAddr>() {
+            return self.devices.iter().find(|d| d.ip == ip);
+        }
+        if let Ok(mac) = MacAddress::new(query.to_string()) {
+            return self.devices.iter().find(|d| d.mac == mac);
+        }
+
+        let query_lower = query.to_lowercase();
+        self.devices.iter().find(|d| device_names(d).iter().flatten().any(|n| *n == query_lower))
+    }
+}
+
+/// Lowercased names a device can be looked up by
+fn device_names(device: &NetworkDevice) -> [Option<String>; 3] {
+    let hostname = match &device.hostname {
+        Hostname::Resolved(name) => Some(name.to_lowercase()),
+        _ => None,
+    };
+    let friendly = device.identity.friendly_name.as_ref().map(|n| n.as_str().to_lowercase());
+    [hostname, friendly, Some(device.identity.name().to_lowercase())]
 }
 
 // For backward compatibility with existing code
@@ -788,6 +1581,13 @@ mod tests {
         assert_eq!(format!("{}", mac.unwrap()), "AA:BB:CC:DD:EE:FF");
     }
 
+    #[test]
+    fn test_mac_address_octets() {
+        let mac = MacAddress::new("aa:bb:cc:01:02:ff".to_string()).unwrap();
+        assert_eq!(mac.octets(), [0xAA, 0xBB, 0xCC, 0x01, 0x02, 0xFF]);
+        assert_eq!(mac.to_compact(), "aabbcc0102ff");
+    }
+
     #[test]
     fn test_mac_address_invalid_length() {
         let mac = MacAddress::new("AA:BB:CC".to_string());
@@ -800,6 +1600,14 @@ mod tests {
         assert!(mac.is_err());
     }
 
+    #[test]
+    fn test_device_type_ids_round_trip() {
+        for device_type in DeviceType::ALL {
+            assert_eq!(DeviceType::from_id(device_type.id()), Some(device_type));
+        }
+        assert_eq!(DeviceType::from_id("toaster"), None);
+    }
+
     #[test]
     fn test_hostname_states() {
         assert_eq!(
@@ -862,4 +1670,47 @@ mod tests {
         assert_eq!(by_interface.get(&InterfaceName::new("eth0".to_string())).unwrap().len(), 1);
         assert_eq!(by_interface.get(&InterfaceName::new("wlan0".to_string())).unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_build_identity_records_evidence() {
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 60));
+        let mac = MacAddress::new("AA:BB:CC:DD:EE:01".to_string()).unwrap();
+        let mut device = NetworkDevice::new(ip, mac, InterfaceName::new("eth0".to_string()));
+        device.hostname = Hostname::resolved("office-printer".to_string());
+        device.services.push(ServiceInfo::new(
+            ServiceType::new("_ipp._tcp.local.".to_string()),
+            ServiceInstanceName::new("Office._ipp._tcp.local.".to_string()),
+            631,
+        ));
+        device.build_identity();
+
+        assert_eq!(device.identity.device_type, DeviceType::Printer);
+        assert_eq!(device.identity.evidence[0].source, EvidenceSource::Mdns);
+        assert!(device.identity.evidence.iter()
+            .any(|e| e.source == EvidenceSource::Hostname && e.detail.contains("office-printer")));
+    }
+
+    #[test]
+    fn test_find_device() {
+        let ip1 = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50));
+        let ip2 = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 51));
+        let mac1 = MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap();
+        let mac2 = MacAddress::new("11:22:33:44:55:66".to_string()).unwrap();
+
+        let device1 = NetworkDevice::new(ip1, mac1, InterfaceName::new("eth0".to_string()));
+        let mut device2 = NetworkDevice::new(ip2, mac2, InterfaceName::new("eth0".to_string()));
+        device2.hostname = Hostname::resolved("nas.lan".to_string());
+        device2.build_identity();
+
+        let snapshot = NetworkSnapshot::new(vec![], vec![device1, device2], None, vec![]);
+
+        assert_eq!(snapshot.find_device("192.168.1.50").unwrap().ip, ip1);
+        assert_eq!(snapshot.find_device("11-22-33-44-55-66").unwrap().ip, ip2);
+        assert_eq!(snapshot.find_device("NAS").unwrap().ip, ip2);
+        assert!(snapshot.find_device("192.168.1.99").is_none());
+        assert!(snapshot.find_device("toaster").is_none());
+
+        assert_eq!(snapshot.find_device_exact("nas.lan").unwrap().ip, ip2);
+        assert!(snapshot.find_device_exact("nas").is_none());
+    }
 }
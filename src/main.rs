@@ -3,24 +3,291 @@
 
 #![allow(clippy::upper_case_acronyms)] // NAS is standard industry acronym
 
+mod cli;
 mod data;
 mod display;
 mod domain;
+mod menu;
 
 use anyhow::Result;
-use data::NetworkCollector;
-use display::WaybarFormatter;
+use clap::Parser;
+use cli::{Cli, Command, DiffFormat, OutputBackend};
+use data::port_probe::PortProbe;
+use data::snmp::SnmpProbe;
+use data::{NetworkCollector, SysrootCollector};
+use display::{
+    DiffFormatter, DisplayOptions, EwwFormatter, I3barFormatter, I3blocksFormatter, MetricsRegistry,
+    MetricsServer, OutputFormatter, PolybarFormatter, TerminalFormatter, TextFormatter, WaybarFormatter,
+};
+use domain::{IconSet, NetworkData};
+use std::io::Write;
 
 fn main() -> Result<()> {
-    let collector = NetworkCollector::new()?;
-    let formatter = WaybarFormatter::new();
+    let cli = Cli::parse();
+    let collector: Box<dyn Fn() -> Result<NetworkData>> = match &cli.sysroot {
+        Some(root) => {
+            let collector = configure(SysrootCollector::from_sysroot(root), &cli);
+            Box::new(move || collector.collect_network_info())
+        }
+        None => {
+            let collector = configure(NetworkCollector::new()?, &cli);
+            Box::new(move || collector.collect_network_info())
+        }
+    };
+
+    match cli.command.clone().unwrap_or(Command::Bar) {
+        Command::Bar => run_bar(&cli, &collector)?,
+        Command::List => {
+            let data = snapshot(&cli, &collector)?;
+            println!("{}", TextFormatter::with_options(&display_options(&cli)).format_list(&data));
+        }
+        Command::Show { query } => {
+            let data = snapshot(&cli, &collector)?;
+            let device = data.find_device(&query)
+                .ok_or_else(|| anyhow::anyhow!("No device matching '{}'", query))?;
+            println!("{}", TextFormatter::with_options(&display_options(&cli)).format_device(device, &data));
+        }
+        Command::Json => {
+            let data = snapshot(&cli, &collector)?;
+            println!("{}", data::export::to_export_json(&data)?);
+        }
+        Command::Tooltip => {
+            let data = snapshot(&cli, &collector)?;
+            println!("{}", TerminalFormatter::with_options(&display_options(&cli)).format(&data));
+        }
+        Command::Menu { launcher } => {
+            let launcher = match launcher {
+                Some(command) => command.split_whitespace().map(str::to_string).collect(),
+                None => menu::detect_launcher()
+                    .ok_or_else(|| anyhow::anyhow!("No launcher found; install rofi, wofi, fuzzel or dmenu"))?,
+            };
+            let data = snapshot(&cli, &collector)?;
+            menu::run(&data, &launcher, &display_options(&cli).icons)?;
+        }
+        Command::Wake { target } => println!("{}", wake(&cli, &target)?),
+        Command::Diff { old, new, format } => {
+            let old = data::export::import_snapshot(&old)?;
+            let new = data::export::import_snapshot(&new)?;
+            let diff = domain::SnapshotDiff::between(&old, &new);
+            let formatter = DiffFormatter::with_options(&display_options(&cli));
+            match format {
+                DiffFormat::Text => println!("{}", formatter.format_text(&diff)),
+                DiffFormat::Json => println!("{}", formatter.format_json(&diff)?),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Enables the opt-in discovery steps selected on the command line
+fn configure<I, N, R, D, M, S>(mut collector: NetworkCollector<I, N, R, D, M, S>, cli: &Cli)
+    -> NetworkCollector<I, N, R, D, M, S> {
+    if let Some(target) = &cli.upstream {
+        collector = collector.with_upstream(target.clone());
+    }
+    if let Some(url) = &cli.connectivity_check {
+        collector = collector.with_connectivity_check(url.clone());
+    }
+    if cli.grab_banners {
+        collector = collector.with_banner_grabbing();
+    }
+    if cli.printer_status {
+        collector = collector.with_printer_status();
+    }
+    if cli.nat_pmp {
+        collector = collector.with_nat_pmp();
+    }
+    if cli.igd {
+        collector = collector.with_gateway_queries();
+    }
+    if let Some(communities) = &cli.snmp {
+        collector = collector.with_snmp(SnmpProbe::new(communities.clone()));
+    }
+    if let Some(ports) = &cli.probe_ports {
+        collector = collector.with_port_probe(PortProbe {
+            concurrency: cli.probe_concurrency.get(),
+            rate: cli.probe_rate.get(),
+            ..PortProbe::new(ports.clone())
+        });
+    }
+    collector
+}
+
+/// Prints bar updates in the selected output format, once or in watch mode
+fn run_bar(cli: &Cli, collector: &dyn Fn() -> Result<NetworkData>) -> Result<()> {
+    let options = display_options(cli);
+    let formatter: Box<dyn OutputFormatter> = match cli.output {
+        OutputBackend::Waybar => Box::new(WaybarFormatter::with_options(&options)),
+        OutputBackend::I3bar => Box::new(I3barFormatter::with_options(&options)),
+        OutputBackend::I3blocks if cli.watch => {
+            Box::new(I3blocksFormatter::with_options(&options).with_persistent_output())
+        }
+        OutputBackend::I3blocks => Box::new(I3blocksFormatter::with_options(&options)),
+        OutputBackend::Polybar => Box::new(PolybarFormatter::with_options(&options)),
+        OutputBackend::Eww => Box::new(EwwFormatter::with_options(&options)),
+    };
+
+    let metrics_server = cli.metrics_addr.map(MetricsServer::start).transpose()?;
+    if let Some(server) = &metrics_server {
+        eprintln!("Serving metrics on http://{}/metrics", server.local_addr());
+    }
+    let mut metrics = MetricsRegistry::new();
+
+    let mut stdout = std::io::stdout();
+    if let Some(header) = formatter.header() {
+        writeln!(stdout, "{}", header)?;
+    }
+
+    loop {
+        let network_data = snapshot(cli, collector);
+
+        if metrics_server.is_some() || cli.textfile.is_some() {
+            match &network_data {
+                Ok(data) => metrics.update(data),
+                Err(_) => metrics.record_failed_scan(),
+            }
+            let rendered = metrics.render();
+            // A full disk or a removed directory must not stop the bar
+            if let Some(path) = &cli.textfile
+                && let Err(e) = display::write_textfile(path, &rendered)
+            {
+                eprintln!("Warning: {:#}", e);
+            }
+            if let Some(server) = &metrics_server {
+                server.publish(rendered);
+            }
+        }
+
+        let update = match network_data {
+            Ok(data) => formatter.render(&data)?,
+            Err(e) => formatter.render_error(e)?,
+        };
+        writeln!(stdout, "{}", update)?;
+        stdout.flush()?;
+
+        if !cli.watch {
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_secs(cli.interval));
+    }
+}
 
+/// Display settings from the command line, falling back to the default templates
+fn display_options(cli: &Cli) -> DisplayOptions {
+    let defaults = DisplayOptions::default();
+    DisplayOptions {
+        text_template: cli.text_template.clone().unwrap_or(defaults.text_template),
+        device_template: cli.device_template.clone().unwrap_or(defaults.device_template),
+        icons: cli.icon_overrides
+            .iter()
+            .fold(IconSet::new(cli.icons), |icons, (device_type, icon)| {
+                icons.with_override(*device_type, icon.clone())
+            }),
+        group_by: cli.group_by,
+        sort_by: cli.sort_by,
+        max_lines: cli.max_lines.map(|lines| lines.get()),
+        hide_stale: cli.hide_stale,
+        hide_unnamed: cli.hide_unnamed,
+        critical_devices: cli.critical_devices.clone(),
+    }
+}
+
+/// Loads the snapshot from `--import`, or scans and saves it to `--export`
+fn snapshot(cli: &Cli, collector: &dyn Fn() -> Result<NetworkData>) -> Result<NetworkData> {
+    if let Some(path) = &cli.import {
+        return data::export::import_snapshot(path);
+    }
+
+    // Watch mode refreshes on its own schedule, so only retry one-shot runs
+    let mut data = if cli.watch {
+        collector()?
+    } else {
+        collect_with_retry(collector)?
+    };
+
+    // Device history is best effort; a broken state file must not hide the scan
+    if let Some(state_dir) = recorded_state_dir(cli) {
+        if let Err(e) = record_devices(&state_dir, &mut data) {
+            eprintln!("Warning: {:#}", e);
+        }
+        if let Err(e) = data::traffic::record_throughput(sysroot(cli), &state_dir, &mut data) {
+            eprintln!("Warning: {:#}", e);
+        }
+        if cli.conntrack
+            && let Err(e) = data::conntrack::record_device_traffic(sysroot(cli), &state_dir, &mut data)
+        {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+
+    if let Some(path) = &cli.export {
+        data::export::export_snapshot(&data, path)?;
+    }
+    Ok(data)
+}
+
+/// Marks new devices and updates the device store in the state directory
+fn record_devices(state_dir: &std::path::Path, data: &mut NetworkData) -> Result<()> {
+    let mut store = data::store::DeviceStore::load(state_dir)?;
+    if store.record(data) {
+        store.save()?;
+    }
+    Ok(())
+}
+
+/// Wakes a device from the device store; unknown MAC addresses get an unbound broadcast
+fn wake(cli: &Cli, target: &str) -> Result<String> {
+    let store = data::store::DeviceStore::load(&state_dir(cli))?;
+    let (mac, interface) = match store.find(target)? {
+        Some(device) => (device.mac.clone(), Some(device.interface.clone())),
+        None => {
+            let mac = domain::MacAddress::new(target.to_string())
+                .map_err(|_| anyhow::anyhow!("No device matching '{}' in the device store", target))?;
+            (mac, None)
+        }
+    };
+
+    let report = data::wol::wake(&mac, interface.as_ref())?;
+    let udp = match &report.bind_error {
+        Some(error) => format!("UDP broadcast not bound to the interface: {}", error),
+        None => "UDP broadcast".to_string(),
+    };
+    let via = match (&report.interface, report.raw_ethernet) {
+        (Some(interface), true) => format!(" on {} ({} and Ethernet frame)", interface.as_str(), udp),
+        (Some(interface), false) => format!(" on {} ({})", interface.as_str(), udp),
+        (None, _) => format!(" ({})", udp),
+    };
+    Ok(format!("Sent magic packet to {}{}", mac, via))
+}
+
+/// State directory from `--state-dir`, or the XDG default
+fn state_dir(cli: &Cli) -> std::path::PathBuf {
+    cli.state_dir.clone().unwrap_or_else(data::store::default_state_dir)
+}
+
+/// State directory scans are recorded in; none for `--sysroot` runs without an explicit
+/// `--state-dir`, so fixture devices and counters stay out of the live history
+fn recorded_state_dir(cli: &Cli) -> Option<std::path::PathBuf> {
+    match (&cli.sysroot, &cli.state_dir) {
+        (Some(_), None) => None,
+        _ => Some(state_dir(cli)),
+    }
+}
+
+/// Directory the /proc files are read from: `--sysroot`, or the live system
+fn sysroot(cli: &Cli) -> &std::path::Path {
+    cli.sysroot.as_deref().unwrap_or(std::path::Path::new("/"))
+}
+
+/// Collects a snapshot, retrying while no devices have been found yet
+fn collect_with_retry(collector: &dyn Fn() -> Result<NetworkData>) -> Result<NetworkData> {
     // Exponential backoff: initial attempt, then retry after 1s, 2s, 4s, 8s
     // Total: 5 attempts, up to 15 seconds of delays
     let retry_delays_secs = [1u64, 2, 4, 8];
     let total_attempts = retry_delays_secs.len() + 1;
 
-    let network_data = std::iter::once(None)
+    std::iter::once(None)
         .chain(retry_delays_secs.iter().map(|&delay| Some(delay)))
         .enumerate()
         .find_map(|(attempt, delay_option)| {
@@ -29,7 +296,7 @@ fn main() -> Result<()> {
                 std::thread::sleep(std::time::Duration::from_secs(delay_secs));
             }
 
-            match collector.collect_network_info() {
+            match collector() {
                 // Success with devices found - return immediately
                 Ok(data) if !data.devices.is_empty() => Some(Ok(data)),
 
@@ -46,21 +313,8 @@ fn main() -> Result<()> {
         .unwrap_or_else(|| {
             // Safety: Should never reach here as last attempt always returns Some
             // Include fallback for absolute safety
-            collector.collect_network_info()
-        });
-
-    match network_data {
-        Ok(data) => {
-            let output = formatter.format(&data)?;
-            println!("{}", serde_json::to_string(&output)?);
-        }
-        Err(e) => {
-            let error_output = WaybarFormatter::create_error_output(e);
-            println!("{}", serde_json::to_string(&error_output)?);
-        }
-    }
-
-    Ok(())
+            collector()
+        })
 }
 
 #[cfg(test)]
@@ -70,7 +324,7 @@ mod integration_tests {
     #[test]
     fn test_error_handling_flow() {
         let error = anyhow::anyhow!("Test error");
-        let error_output = WaybarFormatter::create_error_output(error);
+        let error_output = WaybarFormatter::new().error_output(error);
 
         assert!(error_output.text.contains("unavailable"));
         assert!(error_output.tooltip.contains("Test error"));
@@ -80,4 +334,16 @@ mod integration_tests {
         assert!(json.contains("text"));
         assert!(json.contains("tooltip"));
     }
+
+    #[test]
+    fn test_sysroot_runs_keep_out_of_state_dir() {
+        let cli = Cli::try_parse_from(["waybar_lan", "list", "--sysroot", "fixtures"]).unwrap();
+        assert_eq!(recorded_state_dir(&cli), None);
+
+        let cli = Cli::try_parse_from(["waybar_lan", "list", "--sysroot", "fixtures", "--state-dir", "/tmp/fixture"]).unwrap();
+        assert_eq!(recorded_state_dir(&cli), Some(std::path::PathBuf::from("/tmp/fixture")));
+
+        let cli = Cli::try_parse_from(["waybar_lan", "list"]).unwrap();
+        assert_eq!(recorded_state_dir(&cli), Some(state_dir(&cli)));
+    }
 }
@@ -0,0 +1,271 @@
+//! Device menu for dmenu-compatible launchers (rofi, wofi, fuzzel, dmenu).
+
+use crate::data::wol;
+use crate::display::Markup;
+use crate::domain::{IconSet, InterfaceName, MacAddress, NetworkData, NetworkDevice};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+
+/// Launchers tried in order when none is configured
+const KNOWN_LAUNCHERS: [&[&str]; 4] = [
+    &["rofi", "-dmenu", "-i", "-p", "LAN"],
+    &["wofi", "--dmenu", "--prompt", "LAN"],
+    &["fuzzel", "--dmenu", "--prompt", "LAN "],
+    &["dmenu", "-i", "-p", "LAN"],
+];
+
+/// An action offered for the selected device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuAction {
+    CopyIp(IpAddr),
+    CopyMac(MacAddress),
+    OpenUrl(String),
+    Ssh { ip: IpAddr, port: u16 },
+    OpenSmb(IpAddr),
+    WakeOnLan { mac: MacAddress, interface: InterfaceName },
+}
+
+impl MenuAction {
+    /// Label shown in the launcher
+    pub fn label(&self) -> String {
+        match self {
+            Self::CopyIp(ip) => format!("Copy IP ({})", ip),
+            Self::CopyMac(mac) => format!("Copy MAC ({})", mac),
+            Self::OpenUrl(url) => format!("Open web UI ({})", url),
+            Self::Ssh { ip, port: 22 } => format!("SSH to {}", ip),
+            Self::Ssh { ip, port } => format!("SSH to {} port {}", ip, port),
+            Self::OpenSmb(ip) => format!("Open SMB shares (smb://{}/)", ip),
+            Self::WakeOnLan { mac, .. } => format!("Wake-on-LAN ({})", mac),
+        }
+    }
+
+    /// Runs the action
+    pub fn perform(&self) -> Result<()> {
+        match self {
+            Self::CopyIp(ip) => copy_to_clipboard(&ip.to_string()),
+            Self::CopyMac(mac) => copy_to_clipboard(&mac.to_string()),
+            Self::OpenUrl(url) => spawn_detached(&["xdg-open", url]),
+            Self::Ssh { ip, port } => {
+                let terminal = std::env::var("TERMINAL").unwrap_or_else(|_| "xterm".to_string());
+                spawn_detached(&[&terminal, "-e", "ssh", "-p", &port.to_string(), &ip.to_string()])
+            }
+            Self::OpenSmb(ip) => spawn_detached(&["xdg-open", &format!("smb://{}/", ip)]),
+            Self::WakeOnLan { mac, interface } => wol::wake(mac, Some(interface)).map(|_| ()),
+        }
+    }
+}
+
+/// Actions available for a device, based on its discovered services
+pub fn actions_for(device: &NetworkDevice) -> Vec<MenuAction> {
+    let mut actions = vec![
+        MenuAction::CopyIp(device.ip),
+        MenuAction::CopyMac(device.mac.clone()),
+    ];
+
+    let host = match device.ip {
+        IpAddr::V6(ip) => format!("[{}]", ip),
+        IpAddr::V4(ip) => ip.to_string(),
+    };
+    let has = |prefix: &'static str| {
+        device.services.iter().filter(move |s| s.service_type.as_str().starts_with(prefix))
+    };
+
+    for service in has("_http._tcp") {
+        let url = match service.port {
+            80 => format!("http://{}/", host),
+            port => format!("http://{}:{}/", host, port),
+        };
+        actions.push(MenuAction::OpenUrl(url));
+    }
+    for service in has("_https._tcp") {
+        let url = match service.port {
+            443 => format!("https://{}/", host),
+            port => format!("https://{}:{}/", host, port),
+        };
+        actions.push(MenuAction::OpenUrl(url));
+    }
+    if let Some(service) = has("_ssh._tcp").next() {
+        actions.push(MenuAction::Ssh { ip: device.ip, port: service.port });
+    }
+    if has("_smb._tcp").next().is_some() {
+        actions.push(MenuAction::OpenSmb(device.ip));
+    }
+
+    actions.dedup();
+    actions.push(MenuAction::WakeOnLan {
+        mac: device.mac.clone(),
+        interface: device.interface_name.clone(),
+    });
+    actions
+}
+
+/// One launcher line per device, sorted by IP address
+pub fn device_entries<'a>(network_data: &'a NetworkData, icons: &IconSet) -> Vec<(String, &'a NetworkDevice)> {
+    let mut devices: Vec<&NetworkDevice> = network_data.devices.iter().collect();
+    devices.sort_by_key(|d| d.ip);
+    devices
+        .into_iter()
+        .map(|device| {
+            // Names come from the network; keep each entry on one line
+            let label = Markup::from(format!("{}  {}", device.identity.format_with(icons), device.ip)).to_plain();
+            (label, device)
+        })
+        .collect()
+}
+
+/// Finds the first known launcher on PATH
+pub fn detect_launcher() -> Option<Vec<String>> {
+    KNOWN_LAUNCHERS
+        .iter()
+        .find(|launcher| is_on_path(launcher[0]))
+        .map(|launcher| launcher.iter().map(|s| s.to_string()).collect())
+}
+
+fn is_on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Shows choices in the launcher and returns the selected index
+/// Returns None if the user dismissed the launcher
+pub fn choose(launcher: &[String], choices: &[String]) -> Result<Option<usize>> {
+    let (program, args) = launcher.split_first().context("Empty launcher command")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start launcher {}", program))?;
+
+    // Launchers may exit before reading every choice
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(choices.join("\n").as_bytes())
+        && e.kind() != std::io::ErrorKind::BrokenPipe
+    {
+        return Err(e.into());
+    }
+
+    let output = child.wait_with_output()?;
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let selection = selection.trim_end_matches('\n');
+    Ok(choices.iter().position(|choice| choice == selection))
+}
+
+/// Runs the full menu: pick a device, then pick an action for it
+pub fn run(network_data: &NetworkData, launcher: &[String], icons: &IconSet) -> Result<()> {
+    let entries = device_entries(network_data, icons);
+    let labels: Vec<String> = entries.iter().map(|(label, _)| label.clone()).collect();
+    let Some(index) = choose(launcher, &labels)? else { return Ok(()) };
+
+    let actions = actions_for(entries[index].1);
+    let labels: Vec<String> = actions.iter().map(MenuAction::label).collect();
+    let Some(index) = choose(launcher, &labels)? else { return Ok(()) };
+
+    actions[index].perform()
+}
+
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let candidates: [&[&str]; 2] = [&["wl-copy"], &["xclip", "-selection", "clipboard"]];
+    for command in candidates {
+        if !is_on_path(command[0]) {
+            continue;
+        }
+        let mut child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        child.wait()?;
+        return Ok(());
+    }
+    anyhow::bail!("No clipboard tool found (install wl-clipboard or xclip)")
+}
+
+fn spawn_detached(command: &[&str]) -> Result<()> {
+    Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command[0]))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{InterfaceName, ServiceInfo, ServiceInstanceName, ServiceType};
+    use std::net::Ipv4Addr;
+
+    fn device_with_services(services: &[(&str, u16)]) -> NetworkDevice {
+        let mut device = NetworkDevice::new(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5)),
+            MacAddress::new("AA:BB:CC:DD:EE:FF".to_string()).unwrap(),
+            InterfaceName::new("eth0".to_string()),
+        );
+        device.services = services
+            .iter()
+            .map(|(service_type, port)| ServiceInfo::new(
+                ServiceType::new(service_type.to_string()),
+                ServiceInstanceName::new(format!("nas.{}", service_type)),
+                *port,
+            ))
+            .collect();
+        device
+    }
+
+    #[test]
+    fn test_actions_from_services() {
+        let device = device_with_services(&[
+            ("_http._tcp.local.", 5000),
+            ("_https._tcp.local.", 443),
+            ("_ssh._tcp.local.", 22),
+            ("_smb._tcp.local.", 445),
+        ]);
+        let labels: Vec<String> = actions_for(&device).iter().map(MenuAction::label).collect();
+
+        assert_eq!(labels, vec![
+            "Copy IP (192.168.1.5)",
+            "Copy MAC (AA:BB:CC:DD:EE:FF)",
+            "Open web UI (http://192.168.1.5:5000/)",
+            "Open web UI (https://192.168.1.5/)",
+            "SSH to 192.168.1.5",
+            "Open SMB shares (smb://192.168.1.5/)",
+            "Wake-on-LAN (AA:BB:CC:DD:EE:FF)",
+        ]);
+    }
+
+    #[test]
+    fn test_actions_without_services() {
+        let actions = actions_for(&device_with_services(&[]));
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(&actions[2], MenuAction::WakeOnLan { interface, .. } if interface.as_str() == "eth0"));
+    }
+
+    #[test]
+    fn test_device_entries_use_icon_theme() {
+        let data = NetworkData::new(vec![], vec![device_with_services(&[])], None, vec![]);
+        let icons = IconSet::new(crate::domain::IconTheme::Ascii);
+        let entries = device_entries(&data, &icons);
+
+        assert_eq!(entries[0].0, "[?] Device  192.168.1.5");
+    }
+
+    #[test]
+    fn test_choose_with_dmenu_compatible_command() {
+        // `head -n 1` behaves like a launcher that picks the first entry
+        let launcher = vec!["head".to_string(), "-n".to_string(), "1".to_string()];
+        let choices = vec!["first".to_string(), "second".to_string()];
+        assert_eq!(choose(&launcher, &choices).unwrap(), Some(0));
+
+        // A launcher that prints nothing means the menu was dismissed
+        let dismissed = vec!["true".to_string()];
+        assert_eq!(choose(&dismissed, &choices).unwrap(), None);
+    }
+}